@@ -0,0 +1,45 @@
+# Rules applied to the apps using the library, so that minifying them doesn't
+# break what the native library reaches through JNI by name
+
+# Native methods are bound to Noir$Companion by their names
+-keep class com.noirandroid.lib.Noir$Companion {
+    native <methods>;
+}
+
+# Callbacks called from native code
+-keep class com.noirandroid.lib.ForeignCallBridge {
+    java.lang.String resolve(java.lang.String, java.lang.String);
+}
+-keep class com.noirandroid.lib.LogSinkBridge {
+    void onLog(int, java.lang.String, java.lang.String);
+}
+-keep interface com.noirandroid.lib.PrintListener {
+    void onPrint(java.lang.String);
+}
+-keep interface com.noirandroid.lib.SrsDownloadListener {
+    void onProgress(long, long);
+}
+
+# Built by execute_indexed
+-keep class com.noirandroid.lib.ExecutionWitness {
+    <init>(long[], java.lang.String[]);
+}
+
+# Thrown from native code by class name
+-keep class com.noirandroid.lib.SrsVerificationException {
+    <init>(java.lang.String);
+}
+-keep class com.noirandroid.lib.ExecutionLimitException {
+    <init>(java.lang.String);
+}
+-keep class com.noirandroid.lib.MemoryBudgetException {
+    <init>(java.lang.String);
+}
+-keep class com.noirandroid.lib.IncompatibleCircuitException {
+    <init>(java.lang.String);
+}
+
+# Results are exchanged with native code as JSON, which Gson maps by field name
+-keepclassmembers class com.noirandroid.lib.** {
+    <fields>;
+}
@@ -1,65 +1,332 @@
 package com.noirandroid.lib
 
 import com.google.gson.Gson
+import com.google.gson.JsonElement
+import com.google.gson.JsonParser
+import com.google.gson.reflect.TypeToken
+import java.util.concurrent.atomic.AtomicInteger
 import android.util.Log
 
+// A compiled circuit, as parsed by the native library from the output of nargo compile
 data class CircuitManifest(
-    val noir_version: String,
-    // All JSON numbers are parsed as Double by Gson
-    val hash: Double,
+    val noirVersion: String?,
+    // Unsigned 64 bit hash of the circuit in decimal, exact so it can be used as a key
+    val hash: String,
     val abi: Abi,
     val bytecode: String,
-    val debug_symbols: String,
-    val file_map: Map<String, FileMap>,
-    val names: List<String>
+    // Debug symbols are only decoded when profiling
+    val hasDebugSymbols: Boolean
+) {
+    val hashValue: ULong
+        get() = hash.toULong()
+
+    // Initial witnesses taken by each parameter of main
+    val paramWitnesses: Map<String, List<WitnessRange>>
+        get() = abi.paramWitnesses
+
+    // Manifest as compiled, set by Circuit.fromJsonManifest for the deprecated fields
+    @Transient
+    internal var json: String? = null
+
+    @Deprecated("Use noirVersion", ReplaceWith("noirVersion"))
+    val noir_version: String?
+        get() = noirVersion
+
+    @Deprecated("Profiling decodes the debug symbols natively, check hasDebugSymbols")
+    val debug_symbols: String
+        get() = compiledField("debug_symbols")?.takeIf { it.isJsonPrimitive }?.asString ?: ""
+
+    @Deprecated("Profiling decodes the source files natively, check hasDebugSymbols")
+    val file_map: Map<String, FileMap>
+        get() = compiledField("file_map")?.let { Gson().fromJson<Map<String, FileMap>>(it, object : TypeToken<Map<String, FileMap>>() {}.type) } ?: emptyMap()
+
+    // Read from the compiled manifest when asked for, as the native view leaves them out
+    private fun compiledField(name: String): JsonElement? =
+        json?.let { JsonParser.parseString(it).asJsonObject.get(name) }?.takeUnless { it.isJsonNull }
+}
+
+@Deprecated("Only used by the deprecated CircuitManifest.file_map")
+data class FileMap(
+    val source: String,
+    val path: String
 )
 
 data class Abi(
     val parameters: List<Parameter>,
-    val param_witnesses: Map<String, List<Witness>>,
-    val return_type: Any?,
-    val return_witnesses: List<Any>,
-    val error_types: ErrorTypes
-)
+    val returnType: AbiReturnType?,
+    val errorTypes: Map<String, AbiErrorType>,
+    // Initial witnesses taken by each parameter of main, as written by the compiler or,
+    // when it doesn't write them, worked out from the types of the parameters
+    val paramWitnesses: Map<String, List<WitnessRange>>,
+    // Witnesses of the return value, empty if the compiler didn't write them
+    val returnWitnesses: List<Long>
+) {
+    @Deprecated("Use paramWitnesses", ReplaceWith("paramWitnesses"))
+    val param_witnesses: Map<String, List<WitnessRange>>
+        get() = paramWitnesses
+
+    @Deprecated("Use returnWitnesses", ReplaceWith("returnWitnesses"))
+    val return_witnesses: List<Any>
+        get() = returnWitnesses
+}
 
 data class Parameter(
     val name: String,
     val type: Type,
+    // public, private or databus. Not set for the fields of a struct
     val visibility: String?
 )
 
+data class AbiReturnType(
+    val abiType: Type,
+    val visibility: String
+)
+
 data class Type(
-    // struct, array, field, integer, string
+    // field, integer, boolean, array, string, struct or tuple
     val kind: String,
     val path: String?,
     val type: Type?,
+    // Fields of a struct
     val fields: List<Parameter>?,
-    val length: Double?,
+    // Elements of a tuple, which have no name
+    val elements: List<Type>?,
+    val length: Int?,
     val sign: String?,
-    val width: Double?,
+    val width: Int?,
 )
 
-data class Witness(
-    // All JSON numbers are parsed as Double by Gson
-    val start: Double,
-    val end: Double
+// Witnesses start until end (excluded)
+data class WitnessRange(
+    val start: Long,
+    val end: Long
 )
 
-data class ErrorTypes(
-    val error: Any?
+// Data an assertion fails with: a string, a format string of length items, or a value of type
+data class AbiErrorType(
+    // string, fmtstring or custom
+    val errorKind: String,
+    val string: String?,
+    val length: Int?,
+    val itemTypes: List<Type>?,
+    val type: Type?
 )
 
-data class FileMap(
-    val source: String,
-    val path: String
+data class CircuitInfo(
+    val gateCount: Int,
+    // Gate count rounded up to the next power of two, to be used as the circuit size
+    val circuitSize: Int,
+    val publicInputs: Int,
+    val opcodeCounts: Map<String, Int>,
+    val blackBoxFunctions: List<String>,
+    val brilligFunctions: Int,
+    // Names of the ACIR functions, main first. Only single function programs can be proven
+    val acirFunctions: List<String>
+)
+
+// Witness indices with the hex value of each witness at the same position
+class ExecutionWitness(val indices: LongArray, val values: Array<String>) {
+    fun toMap(): Map<Long, String> = indices.zip(values).toMap()
+
+    operator fun get(index: Long): String? {
+        val position = indices.indexOf(index)
+        return if (position >= 0) values[position] else null
+    }
+}
+
+// Solved witness of one ACIR function call of the program
+data class FunctionWitness(
+    val function: Int,
+    val name: String,
+    val indices: List<Long>,
+    val values: List<String>
+)
+
+data class OpcodeStats(
+    val count: Long,
+    val nanos: Long
+)
+
+// Time spent executing the circuit, by opcode type, unconstrained function,
+// black box function and Noir function
+data class ExecutionProfile(
+    val totalNanos: Long,
+    val acirOpcodes: Map<String, OpcodeStats>,
+    val brilligFunctions: Map<String, OpcodeStats>,
+    val blackBoxFunctions: Map<String, OpcodeStats>,
+    val sourceFunctions: Map<String, OpcodeStats>
+)
+
+data class PhaseTiming(
+    val name: String,
+    val nanos: Long
+)
+
+// Cost of one native call. Phases are decode, then execute and encode, or prove
+// (which includes executing the circuit again and building the proving key, as
+// noir_rs does all three in one call), or verification_key
+data class CallMetrics(
+    val operation: String,
+    val succeeded: Boolean,
+    val totalNanos: Long,
+    val phases: List<PhaseTiming>,
+    val peakRssBytes: Long?,
+    val peakRssReset: Boolean,
+    val diskBytesWritten: Long?,
+    val srsPoints: Int?
 )
 
+// Either the proof or the reason it couldn't be generated
+data class BatchProofResult(
+    val proof: String?,
+    val error: String?
+)
+
+// Either the verdict or the reason the proof couldn't be checked
+data class BatchVerifyResult(
+    val verified: Boolean?,
+    val error: String?
+)
+
+// Predicted peak memory of proving the circuit, in bytes
+data class MemoryEstimate(
+    val circuitSize: Int,
+    val peakBytes: Long,
+    val lowMemoryPeakBytes: Long,
+    val lowMemoryStorageBytes: Long
+)
+
+data class MemoryMode(
+    val lowMemoryMode: Boolean,
+    val storageCap: Long,
+    val estimate: MemoryEstimate
+)
+
+// Directory where low memory mode moves data out of memory, as set by ScratchDir.set
+data class ScratchDir(
+    val path: String,
+    val removedLeftovers: Int,
+    val freeBytes: Long
+) {
+    companion object {
+        // Uses a noir_scratch subdirectory of parentDir (e.g. context.cacheDir), removing
+        // what runs that crashed left there. Call it at startup: it throws while a proof
+        // is being generated
+        fun set(parentDir: String): ScratchDir = Gson().fromJson(Noir.set_scratch_dir(parentDir), ScratchDir::class.java)
+    }
+}
+
+// What Noir.release_resources freed. freedBytes is the decrease of the process RSS
+data class ReleaseReport(
+    val level: Int,
+    val verificationKeysDropped: Int,
+    val srsUnloaded: Boolean,
+    val heapTrimmed: Boolean,
+    val freedBytes: Long
+) {
+    companion object {
+        // Takes the level passed to ComponentCallbacks2.onTrimMemory. Once the SRS is
+        // unloaded, circuits have to set it up again before proving
+        fun release(level: Int): ReleaseReport {
+            val report = Gson().fromJson(Noir.release_resources(level), ReleaseReport::class.java)
+            if (report.srsUnloaded) {
+                Circuit.srsUnloads.incrementAndGet()
+            }
+            return report
+        }
+    }
+}
+
+data class VkCacheStats(
+    val memoryHits: Long,
+    val diskHits: Long,
+    val misses: Long,
+    val entries: Int
+) {
+    companion object {
+        fun get(): VkCacheStats = Gson().fromJson(Noir.get_vk_cache_stats(), VkCacheStats::class.java)
+    }
+}
+
+enum class CompatibilityStatus { COMPATIBLE, WARNING, INCOMPATIBLE }
+
+// Whether a compiled circuit can be used with the Noir release of the native library.
+// WARNING means the bytecode can be read but comes from another release
+data class Compatibility(
+    val status: CompatibilityStatus,
+    val reason: String,
+    val circuitVersion: String?,
+    val libraryVersion: String
+) {
+    companion object {
+        fun check(jsonManifest: String): Compatibility = Gson().fromJson(Noir.check_compatibility(jsonManifest), Compatibility::class.java)
+    }
+}
+
+// Versions of the native library and of what it links
+data class VersionInfo(
+    val noirJava: String,
+    val noirRs: String,
+    val acvm: String,
+    val barretenberg: String
+) {
+    companion object {
+        fun get(): VersionInfo = Gson().fromJson(Noir.version_info(), VersionInfo::class.java)
+    }
+}
+
 class Circuit(public val bytecode: String, public val manifest: CircuitManifest, public var num_points: Int = 0, public var size: Int = 0, public var lowMemoryMode: Boolean = false, public var storageCap: Long = 0) {
 
+    // Resolves the oracles called during execution, if the circuit uses any
+    public var foreignCallHandler: ForeignCallHandler? = null
+
+    // Receives the output of print and println during execution
+    public var printListener: PrintListener? = null
+
+    // Bounds on the execution, 0 meaning unlimited. When one is hit, execution
+    // throws an ExecutionLimitException telling where it stopped. The timeout is checked
+    // around foreign calls, but doesn't interrupt a handler that blocks
+    public var maxBrilligSteps: Long = 0
+    public var executionTimeoutMillis: Long = 0
+
+    // Receives the timing and memory usage of each native call, when set
+    public var metricsListener: MetricsListener? = null
+
+    // Memory the prover may use in bytes, e.g. from ActivityManager.MemoryInfo.availMem.
+    // When set, lowMemoryMode and storageCap are picked for each call from an estimate of
+    // the memory the circuit needs, storageCap then being the most storage it may use (0
+    // for the free space of the scratch directory). Calls that can't fit throw a
+    // MemoryBudgetException
+    public var availableMemory: Long = 0
+
+    // Result of the compatibility check made by fromJsonManifest
+    public var compatibility: Compatibility? = null
+
+    // Manifest as compiled, for the debug symbols profiling decodes
+    private var manifestJson: String? = null
+
+    // Value of srsUnloads when this circuit last set up the SRS
+    private var srsSetupAfter = -1
+
     companion object {
+        // Number of times the SRS was unloaded by ReleaseReport.release
+        internal val srsUnloads = AtomicInteger()
+
+        // Throws an IncompatibleCircuitException if the circuit was compiled by a Noir
+        // release whose bytecode can't be read
         fun fromJsonManifest(jsonManifest: String, size: Int? = null, lowMemoryMode: Boolean = false, storageCap: Long = 0): Circuit {
-            val manifest: CircuitManifest = Gson().fromJson(jsonManifest, CircuitManifest::class.java)
-            return Circuit(manifest.bytecode, manifest, 0, size ?: 0, lowMemoryMode, storageCap)
+            val compatibility = Compatibility.check(jsonManifest)
+            when (compatibility.status) {
+                CompatibilityStatus.INCOMPATIBLE -> throw IncompatibleCircuitException(compatibility.reason)
+                CompatibilityStatus.WARNING -> Log.w("Circuit", compatibility.reason)
+                CompatibilityStatus.COMPATIBLE -> {}
+            }
+            val manifest: CircuitManifest = Gson().fromJson(Noir.parse_manifest(jsonManifest), CircuitManifest::class.java)
+            val circuit = Circuit(manifest.bytecode, manifest, 0, size ?: 0, lowMemoryMode, storageCap)
+            circuit.compatibility = compatibility
+            circuit.manifestJson = if (manifest.hasDebugSymbols) jsonManifest else null
+            manifest.json = jsonManifest
+            return circuit
         }
     }
 
@@ -78,40 +345,201 @@ class Circuit(public val bytecode: String, public val manifest: CircuitManifest,
 
     fun setupSrs(srs_path: String? = null) {
         try {
+            srsSetupAfter = srsUnloads.get()
             if (size > 0) {
                 num_points = Noir.setup_srs(size, srs_path)
             } else {
                 num_points = Noir.setup_srs_from_bytecode(bytecode, srs_path)
             }
+        } catch (e: SrsVerificationException) {
+            Log.e("Circuit", "Invalid SRS: ${e.message}", e)
+            throw e
         } catch (e: Throwable) {
             Log.e("Circuit", "Failed to setup SRS: ${e.message}", e)
             throw RuntimeException("SRS setup failed: ${e.message}", e)
         }
     }
 
+    fun setupSrsFromSource(cacheDir: String, baseUrl: String? = null, listener: SrsDownloadListener? = null) {
+        try {
+            if (size == 0) {
+                size = getCircuitInfo().circuitSize
+            }
+            srsSetupAfter = srsUnloads.get()
+            num_points = Noir.setup_srs_from_source(size, baseUrl, cacheDir, listener)
+        } catch (e: SrsVerificationException) {
+            Log.e("Circuit", "Invalid SRS: ${e.message}", e)
+            throw e
+        } catch (e: Throwable) {
+            Log.e("Circuit", "Failed to setup SRS: ${e.message}", e)
+            throw RuntimeException("SRS setup failed: ${e.message}", e)
+        }
+    }
+
+    fun getCircuitInfo(): CircuitInfo {
+        try {
+            return Gson().fromJson(Noir.circuit_info(bytecode), CircuitInfo::class.java)
+        } catch (e: Throwable) {
+            Log.e("Circuit", "Failed to get circuit info: ${e.message}", e)
+            throw RuntimeException("Failed to get circuit info: ${e.message}", e)
+        }
+    }
+
     fun execute(initialWitness: Map<String, Any>): Array<String> {
         try {
             val witness = generateWitnessMap(initialWitness, manifest.abi.parameters, 0)
-            return Noir.execute(bytecode, witness)
+            return measured { Noir.execute(bytecode, witness, foreignCallBridge(), printListener, maxBrilligSteps, executionTimeoutMillis) }
+        } catch (e: ExecutionLimitException) {
+            Log.e("Circuit", "Execution stopped: ${e.message}", e)
+            throw e
+        } catch (e: Throwable) {
+            Log.e("Circuit", "Failed to execute circuit: ${e.message}", e)
+            throw RuntimeException("Circuit execution failed: ${e.message}", e)
+        }
+    }
+
+    fun executeIndexed(initialWitness: Map<String, Any>, indices: List<Long>? = null): ExecutionWitness {
+        try {
+            val witness = generateWitnessMap(initialWitness, manifest.abi.parameters, 0)
+            return measured { Noir.execute_indexed(bytecode, witness, indices?.toLongArray(), false, foreignCallBridge(), printListener, maxBrilligSteps, executionTimeoutMillis) }
+        } catch (e: ExecutionLimitException) {
+            Log.e("Circuit", "Execution stopped: ${e.message}", e)
+            throw e
+        } catch (e: Throwable) {
+            Log.e("Circuit", "Failed to execute circuit: ${e.message}", e)
+            throw RuntimeException("Circuit execution failed: ${e.message}", e)
+        }
+    }
+
+    fun executeReturnValues(initialWitness: Map<String, Any>): ExecutionWitness {
+        try {
+            val witness = generateWitnessMap(initialWitness, manifest.abi.parameters, 0)
+            return measured { Noir.execute_indexed(bytecode, witness, null, true, foreignCallBridge(), printListener, maxBrilligSteps, executionTimeoutMillis) }
+        } catch (e: ExecutionLimitException) {
+            Log.e("Circuit", "Execution stopped: ${e.message}", e)
+            throw e
+        } catch (e: Throwable) {
+            Log.e("Circuit", "Failed to execute circuit: ${e.message}", e)
+            throw RuntimeException("Circuit execution failed: ${e.message}", e)
+        }
+    }
+
+    fun executeToWitnessStack(initialWitness: Map<String, Any>): ByteArray {
+        try {
+            val witness = generateWitnessMap(initialWitness, manifest.abi.parameters, 0)
+            return measured { Noir.execute_to_witness_stack(bytecode, witness, foreignCallBridge(), printListener, maxBrilligSteps, executionTimeoutMillis) }
+        } catch (e: ExecutionLimitException) {
+            Log.e("Circuit", "Execution stopped: ${e.message}", e)
+            throw e
+        } catch (e: Throwable) {
+            Log.e("Circuit", "Failed to execute circuit: ${e.message}", e)
+            throw RuntimeException("Circuit execution failed: ${e.message}", e)
+        }
+    }
+
+    fun executeWitnessStack(initialWitness: Map<String, Any>): List<FunctionWitness> {
+        try {
+            val witness = generateWitnessMap(initialWitness, manifest.abi.parameters, 0)
+            return Gson().fromJson(measured { Noir.execute_witness_stack(bytecode, witness, foreignCallBridge(), printListener, maxBrilligSteps, executionTimeoutMillis) }, Array<FunctionWitness>::class.java).toList()
+        } catch (e: ExecutionLimitException) {
+            Log.e("Circuit", "Execution stopped: ${e.message}", e)
+            throw e
         } catch (e: Throwable) {
             Log.e("Circuit", "Failed to execute circuit: ${e.message}", e)
             throw RuntimeException("Circuit execution failed: ${e.message}", e)
         }
     }
 
+    fun profileExecution(initialWitness: Map<String, Any>): ExecutionProfile {
+        return Gson().fromJson(profile(initialWitness, "json"), ExecutionProfile::class.java)
+    }
+
+    // Folded stacks in nanoseconds, for flamegraph.pl or inferno
+    fun profileExecutionFolded(initialWitness: Map<String, Any>): String {
+        return profile(initialWitness, "folded")
+    }
+
+    private fun profile(initialWitness: Map<String, Any>, format: String): String {
+        try {
+            val witness = generateWitnessMap(initialWitness, manifest.abi.parameters, 0)
+            return Noir.profile_execution(bytecode, witness, manifestJson, format, foreignCallBridge(), printListener, maxBrilligSteps, executionTimeoutMillis)
+        } catch (e: ExecutionLimitException) {
+            Log.e("Circuit", "Execution stopped: ${e.message}", e)
+            throw e
+        } catch (e: Throwable) {
+            Log.e("Circuit", "Failed to profile circuit: ${e.message}", e)
+            throw RuntimeException("Circuit profiling failed: ${e.message}", e)
+        }
+    }
+
+    fun proveFromWitness(witnessStack: ByteArray, vk: String? = null, proofType: String? = "ultra_honk"): String {
+        if (!srsReady()) {
+            throw IllegalArgumentException("SRS not set up")
+        }
+        try {
+            val (lowMemory, cap) = memorySettings(proofType)
+            return measured { Noir.prove_from_witness(bytecode, witnessStack, vk ?: getVerificationKey(), proofType, lowMemory, cap) }
+        } catch (e: MemoryBudgetException) {
+            Log.e("Circuit", "Not enough memory: ${e.message}", e)
+            throw e
+        } catch (e: Throwable) {
+            Log.e("Circuit", "Failed to prove circuit: ${e.message}", e)
+            throw RuntimeException("Circuit proving failed: ${e.message}", e)
+        }
+    }
+
     fun prove(initialWitness: Map<String, Any>, vk: String? = null, proofType: String? = "ultra_honk"): String {
-        if (num_points == 0) {
+        if (!srsReady()) {
             throw IllegalArgumentException("SRS not set up")
         }
         try {
             val witness = generateWitnessMap(initialWitness, manifest.abi.parameters, 0)
-            return Noir.prove(bytecode, witness, vk ?: getVerificationKey(), proofType, lowMemoryMode, storageCap)
+            val (lowMemory, cap) = memorySettings(proofType)
+            return measured { Noir.prove(bytecode, witness, vk ?: getVerificationKey(), proofType, lowMemory, cap) }
+        } catch (e: MemoryBudgetException) {
+            Log.e("Circuit", "Not enough memory: ${e.message}", e)
+            throw e
         } catch (e: Throwable) {
             Log.e("Circuit", "Failed to prove circuit: ${e.message}", e)
             throw RuntimeException("Circuit proving failed: ${e.message}", e)
         }
     }
 
+    // Proves each input as its own prove call, up to maxParallel at a time (one at a time in
+    // low memory mode). Only the verification key is computed and decoded once for the batch
+    fun proveBatch(initialWitnesses: List<Map<String, Any>>, vk: String? = null, proofType: String? = "ultra_honk", maxParallel: Int = 1, memoryBudget: Long = 0): List<BatchProofResult> {
+        if (!srsReady()) {
+            throw IllegalArgumentException("SRS not set up")
+        }
+        try {
+            // An input that doesn't match the ABI only fails its own item
+            val witnesses = initialWitnesses.map { runCatching { generateWitnessMap(it, manifest.abi.parameters, 0) } }
+            val valid = witnesses.mapNotNull { it.getOrNull() }
+            val (lowMemory, cap) = memorySettings(proofType)
+            val proven = if (valid.isEmpty()) {
+                emptyList()
+            } else {
+                // The SRS holds at least as many points as the circuit has gates
+                val results = measured { Noir.prove_batch(bytecode, valid, vk ?: getVerificationKey(), proofType, lowMemory, cap, maxParallel, memoryBudget, num_points) }
+                Gson().fromJson(results, Array<BatchProofResult>::class.java).toList()
+            }
+            check(proven.size == valid.size) { "Got ${proven.size} results for ${valid.size} inputs" }
+            val provenResults = proven.iterator()
+            return witnesses.map { witness ->
+                witness.fold(
+                    onSuccess = { provenResults.next() },
+                    onFailure = { BatchProofResult(null, "Invalid input: ${it.message}") }
+                )
+            }
+        } catch (e: MemoryBudgetException) {
+            Log.e("Circuit", "Not enough memory: ${e.message}", e)
+            throw e
+        } catch (e: Throwable) {
+            Log.e("Circuit", "Failed to prove batch: ${e.message}", e)
+            throw RuntimeException("Batch proving failed: ${e.message}", e)
+        }
+    }
+
     fun verify(proof: String, vk: String? = null, proofType: String? = "ultra_honk"): Boolean {
         if (num_points == 0) {
             throw IllegalArgumentException("SRS not set up")
@@ -124,22 +552,88 @@ class Circuit(public val bytecode: String, public val manifest: CircuitManifest,
         }
     }
 
+    fun verifyBatch(proofs: List<String>, vk: String? = null, proofType: String = "ultra_honk", threads: Int = 1): List<BatchVerifyResult> {
+        if (num_points == 0) {
+            throw IllegalArgumentException("SRS not set up")
+        }
+        try {
+            val key = vk ?: getVerificationKey(proofType)
+            val results = Noir.verify_batch(proofs.toTypedArray(), Array(proofs.size) { key }, Array(proofs.size) { proofType }, threads)
+            return Gson().fromJson(results, Array<BatchVerifyResult>::class.java).toList()
+        } catch (e: Throwable) {
+            Log.e("Circuit", "Failed to verify batch: ${e.message}", e)
+            throw RuntimeException("Batch verification failed: ${e.message}", e)
+        }
+    }
+
     fun getVerificationKey(proofType: String? = "ultra_honk"): String {
         try {
-            return Noir.get_verification_key(bytecode, proofType, lowMemoryMode, storageCap)
+            val (lowMemory, cap) = memorySettings(proofType)
+            return measured { Noir.get_verification_key(bytecode, proofType, lowMemory, cap) }
+        } catch (e: MemoryBudgetException) {
+            Log.e("Circuit", "Not enough memory: ${e.message}", e)
+            throw e
         } catch (e: Throwable) {
             Log.e("Circuit", "Failed to get verification key: ${e.message}", e)
             throw RuntimeException("Failed to get verification key: ${e.message}", e)
         }
     }
 
+    fun estimateMemory(proofType: String = "ultra_honk"): MemoryEstimate {
+        try {
+            if (size == 0) {
+                size = getCircuitInfo().circuitSize
+            }
+            return Gson().fromJson(Noir.estimate_memory(size, proofType), MemoryEstimate::class.java)
+        } catch (e: Throwable) {
+            Log.e("Circuit", "Failed to estimate memory: ${e.message}", e)
+            throw RuntimeException("Failed to estimate memory: ${e.message}", e)
+        }
+    }
+
+    // Low memory mode and storage cap of a call, picked natively if availableMemory is set
+    private fun memorySettings(proofType: String?): Pair<Boolean, Long> {
+        if (availableMemory <= 0) {
+            return Pair(lowMemoryMode, storageCap)
+        }
+        if (size == 0) {
+            size = getCircuitInfo().circuitSize
+        }
+        val mode = Gson().fromJson(Noir.select_memory_mode(size, proofType ?: "ultra_honk", availableMemory, storageCap), MemoryMode::class.java)
+        return Pair(mode.lowMemoryMode, mode.storageCap)
+    }
+
+    // Collects the metrics of the native calls made by block and hands them to the
+    // listener. Nested calls (e.g. computing the verification key for prove) are
+    // reported by the outermost one
+    private fun <T> measured(block: () -> T): T {
+        val listener = metricsListener ?: return block()
+        val wasCollecting = Noir.set_collect_metrics(true)
+        try {
+            return block()
+        } finally {
+            if (!wasCollecting) {
+                val metrics = Noir.take_metrics()
+                Noir.set_collect_metrics(false)
+                for (callMetrics in Gson().fromJson(metrics, Array<CallMetrics>::class.java)) {
+                    listener.onMetrics(callMetrics)
+                }
+            }
+        }
+    }
+
+    // Verifying only needs the first point, which stays loaded when the SRS is unloaded
+    private fun srsReady(): Boolean = num_points > 0 && srsSetupAfter == srsUnloads.get()
+
+    private fun foreignCallBridge(): ForeignCallBridge? = foreignCallHandler?.let { ForeignCallBridge(it) }
+
     private fun flattenMultiDimensionalArray(array: List<Any>, elementType: Type): List<Any> {
         val flattenedArray = mutableListOf<Any>()
         for (element in array) {
             if (element is List<*>) {
                 flattenedArray.addAll(flattenMultiDimensionalArray(element as List<Any>, elementType.type!!))
             } else if(elementType.kind == "string" && element is String) {
-                val length = elementType.length!!.toInt()
+                val length = elementType.length!!
                 for (i in 0 until length) {
                     if (i < element.length) {
                         flattenedArray.add(element.get(i).toDouble())
@@ -159,22 +653,25 @@ class Circuit(public val bytecode: String, public val manifest: CircuitManifest,
     private fun computeTotalLengthOfArray(parameter_type: Type): Int {
         when(parameter_type.kind) {
             "array" -> {
-                return parameter_type.length!!.toInt() * computeTotalLengthOfArray(parameter_type.type!!)
+                return parameter_type.length!! * computeTotalLengthOfArray(parameter_type.type!!)
             }
-            "field", "integer" -> {
+            "field", "integer", "boolean" -> {
                 return 1
             }
             "string" -> {
-                return parameter_type.length!!.toInt()
+                return parameter_type.length!!
             }
             "struct" -> {
                 return parameter_type.fields!!.map { computeTotalLengthOfArray(it.type) }.sum()
             }
+            "tuple" -> {
+                return parameter_type.elements!!.map { computeTotalLengthOfArray(it) }.sum()
+            }
         }
         return 0
     }
 
-    private fun generateWitnessMap(initialWitness: Map<String, Any>, parameters: List<Parameter>, startIndex: Long): HashMap<String, String> {
+    internal fun generateWitnessMap(initialWitness: Map<String, Any>, parameters: List<Parameter>, startIndex: Long): HashMap<String, String> {
         val witness = HashMap<String, String>()
         var index = startIndex
         for (parameter in parameters) {
@@ -272,11 +769,25 @@ class Circuit(public val bytecode: String, public val manifest: CircuitManifest,
                         throw IllegalArgumentException("Expected struct for parameter: ${parameter.name}. Got ${value.javaClass}")
                     }
                 }
+                "tuple" -> {
+                    val elements = parameter.type.elements!!
+                    if (value is List<*> && value.size == elements.size) {
+                        // The elements are flattened in order, like the fields of a struct named by position
+                        val tuple = value.withIndex().mapNotNull { (position, element) -> element?.let { position.toString() to it } }.toMap()
+                        val tupleWitness = generateWitnessMap(tuple, elements.mapIndexed { position, type -> Parameter(position.toString(), type, null) }, index)
+                        for ((key, witnessValue) in tupleWitness) {
+                            witness[key] = witnessValue
+                            index++
+                        }
+                    } else {
+                        throw IllegalArgumentException("Expected list of ${elements.size} elements for tuple parameter: ${parameter.name}. Got ${value.javaClass}")
+                    }
+                }
                 "string" -> {
                     if (value is String) {
                         // Transform the string into a byte array
                         val array = value.toByteArray()
-                        if (array.size != parameter.type.length!!.toInt()) {
+                        if (array.size != parameter.type.length!!) {
                             throw IllegalArgumentException("Expected string of length ${parameter.type.length} for parameter: ${parameter.name}. Instead got ${array.size}")
                         }
                         for (element in array) {
@@ -0,0 +1,25 @@
+package com.noirandroid.lib
+
+import com.google.gson.Gson
+
+// Argument or return value of a foreign call: a single field or an array of fields, as hex strings
+data class ForeignCallParam(val single: String? = null, val array: List<String>? = null) {
+    companion object {
+        fun single(value: String) = ForeignCallParam(single = value)
+        fun array(values: List<String>) = ForeignCallParam(array = values)
+    }
+}
+
+// Resolves the oracles (#[oracle] functions) called by unconstrained code during execution.
+// print is handled natively and never reaches the handler
+fun interface ForeignCallHandler {
+    fun handle(name: String, inputs: List<ForeignCallParam>): List<ForeignCallParam>
+}
+
+// Called from native code, which exchanges the values as JSON
+class ForeignCallBridge(private val handler: ForeignCallHandler) {
+    fun resolve(name: String, inputs: String): String {
+        val params = Gson().fromJson(inputs, Array<ForeignCallParam>::class.java).toList()
+        return Gson().toJson(handler.handle(name, params))
+    }
+}
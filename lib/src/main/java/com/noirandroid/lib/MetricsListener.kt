@@ -0,0 +1,7 @@
+package com.noirandroid.lib
+
+// Receives the metrics of each execute, prove and verification key call made by a
+// Circuit, on the thread that made the call, once the call returns or throws
+fun interface MetricsListener {
+    fun onMetrics(metrics: CallMetrics)
+}
@@ -0,0 +1,23 @@
+package com.noirandroid.lib
+
+enum class LogLevel { OFF, ERROR, WARN, INFO, DEBUG, TRACE }
+
+// Settings of the logs of the native library, which apply to every thread
+object NativeLog {
+    // INFO by default, DEBUG and TRACE have to be enabled explicitly
+    fun setLevel(level: LogLevel) {
+        Noir.set_log_level(level.name.lowercase())
+    }
+
+    // While enabled, witness values, witness keys and the output of print are
+    // replaced with <redacted> in the native logs and error messages
+    fun setRedaction(enabled: Boolean) {
+        Noir.set_log_redaction(enabled)
+    }
+
+    // Also sends the records up to level to sink, whatever the level of logcat.
+    // Passing null removes the sink
+    fun setSink(sink: NativeLogSink?, level: LogLevel = LogLevel.INFO) {
+        Noir.set_log_sink(sink?.let { LogSinkBridge(it) }, level.name.lowercase())
+    }
+}
@@ -0,0 +1,14 @@
+package com.noirandroid.lib
+
+// Receives the records of the native logs, on a thread dedicated to it. Records
+// logged while it is busy are queued, and dropped once the queue is full
+fun interface NativeLogSink {
+    fun onLog(level: LogLevel, target: String, message: String)
+}
+
+// Called from native code, which passes the level as the ordinal of LogLevel
+class LogSinkBridge(private val sink: NativeLogSink) {
+    fun onLog(level: Int, target: String, message: String) {
+        sink.onLog(LogLevel.values()[level], target, message)
+    }
+}
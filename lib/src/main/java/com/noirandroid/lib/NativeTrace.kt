@@ -0,0 +1,24 @@
+package com.noirandroid.lib
+
+import com.google.gson.Gson
+
+// Trace written by NativeTrace.stop
+data class TraceSummary(
+    val path: String,
+    val spans: Int,
+    val durationNanos: Long
+)
+
+// Records the native calls and their phases as a Chrome trace, which Perfetto opens
+object NativeTrace {
+    // Records the spans of every thread until stop, throwing IllegalStateException
+    // if a trace is already being recorded
+    fun start(path: String) {
+        Noir.start_trace(path)
+    }
+
+    // Writes the trace to the path given to start
+    fun stop(): TraceSummary {
+        return Gson().fromJson(Noir.stop_trace(), TraceSummary::class.java)
+    }
+}
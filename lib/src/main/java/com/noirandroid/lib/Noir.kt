@@ -18,14 +18,112 @@ class Noir {
         
         external fun prove(circuitBytecode: String, initialWitness: Map<String, String>, vk: String, proofType: String?, lowMemoryMode: Boolean, storageCap: Long): String
 
+        external fun prove_batch(circuitBytecode: String, initialWitnesses: List<Map<String, String>>, vk: String, proofType: String?, lowMemoryMode: Boolean, storageCap: Long, maxParallel: Int, memoryBudget: Long, circuitSize: Int): String
+
         external fun verify(proof: String, vk: String, proofType: String?): Boolean
 
+        external fun verify_batch(proofs: Array<String>, vks: Array<String>, proofTypes: Array<String>, threads: Int): String
+
+        // Number of threads verify_batch runs on, one per core unless set before the first batch
+        external fun set_verify_pool_size(threads: Int)
+
         external fun setup_srs(size: Int, srsPath: String?): Int
 
         external fun setup_srs_from_bytecode(circuitBytecode: String, srsPath: String?): Int
+
+        external fun setup_srs_from_source(size: Int, baseUrl: String?, cacheDir: String, listener: SrsDownloadListener?): Int
+
+        external fun verify_srs(srsPath: String, numPoints: Int, expectedHash: String?): String
         
-        external fun execute(circuitBytecode: String, initialWitness: Map<String, String>): Array<String>
+        external fun circuit_info(circuitBytecode: String): String
+
+        // Parses a compiled circuit into the JSON of a CircuitManifest, with the exact hash
+        external fun parse_manifest(manifestJson: String): String
+
+        // Checks the noir_version of a compiled circuit against the Noir release of the
+        // library, decoding the bytecode if they differ. Returns the result as JSON
+        external fun check_compatibility(manifestJson: String): String
+
+        // Versions of the library, noir_rs, the ACVM and barretenberg, as JSON
+        external fun version_info(): String
+
+        // maxBrilligSteps and timeoutMillis bound the execution, 0 meaning unlimited
+        external fun execute(circuitBytecode: String, initialWitness: Map<String, String>, foreignCallHandler: ForeignCallBridge? = null, printListener: PrintListener? = null, maxBrilligSteps: Long = 0, timeoutMillis: Long = 0): Array<String>
+
+        // Returns the indices of the solved witnesses with their values, limited to the
+        // requested indices (or to the return values) so large witnesses don't all cross JNI
+        external fun execute_indexed(circuitBytecode: String, initialWitness: Map<String, String>, requestedIndices: LongArray?, returnValuesOnly: Boolean, foreignCallHandler: ForeignCallBridge? = null, printListener: PrintListener? = null, maxBrilligSteps: Long = 0, timeoutMillis: Long = 0): ExecutionWitness
+
+        // Returns the solved witness stack, gzipped as written by `nargo execute`
+        external fun execute_to_witness_stack(circuitBytecode: String, initialWitness: Map<String, String>, foreignCallHandler: ForeignCallBridge? = null, printListener: PrintListener? = null, maxBrilligSteps: Long = 0, timeoutMillis: Long = 0): ByteArray
+
+        // Returns the witness of every ACIR function call as JSON, main coming last
+        external fun execute_witness_stack(circuitBytecode: String, initialWitness: Map<String, String>, foreignCallHandler: ForeignCallBridge? = null, printListener: PrintListener? = null, maxBrilligSteps: Long = 0, timeoutMillis: Long = 0): String
+
+        // Executes the circuit and returns where the time went, as JSON or folded stacks.
+        // debugArtifact holds the debug_symbols and file_map of the compiled circuit, if any
+        external fun profile_execution(circuitBytecode: String, initialWitness: Map<String, String>, debugArtifact: String?, format: String, foreignCallHandler: ForeignCallBridge? = null, printListener: PrintListener? = null, maxBrilligSteps: Long = 0, timeoutMillis: Long = 0): String
+
+        // Witness files use the `nargo execute` format, so they can be exchanged with nargo and bb
+        external fun write_witness_file(witnessStack: ByteArray, path: String)
+
+        external fun read_witness_file(path: String): ByteArray
+
+        external fun prove_from_witness(circuitBytecode: String, witnessStack: ByteArray, vk: String, proofType: String?, lowMemoryMode: Boolean, storageCap: Long): String
 
         external fun get_verification_key(circuitBytecode: String, proofType: String?, lowMemoryMode: Boolean, storageCap: Long): String
+
+        // Peak memory of proving a circuit of the given size, as JSON
+        external fun estimate_memory(circuitSize: Int, proofType: String): String
+
+        // Low memory mode and storage cap for proving within memoryBudget bytes, as JSON.
+        // maxStorage bounds the storage cap, 0 meaning no limit
+        external fun select_memory_mode(circuitSize: Int, proofType: String, memoryBudget: Long, maxStorage: Long): String
+
+        // Bytes per gate the estimates of proofType use: the peak with low memory mode
+        // disabled, and the part of it that stays in memory in low memory mode
+        external fun set_memory_model(proofType: String, bytesPerGate: Long, residentBytesPerGate: Long)
+
+        // Enables the collection of metrics for the native calls made on the current
+        // thread, returning whether it was already enabled
+        external fun set_collect_metrics(enabled: Boolean): Boolean
+
+        // Resets the peak RSS of the process at the start of each measured call.
+        // Off by default: the reset is process wide, so the calls running on other
+        // threads at the time get no peak
+        external fun set_peak_rss_reset(enabled: Boolean)
+
+        // Metrics of the calls made on the current thread since the last take, as JSON
+        external fun take_metrics(): String
+
+        // Level of the native logs: off, error, warn, info (the default), debug or trace
+        external fun set_log_level(level: String)
+
+        // Leaves witness values, witness keys and prints out of the native logs and
+        // error messages, enabled by default in release builds
+        external fun set_log_redaction(enabled: Boolean)
+
+        // Forwards the native logs up to level to the sink from a thread of its own,
+        // see NativeLog.setSink. A null sink removes the current one
+        external fun set_log_sink(sink: LogSinkBridge?, level: String)
+
+        // Records spans to a Chrome trace file until stop_trace, see NativeTrace
+        external fun start_trace(path: String)
+
+        // Writes the trace and returns its summary as JSON
+        external fun stop_trace(): String
+
+        // Frees native memory according to an onTrimMemory level, see ReleaseReport.release
+        external fun release_resources(level: Int): String
+
+        // Sets the directory low memory mode writes to, see ScratchDir.set
+        external fun set_scratch_dir(parentDir: String): String
+
+        // Verification keys are cached in memory, and on disk in this directory if set
+        external fun set_vk_cache_dir(cacheDir: String?)
+
+        external fun get_vk_cache_stats(): String
+
+        external fun clear_vk_cache()
     }
 }
\ No newline at end of file
@@ -0,0 +1,16 @@
+package com.noirandroid.lib
+
+// Thrown by the native library when a local SRS fails its integrity checks
+class SrsVerificationException(message: String) : RuntimeException(message)
+
+// Thrown when an execution exceeds its Brillig step budget or its timeout. The message
+// tells where it stopped, which usually points at an unconstrained function that doesn't terminate
+class ExecutionLimitException(message: String) : RuntimeException(message)
+
+// Thrown when a circuit is estimated not to fit in the memory budget set on it, even in
+// low memory mode. The message gives the estimate
+class MemoryBudgetException(message: String) : RuntimeException(message)
+
+// Thrown when a circuit was compiled by a Noir release whose bytecode this library can't
+// read. The message gives both versions
+class IncompatibleCircuitException(message: String) : RuntimeException(message)
@@ -0,0 +1,7 @@
+package com.noirandroid.lib
+
+// Receives the output of print and println calls made during execution, which is also
+// logged under the NoirAndroid tag. Messages printed with println end with a newline
+fun interface PrintListener {
+    fun onPrint(message: String)
+}
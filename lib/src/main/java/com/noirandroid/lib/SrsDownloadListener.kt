@@ -0,0 +1,7 @@
+package com.noirandroid.lib
+
+// Receives the progress of an SRS download, cached bytes included. Throwing stops the
+// download, keeping what was cached, and setupSrsFromSource fails with the exception as cause
+fun interface SrsDownloadListener {
+    fun onProgress(downloadedBytes: Long, totalBytes: Long)
+}
@@ -0,0 +1,420 @@
+use log::{debug, error, info};
+use noir_rs::{
+    acir::native_types::WitnessMap,
+    barretenberg::{
+        prove::{prove_ultra_honk, prove_ultra_honk_keccak},
+        verify::{verify_ultra_honk, verify_ultra_honk_keccak},
+    },
+    FieldElement,
+};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{mpsc, Arc, Mutex, OnceLock};
+
+use crate::memory;
+use crate::secret::{SecretWitnessMap, WipeFreed};
+use crate::witness;
+
+// Witnesses not proven yet are wiped if the batch fails
+type WitnessResult = Result<SecretWitnessMap, String>;
+type ProofResult = Result<Vec<u8>, String>;
+
+pub struct BatchOptions {
+    /// Maximum number of proofs generated at the same time, 1 to prove sequentially
+    pub max_parallel: usize,
+    /// Memory available to the batch in bytes, 0 if unknown
+    pub memory_budget: u64,
+    /// Size of the circuit, or any upper bound of it such as the number of SRS
+    /// points loaded for it, 0 if unknown. Only used with a memory budget.
+    pub circuit_size: u32,
+}
+
+/// Outcome of one item of the batch, serialized as `{"proof": "..."}` or `{"error": "..."}`
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BatchItemResult {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub proof: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl From<ProofResult> for BatchItemResult {
+    fn from(result: ProofResult) -> Self {
+        match result {
+            Ok(proof) => BatchItemResult { proof: Some(hex::encode(proof)), error: None },
+            Err(e) => BatchItemResult { proof: None, error: Some(e) },
+        }
+    }
+}
+
+pub fn prove_with_flavor(
+    proof_type: &str,
+    circuit_bytecode: &str,
+    witness_map: WitnessMap<FieldElement>,
+    verification_key: Vec<u8>,
+    low_memory_mode: bool,
+    storage_cap: u64,
+) -> Result<Vec<u8>, String> {
+    // noir_rs drops its copies of the witness without wiping them
+    let _wipe_freed = WipeFreed::start();
+    match proof_type {
+        "ultra_honk" => prove_ultra_honk(circuit_bytecode, witness_map, verification_key, low_memory_mode, Some(storage_cap)),
+        "ultra_honk_keccak" => prove_ultra_honk_keccak(circuit_bytecode, witness_map, verification_key, false, low_memory_mode, Some(storage_cap)),
+        _ => Err(format!("Unsupported proof type: {}", proof_type)),
+    }
+}
+
+/// Proves the main witness of a serialized witness stack, in the gzipped
+/// format of `serialize_witness_stack` and `nargo execute`. noir_rs has no
+/// prove entry point taking a solved witness, so it executes the circuit again
+/// from these values before proving.
+pub fn prove_from_witness_stack(
+    proof_type: &str,
+    circuit_bytecode: &str,
+    witness_stack: &[u8],
+    verification_key: Vec<u8>,
+    low_memory_mode: bool,
+    storage_cap: u64,
+) -> Result<Vec<u8>, String> {
+    let witness_map = witness::deserialize_witness_stack(witness_stack).and_then(|stack| witness::main_witness(&stack))?;
+    prove_with_flavor(proof_type, circuit_bytecode, witness_map.into_inner(), verification_key, low_memory_mode, storage_cap)
+}
+
+/// Number of proofs to generate at the same time given the options. Without a
+/// circuit size the memory budget can't be applied, and proofs are generated
+/// one at a time rather than risking running out of memory.
+pub fn batch_workers(proof_type: &str, items: usize, options: &BatchOptions) -> usize {
+    let mut workers = options.max_parallel.clamp(1, items.max(1));
+    if workers > 1 && options.memory_budget > 0 {
+        if options.circuit_size == 0 {
+            debug!("Circuit size unknown, proving one item at a time to stay in the memory budget");
+            return 1;
+        }
+        let per_proof = match memory::estimate(options.circuit_size, proof_type) {
+            Ok(estimate) => estimate.peak_bytes,
+            // The items fail on the proof type anyway
+            Err(_) => return 1,
+        };
+        let fitting = (options.memory_budget / per_proof.max(1)).max(1) as usize;
+        debug!("About {} bytes per proof, {} proofs fit in the memory budget", per_proof, fitting);
+        workers = workers.min(fitting);
+    }
+    workers
+}
+
+/// Proves every witness of `witnesses` for the same circuit, each in a
+/// `prove_with_flavor` call of its own, run in parallel. Only the checks made
+/// by the caller and the decoding of the verification key are shared: noir_rs
+/// only proves from the bytecode, so barretenberg decodes it and builds the
+/// circuit and the proving key again for every item. An item that can't be
+/// proven (or whose input couldn't be read) gets an error without failing the
+/// rest of the batch.
+///
+/// The proofs generated at the same time read the same SRS, which barretenberg
+/// keeps globally once it is set up and doesn't change while proving
+/// (`SrsInUse` keeps it from being unloaded); test_prove_batch proves several
+/// items at once against it. In low memory mode the items are proven one at a
+/// time, as the storage cap bounds the disk use of a single proof.
+pub fn prove_each(
+    proof_type: &str,
+    circuit_bytecode: &str,
+    witnesses: Vec<WitnessResult>,
+    verification_key: Vec<u8>,
+    low_memory_mode: bool,
+    storage_cap: u64,
+    options: &BatchOptions,
+) -> Vec<ProofResult> {
+    let items = witnesses.len();
+    let workers = if low_memory_mode { 1 } else { batch_workers(proof_type, items, options) };
+    info!("Proving batch of {} items with {} workers", items, workers);
+
+    let prove_item = |index: usize, witness: WitnessResult| {
+        let _span = tracing::info_span!("batch_item", index).entered();
+        let result = witness.and_then(|witness_map| {
+            prove_with_flavor(proof_type, circuit_bytecode, witness_map.into_inner(), verification_key.clone(), low_memory_mode, storage_cap)
+        });
+        match &result {
+            Ok(proof) => debug!("Batch item {} proven, proof size: {} bytes", index, proof.len()),
+            Err(e) => debug!("Batch item {} failed: {}", index, e),
+        }
+        result
+    };
+
+    parallel_map(witnesses, workers, prove_item)
+}
+
+/// Applies `f` to every item using up to `workers` threads, keeping the order of the items
+pub fn parallel_map<T, R, F>(items: Vec<T>, workers: usize, f: F) -> Vec<R>
+where
+    T: Send,
+    R: Send,
+    F: Fn(usize, T) -> R + Sync,
+{
+    let count = items.len();
+    if workers <= 1 || count <= 1 {
+        return items.into_iter().enumerate().map(|(i, item)| f(i, item)).collect();
+    }
+
+    // Workers take the next pending item until none is left
+    let pending: Vec<Mutex<Option<T>>> = items.into_iter().map(|item| Mutex::new(Some(item))).collect();
+    let results: Vec<Mutex<Option<R>>> = (0..count).map(|_| Mutex::new(None)).collect();
+    let next = AtomicUsize::new(0);
+    std::thread::scope(|scope| {
+        for _ in 0..workers.min(count) {
+            scope.spawn(|| loop {
+                let index = next.fetch_add(1, Ordering::SeqCst);
+                if index >= count {
+                    break;
+                }
+                let item = pending[index].lock().unwrap().take().expect("each item is taken once");
+                let result = f(index, item);
+                *results[index].lock().unwrap() = Some(result);
+            });
+        }
+    });
+
+    results
+        .into_iter()
+        .map(|r| r.into_inner().unwrap().expect("every item is processed"))
+        .collect()
+}
+
+/// A proof to check in `verify_batch`, with its key and proof type
+pub struct VerifyItem {
+    pub proof: String,
+    pub vk: String,
+    pub proof_type: String,
+}
+
+/// Outcome of one proof of the batch, serialized as `{"verified": true}` or `{"error": "..."}`
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VerifyItemResult {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub verified: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl From<Result<bool, String>> for VerifyItemResult {
+    fn from(result: Result<bool, String>) -> Self {
+        match result {
+            Ok(verified) => VerifyItemResult { verified: Some(verified), error: None },
+            Err(e) => VerifyItemResult { verified: None, error: Some(e) },
+        }
+    }
+}
+
+pub fn verify_with_flavor(proof_type: &str, proof: Vec<u8>, verification_key: Vec<u8>) -> Result<bool, String> {
+    match proof_type {
+        "ultra_honk" => verify_ultra_honk(proof, verification_key),
+        "ultra_honk_keccak" => verify_ultra_honk_keccak(proof, verification_key, false),
+        _ => Err(format!("Unsupported proof type: {}", proof_type)),
+    }
+}
+
+type Job = Box<dyn FnOnce() + Send>;
+
+/// Threads shared by every `verify_batch` call, so that concurrent batches
+/// queue up instead of each starting threads of its own
+struct VerifyPool {
+    sender: Mutex<mpsc::Sender<Job>>,
+    threads: usize,
+}
+
+static VERIFY_POOL: OnceLock<VerifyPool> = OnceLock::new();
+// Threads the pool is started with, 0 for one per core
+static VERIFY_POOL_SIZE: AtomicUsize = AtomicUsize::new(0);
+
+/// Sets the number of threads of the pool `verify_batch` runs on, one per core
+/// by default. The pool is started by the first batch, after which its size
+/// can't be changed.
+pub fn set_verify_pool_size(threads: usize) -> Result<(), String> {
+    if threads == 0 {
+        return Err("The verification pool needs at least one thread".to_owned());
+    }
+    if let Some(pool) = VERIFY_POOL.get() {
+        return Err(format!("The verification pool is already running with {} threads", pool.threads));
+    }
+    VERIFY_POOL_SIZE.store(threads, Ordering::Relaxed);
+    Ok(())
+}
+
+fn verify_pool() -> &'static VerifyPool {
+    VERIFY_POOL.get_or_init(|| {
+        let (sender, receiver) = mpsc::channel::<Job>();
+        let receiver = Arc::new(Mutex::new(receiver));
+        let size = match VERIFY_POOL_SIZE.load(Ordering::Relaxed) {
+            0 => std::thread::available_parallelism().map_or(1, |n| n.get()),
+            size => size,
+        };
+        let mut threads = 0;
+        for index in 0..size {
+            let receiver = receiver.clone();
+            let spawned = std::thread::Builder::new().name(format!("noir-verify-{}", index)).spawn(move || loop {
+                let job = match receiver.lock().unwrap().recv() {
+                    Ok(job) => job,
+                    Err(_) => break,
+                };
+                // A job that panics must not take the thread down with it
+                if std::panic::catch_unwind(std::panic::AssertUnwindSafe(job)).is_err() {
+                    error!("Verification job panicked");
+                }
+            });
+            match spawned {
+                Ok(_) => threads += 1,
+                Err(e) => error!("Failed to start verification thread: {:?}", e),
+            }
+        }
+        debug!("Started {} verification threads", threads);
+        VerifyPool { sender: Mutex::new(sender), threads }
+    })
+}
+
+type VerifyJob = (usize, String, String, Arc<Result<Vec<u8>, String>>);
+
+fn verify_job((index, proof, proof_type, verification_key): VerifyJob) -> (usize, Result<bool, String>) {
+    let _span = tracing::info_span!("verify_item", index).entered();
+    let verdict = match verification_key.as_ref() {
+        // noir_rs takes the key by value, so each proof gets a copy of it
+        Ok(vk) => hex::decode(&proof)
+            .map_err(|e| format!("Failed to decode proof: {:?}", e))
+            .and_then(|proof| verify_with_flavor(&proof_type, proof, vk.clone())),
+        Err(e) => Err(e.clone()),
+    };
+    (index, verdict)
+}
+
+/// Verifies many hex encoded proofs, returning one verdict per proof in the
+/// order given. Up to `threads` proofs of the batch are checked at the same
+/// time on the threads of a pool shared by all the batches, whose size is set
+/// with `set_verify_pool_size`. Each distinct verification key is only hex
+/// decoded once, but that is all the proofs of a key share: noir_rs verifies
+/// from a serialized key it takes by value, so every proof gets its own copy of
+/// the decoded key, which barretenberg deserializes again.
+pub fn verify_batch(items: Vec<VerifyItem>, threads: usize) -> Vec<Result<bool, String>> {
+    let count = items.len();
+    let mut keys: HashMap<String, Arc<Result<Vec<u8>, String>>> = HashMap::new();
+    let mut jobs: Vec<VerifyJob> = Vec::with_capacity(count);
+    for (index, item) in items.into_iter().enumerate() {
+        let verification_key = keys
+            .entry(item.vk)
+            .or_insert_with_key(|vk| Arc::new(hex::decode(vk).map_err(|e| format!("Failed to decode verification key: {:?}", e))))
+            .clone();
+        jobs.push((index, item.proof, item.proof_type, verification_key));
+    }
+    let pool = verify_pool();
+    let runners = threads.clamp(1, count.max(1)).min(pool.threads);
+    info!("Verifying batch of {} proofs with {} distinct keys on {} threads", count, keys.len(), runners);
+
+    let mut results: Vec<Option<Result<bool, String>>> = (0..count).map(|_| None).collect();
+    if runners == 0 {
+        // No thread could be started, verify on the calling thread
+        for job in jobs {
+            let (index, verdict) = verify_job(job);
+            results[index] = Some(verdict);
+        }
+    } else {
+        // Each runner takes the next pending proof of the batch until none is left
+        let pending = Arc::new(Mutex::new(jobs));
+        let (verdicts, received) = mpsc::channel();
+        let sender = pool.sender.lock().unwrap();
+        for _ in 0..runners {
+            let pending = pending.clone();
+            let verdicts = verdicts.clone();
+            let runner: Job = Box::new(move || loop {
+                let job = pending.lock().unwrap().pop();
+                match job {
+                    Some(job) => {
+                        let _ = verdicts.send(verify_job(job));
+                    }
+                    None => break,
+                }
+            });
+            if sender.send(runner).is_err() {
+                error!("Verification pool is gone");
+            }
+        }
+        drop(sender);
+        drop(verdicts);
+        // Ends early if a runner panicked, the proofs it had left are reported as failed
+        for (index, verdict) in received.iter() {
+            results[index] = Some(verdict);
+        }
+    }
+    results
+        .into_iter()
+        .map(|r| r.unwrap_or_else(|| Err("Verification did not complete".to_owned())))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parallel_map_keeps_order() {
+        let items: Vec<u64> = (0..50).collect();
+        let results = parallel_map(items, 4, |index, item| {
+            std::thread::sleep(std::time::Duration::from_millis(50 - item));
+            (index, item * 2)
+        });
+        assert_eq!(results, (0..50).map(|i| (i as usize, i * 2)).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_batch_workers() {
+        let options = |max_parallel, memory_budget, circuit_size| BatchOptions { max_parallel, memory_budget, circuit_size };
+        let per_proof = memory::estimate(1024, "ultra_honk").unwrap().peak_bytes;
+        assert_eq!(batch_workers("ultra_honk", 10, &options(4, 0, 0)), 4);
+        assert_eq!(batch_workers("ultra_honk", 2, &options(4, 0, 0)), 2);
+        assert_eq!(batch_workers("ultra_honk", 10, &options(4, 3 * per_proof, 1024)), 3);
+        // Without a size the budget can't be checked
+        assert_eq!(batch_workers("ultra_honk", 10, &options(4, 1 << 30, 0)), 1);
+    }
+
+    #[test]
+    fn test_verify_batch_reports_errors_per_proof() {
+        let item = |proof: &str, vk: &str, proof_type: &str| VerifyItem {
+            proof: proof.to_owned(),
+            vk: vk.to_owned(),
+            proof_type: proof_type.to_owned(),
+        };
+        let results = verify_batch(
+            vec![
+                item("00", "not hex", "ultra_honk"),
+                item("00", "00", "plonk"),
+                item("not hex", "00", "ultra_honk"),
+            ],
+            64,
+        );
+        assert_eq!(results.len(), 3);
+        assert!(results[0].as_ref().unwrap_err().contains("verification key"));
+        assert!(results[1].as_ref().unwrap_err().contains("Unsupported proof type"));
+        assert!(results[2].as_ref().unwrap_err().contains("Failed to decode proof"));
+    }
+
+    #[test]
+    fn test_concurrent_verify_batches_share_the_pool() {
+        let items = || (0..20).map(|_| VerifyItem { proof: "00".to_owned(), vk: "not hex".to_owned(), proof_type: "ultra_honk".to_owned() }).collect();
+        std::thread::scope(|scope| {
+            let batches: Vec<_> = (0..4).map(|_| scope.spawn(|| verify_batch(items(), 1000))).collect();
+            for batch in batches {
+                let results = batch.join().unwrap();
+                assert_eq!(results.len(), 20);
+                assert!(results.iter().all(|r| r.as_ref().unwrap_err().contains("verification key")));
+            }
+        });
+        let cores = std::thread::available_parallelism().map_or(1, |n| n.get());
+        assert!(verify_pool().threads <= cores);
+    }
+
+    #[test]
+    fn test_pool_size_is_set_before_the_pool_starts() {
+        assert!(set_verify_pool_size(0).is_err());
+        verify_pool();
+        assert!(set_verify_pool_size(2).unwrap_err().contains("already running"));
+    }
+}
@@ -0,0 +1,131 @@
+//! Host command line tool to move witnesses and proofs between devices and desktop tooling.
+//! Witness files use the same gzipped format as `nargo execute`, so they can be exchanged with nargo and bb.
+//!
+//! cargo run --features cli --bin noir_java_cli -- <command> ...
+
+use noir_java::{batch, circuit, executor, foreign_calls::NoForeignCalls, source_map::SourceMap, witness};
+use noir_rs::{
+    acir::native_types::{Witness, WitnessMap},
+    barretenberg::{
+        srs::setup_srs_from_bytecode,
+        verify::{get_ultra_honk_keccak_verification_key, get_ultra_honk_verification_key},
+    },
+    AcirField, FieldElement,
+};
+use std::collections::BTreeMap;
+use std::fs;
+use std::process::exit;
+
+const USAGE: &str = "Usage:
+  noir_java_cli execute <circuit.json> <inputs.json> <witness.gz>
+  noir_java_cli prove <circuit.json> <witness.gz> <proof> [ultra_honk|ultra_honk_keccak]
+  noir_java_cli verify <circuit.json> <proof> [ultra_honk|ultra_honk_keccak]
+  noir_java_cli profile <circuit.json> <inputs.json> <output> [json|folded]
+
+inputs.json maps witness indices to hex values, as passed to Noir.execute: {\"0\": \"0x3\", \"1\": \"0x4\"}";
+
+fn read_bytecode(circuit_path: &str) -> Result<String, String> {
+    let manifest = fs::read_to_string(circuit_path).map_err(|e| format!("Failed to read {}: {}", circuit_path, e))?;
+    let manifest: serde_json::Value =
+        serde_json::from_str(&manifest).map_err(|e| format!("Failed to parse {}: {}", circuit_path, e))?;
+    manifest["bytecode"]
+        .as_str()
+        .map(|s| s.to_owned())
+        .ok_or_else(|| format!("No bytecode found in {}", circuit_path))
+}
+
+fn read_inputs(inputs_path: &str) -> Result<WitnessMap<FieldElement>, String> {
+    let inputs = fs::read_to_string(inputs_path).map_err(|e| format!("Failed to read {}: {}", inputs_path, e))?;
+    let inputs: BTreeMap<String, String> =
+        serde_json::from_str(&inputs).map_err(|e| format!("Failed to parse {}: {}", inputs_path, e))?;
+    let mut witness_map = WitnessMap::new();
+    for (key, value) in inputs {
+        let index = key.parse().map_err(|e| format!("Failed to parse witness key '{}': {:?}", key, e))?;
+        let value = FieldElement::try_from_str(&value)
+            .ok_or_else(|| format!("Failed to parse value of witness {}: not a valid field element", key))?;
+        witness_map.insert(Witness(index), value);
+    }
+    Ok(witness_map)
+}
+
+fn verification_key(bytecode: &str, proof_type: &str) -> Result<Vec<u8>, String> {
+    setup_srs_from_bytecode(bytecode, None, false)?;
+    match proof_type {
+        "ultra_honk" => get_ultra_honk_verification_key(bytecode, false, None),
+        "ultra_honk_keccak" => get_ultra_honk_keccak_verification_key(bytecode, false, false, None),
+        _ => Err(format!("Unsupported proof type: {}", proof_type)),
+    }
+}
+
+fn run(args: &[String]) -> Result<(), String> {
+    let arg = |i: usize| args.get(i).map(|s| s.as_str()).ok_or_else(|| USAGE.to_owned());
+    match arg(1)? {
+        "execute" => {
+            let bytecode = read_bytecode(arg(2)?)?;
+            let solved_witness = executor::execute_program(&bytecode, read_inputs(arg(3)?)?, &mut NoForeignCalls, Default::default())?;
+            witness::write_witness_file(arg(4)?, &solved_witness)
+        }
+        "prove" => {
+            let bytecode = read_bytecode(arg(2)?)?;
+            let proof_type = args.get(5).map(|s| s.as_str()).unwrap_or("ultra_honk");
+            circuit::ensure_single_function(&bytecode)?;
+            let witness_stack = witness::read_witness_file(arg(3)?)?;
+            let witness_map = witness::main_witness(&witness_stack)?;
+            let vk = verification_key(&bytecode, proof_type)?;
+            let proof = batch::prove_with_flavor(proof_type, &bytecode, witness_map.into_inner(), vk, false, 0)?;
+            fs::write(arg(4)?, &proof).map_err(|e| format!("Failed to write proof: {}", e))?;
+            println!("Proof of {} bytes written to {}", proof.len(), arg(4)?);
+            Ok(())
+        }
+        "verify" => {
+            let bytecode = read_bytecode(arg(2)?)?;
+            let proof_type = args.get(4).map(|s| s.as_str()).unwrap_or("ultra_honk");
+            let proof = fs::read(arg(3)?).map_err(|e| format!("Failed to read proof: {}", e))?;
+            let vk = verification_key(&bytecode, proof_type)?;
+            let verified = batch::verify_with_flavor(proof_type, proof, vk)?;
+            println!("Verified: {}", verified);
+            if verified {
+                Ok(())
+            } else {
+                Err("Proof is invalid".to_owned())
+            }
+        }
+        "profile" => {
+            let path = arg(2)?;
+            let manifest = fs::read_to_string(path).map_err(|e| format!("Failed to read {}: {}", path, e))?;
+            let bytecode = read_bytecode(arg(2)?)?;
+            let format = args.get(5).map(|s| s.as_str()).unwrap_or("json");
+            // Circuits compiled without debug symbols are still profiled, without the Noir functions
+            let source_map = SourceMap::from_manifest(&manifest)
+                .map_err(|e| eprintln!("{}, profiling without source functions", e))
+                .ok();
+            let (_, profile) = executor::profile_program(
+                &bytecode,
+                read_inputs(arg(3)?)?,
+                &mut NoForeignCalls,
+                Default::default(),
+                source_map.as_ref(),
+            )?;
+            let output = match format {
+                "json" => profile.to_json()?,
+                "folded" => profile.to_folded(),
+                _ => return Err(format!("Unsupported profile format: {}", format)),
+            };
+            fs::write(arg(4)?, output).map_err(|e| format!("Failed to write profile: {}", e))?;
+            println!("Executed in {} ms, profile written to {}", profile.total_nanos / 1_000_000, arg(4)?);
+            Ok(())
+        }
+        _ => Err(USAGE.to_owned()),
+    }
+}
+
+fn main() {
+    env_logger::init();
+    // Run by developers on their own witnesses, which they need to see
+    noir_java::logging::set_redaction(false);
+    let args: Vec<String> = std::env::args().collect();
+    if let Err(e) = run(&args) {
+        eprintln!("{}", e);
+        exit(1);
+    }
+}
@@ -0,0 +1,119 @@
+use base64::{engine::general_purpose::STANDARD, Engine};
+use log::debug;
+use noir_rs::{
+    acir::circuit::{Circuit, Opcode, Program},
+    barretenberg::utils::get_circuit_size,
+    FieldElement,
+};
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet};
+
+/// Decodes the base64 gzipped bytecode found in the `bytecode` field of a compiled circuit
+pub fn decode_program(circuit_bytecode: &str) -> Result<Program<FieldElement>, String> {
+    let buffer = STANDARD
+        .decode(circuit_bytecode)
+        .map_err(|e| format!("Failed to decode bytecode from base64: {}", e))?;
+    Program::deserialize_program(&buffer).map_err(|e| format!("Failed to deserialize program: {}", e))
+}
+
+/// Indices of the witnesses holding the return values of the main function
+pub fn return_witnesses(circuit_bytecode: &str) -> Result<Vec<u32>, String> {
+    let program = decode_program(circuit_bytecode)?;
+    let main = program.functions.first().ok_or("Program has no ACIR function")?;
+    Ok(main.return_values.0.iter().map(|witness| witness.0).collect())
+}
+
+/// Name of an ACIR function, falling back to its index for programs compiled
+/// before function names were recorded
+pub fn function_name(circuit: &Circuit<FieldElement>, index: usize) -> String {
+    if circuit.function_name.is_empty() {
+        format!("function {}", index)
+    } else {
+        circuit.function_name.clone()
+    }
+}
+
+/// Fails if the program calls other ACIR functions (e.g. `#[fold]` functions):
+/// barretenberg only builds the circuit of main, so the proof would not cover them
+pub fn ensure_single_function(circuit_bytecode: &str) -> Result<(), String> {
+    let program = decode_program(circuit_bytecode)?;
+    if program.functions.len() > 1 {
+        let names: Vec<String> = program
+            .functions
+            .iter()
+            .enumerate()
+            .map(|(index, circuit)| function_name(circuit, index))
+            .collect();
+        return Err(format!(
+            "Program has {} ACIR functions ({}) but only single function programs can be proven, \
+             inline the other functions or remove their #[fold] attribute",
+            names.len(),
+            names.join(", ")
+        ));
+    }
+    Ok(())
+}
+
+/// Size and composition of a circuit, as returned by `circuit_info`
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CircuitInfo {
+    /// Number of gates of the circuit as built by barretenberg
+    pub gate_count: u32,
+    /// Gate count rounded up to the next power of two, i.e. the dyadic size of
+    /// the polynomials barretenberg proves with and the size to pass to `setup_srs`
+    pub circuit_size: u32,
+    /// Number of public inputs of the main function, return values included
+    pub public_inputs: usize,
+    /// Number of ACIR opcodes of each type across all the ACIR functions
+    pub opcode_counts: BTreeMap<String, usize>,
+    /// Names of the black box functions called by the circuit
+    pub black_box_functions: BTreeSet<String>,
+    /// Number of unconstrained (Brillig) functions
+    pub brillig_functions: usize,
+    /// Names of the ACIR functions, main first
+    pub acir_functions: Vec<String>,
+}
+
+pub fn circuit_info(circuit_bytecode: &str) -> Result<CircuitInfo, String> {
+    let program = decode_program(circuit_bytecode)?;
+    let main = program.functions.first().ok_or("Program has no ACIR function")?;
+
+    let mut opcode_counts = BTreeMap::new();
+    let mut black_box_functions = BTreeSet::new();
+    for opcode in program.functions.iter().flat_map(|function| function.opcodes.iter()) {
+        *opcode_counts.entry(opcode_name(opcode).to_owned()).or_insert(0) += 1;
+        if let Opcode::BlackBoxFuncCall(call) = opcode {
+            black_box_functions.insert(call.name().to_owned());
+        }
+    }
+
+    let gate_count = get_circuit_size(circuit_bytecode, false);
+    debug!("Circuit has {} gates and {} ACIR opcodes", gate_count, opcode_counts.values().sum::<usize>());
+
+    Ok(CircuitInfo {
+        gate_count,
+        circuit_size: gate_count.max(1).next_power_of_two(),
+        public_inputs: main.public_inputs().0.len(),
+        opcode_counts,
+        black_box_functions,
+        brillig_functions: program.unconstrained_functions.len(),
+        acir_functions: program
+            .functions
+            .iter()
+            .enumerate()
+            .map(|(index, circuit)| function_name(circuit, index))
+            .collect(),
+    })
+}
+
+pub fn opcode_name(opcode: &Opcode<FieldElement>) -> &'static str {
+    match opcode {
+        Opcode::AssertZero(_) => "AssertZero",
+        Opcode::BlackBoxFuncCall(_) => "BlackBoxFuncCall",
+        Opcode::MemoryOp { .. } => "MemoryOp",
+        Opcode::MemoryInit { .. } => "MemoryInit",
+        Opcode::BrilligCall { .. } => "BrilligCall",
+        Opcode::Call { .. } => "Call",
+    }
+}
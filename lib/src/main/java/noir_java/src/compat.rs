@@ -0,0 +1,199 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+use crate::circuit;
+
+/// Noir release the ACVM and the bytecode format come from, the tag of `acvm`
+/// in Cargo.toml
+pub const NOIR_VERSION: &str = "1.0.0-beta.14";
+/// Tag of `noir_rs` in Cargo.toml
+pub const NOIR_RS_VERSION: &str = "1.0.0-beta.14-2";
+/// Release of barretenberg that noir_rs builds and links, recorded in
+/// Cargo.toml next to it as noir_rs doesn't expose it
+pub const BARRETENBERG_VERSION: &str = "3.0.0-nightly.20251104";
+
+/// Versions of the native library and of what it links, as returned by `version_info`
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VersionInfo {
+    pub noir_java: &'static str,
+    pub noir_rs: &'static str,
+    pub acvm: &'static str,
+    pub barretenberg: &'static str,
+}
+
+pub fn version_info() -> VersionInfo {
+    VersionInfo {
+        noir_java: env!("CARGO_PKG_VERSION"),
+        noir_rs: NOIR_RS_VERSION,
+        acvm: NOIR_VERSION,
+        barretenberg: BARRETENBERG_VERSION,
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum CompatibilityStatus {
+    Compatible,
+    /// The bytecode could be read, but was compiled by another Noir release
+    Warning,
+    /// The bytecode can't be read by this library
+    Incompatible,
+}
+
+/// Whether a compiled circuit can be used with the linked Noir release, and why
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Compatibility {
+    pub status: CompatibilityStatus,
+    pub reason: String,
+    /// `noir_version` of the manifest, if it has one
+    pub circuit_version: Option<String>,
+    pub library_version: &'static str,
+}
+
+// Fields of the manifest the check needs
+#[derive(Deserialize)]
+struct ManifestHeader {
+    noir_version: Option<String>,
+    bytecode: Option<String>,
+}
+
+/// Release of a `noir_version`, e.g. `1.0.0-beta.14+60ccd48e...`. The commit
+/// after the `+` is left out, as it doesn't change the bytecode format.
+#[derive(Debug, PartialEq, Eq)]
+struct Release {
+    major: u64,
+    minor: u64,
+    patch: u64,
+    pre: Option<String>,
+}
+
+impl Release {
+    fn parse(version: &str) -> Option<Release> {
+        let version = version.trim();
+        let version = version.strip_prefix('v').unwrap_or(version);
+        let release = version.split('+').next()?;
+        let (core, pre) = match release.split_once('-') {
+            Some((core, pre)) if !pre.is_empty() => (core, Some(pre.to_owned())),
+            Some(_) => return None,
+            None => (release, None),
+        };
+        let mut numbers = core.split('.').map(|number| number.parse::<u64>().ok());
+        let release = Release {
+            major: numbers.next()??,
+            minor: numbers.next()??,
+            patch: numbers.next()??,
+            pre,
+        };
+        numbers.next().is_none().then_some(release)
+    }
+}
+
+impl fmt::Display for Release {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)?;
+        if let Some(pre) = &self.pre {
+            write!(f, "-{}", pre)?;
+        }
+        Ok(())
+    }
+}
+
+/// Checks the `noir_version` of a manifest against the linked Noir release.
+/// Circuits from the same release are compatible without further work; for any
+/// other (or no) version, the bytecode is decoded to tell whether it can be read
+/// at all, which fails with an opaque error when the format has changed.
+pub fn check_compatibility(manifest_json: &str) -> Result<Compatibility, String> {
+    let manifest: ManifestHeader =
+        serde_json::from_str(manifest_json).map_err(|e| format!("Failed to parse circuit manifest: {}", e))?;
+    let library = Release::parse(NOIR_VERSION).expect("NOIR_VERSION is a valid release");
+    let circuit_release = manifest.noir_version.as_deref().and_then(Release::parse);
+
+    let (status, reason) = match (&circuit_release, manifest.bytecode.as_deref()) {
+        (_, None) => (CompatibilityStatus::Incompatible, "Manifest has no bytecode".to_owned()),
+        (Some(release), Some(_)) if *release == library => {
+            (CompatibilityStatus::Compatible, format!("Compiled with Noir {}, the release this library runs", release))
+        }
+        (release, Some(bytecode)) => {
+            let compiled_with = match (release, &manifest.noir_version) {
+                (Some(release), _) => format!("Compiled with Noir {}", release),
+                (None, Some(version)) => format!("Compiled with an unknown Noir version ({:?})", version),
+                (None, None) => "Manifest has no noir_version".to_owned(),
+            };
+            match circuit::decode_program(bytecode) {
+                Ok(_) => (
+                    CompatibilityStatus::Warning,
+                    format!(
+                        "{} but this library runs Noir {}. The bytecode can be read, but execution and proofs \
+                         may differ; recompile the circuit with nargo {}",
+                        compiled_with, library, library
+                    ),
+                ),
+                Err(e) => (
+                    CompatibilityStatus::Incompatible,
+                    format!(
+                        "{} but this library runs Noir {}, which can't read its bytecode ({}); recompile \
+                         the circuit with nargo {}",
+                        compiled_with, library, e, library
+                    ),
+                ),
+            }
+        }
+    };
+
+    Ok(Compatibility {
+        status,
+        reason,
+        circuit_version: manifest.noir_version,
+        library_version: NOIR_VERSION,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::TEST_CIRCUIT_BYTECODE;
+
+    fn manifest(noir_version: &str, bytecode: &str) -> String {
+        serde_json::json!({ "noir_version": noir_version, "bytecode": bytecode }).to_string()
+    }
+
+    #[test]
+    fn test_parse_release() {
+        let release = Release::parse("1.0.0-beta.14+60ccd48e18ad8ce50d5ecda9baf813b712145051").unwrap();
+        assert_eq!(release.to_string(), "1.0.0-beta.14");
+        assert_eq!(Release::parse("v0.36.0").unwrap(), Release { major: 0, minor: 36, patch: 0, pre: None });
+        assert!(Release::parse("1.0").is_none());
+        assert!(Release::parse("1.0.0-").is_none());
+        assert!(Release::parse("latest").is_none());
+    }
+
+    #[test]
+    fn test_check_compatibility() {
+        let same = check_compatibility(&manifest("1.0.0-beta.14+60ccd48e18ad8ce50d5ecda9baf813b712145051", "not read")).unwrap();
+        assert_eq!(same.status, CompatibilityStatus::Compatible);
+
+        let readable = check_compatibility(&manifest("1.0.0-beta.13+abc", TEST_CIRCUIT_BYTECODE)).unwrap();
+        assert_eq!(readable.status, CompatibilityStatus::Warning);
+        assert!(readable.reason.starts_with("Compiled with Noir 1.0.0-beta.13 but this library runs Noir 1.0.0-beta.14"));
+
+        let unreadable = check_compatibility(&manifest("0.36.0", "not base64")).unwrap();
+        assert_eq!(unreadable.status, CompatibilityStatus::Incompatible);
+        assert_eq!(unreadable.circuit_version.as_deref(), Some("0.36.0"));
+
+        let no_version = check_compatibility(r#"{"bytecode":"not base64"}"#).unwrap();
+        assert_eq!(no_version.status, CompatibilityStatus::Incompatible);
+        assert!(no_version.reason.starts_with("Manifest has no noir_version"));
+
+        assert!(check_compatibility("{").is_err());
+    }
+
+    #[test]
+    fn test_versions_match_cargo_toml() {
+        let manifest = include_str!("../Cargo.toml");
+        assert!(manifest.contains(&format!("tag = \"v{}\"", NOIR_VERSION)));
+        assert!(manifest.contains(&format!("tag = \"v{}\"", NOIR_RS_VERSION)));
+        assert!(manifest.contains(&format!("barretenberg = \"{}\"", BARRETENBERG_VERSION)));
+    }
+}
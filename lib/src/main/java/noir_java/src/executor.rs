@@ -0,0 +1,503 @@
+use acvm::{
+    acir::{
+        brillig::ForeignCallResult,
+        circuit::{brillig::BrilligBytecode, Circuit, Opcode, Program},
+        native_types::{Witness, WitnessMap, WitnessStack},
+    },
+    pwg::{ACVMStatus, BrilligSolver, BrilligSolverStatus, ForeignCallWaitInfo, StepResult, ACVM},
+    AcirField, FieldElement,
+};
+use bn254_blackbox_solver::Bn254BlackBoxSolver;
+use log::debug;
+use serde::Serialize;
+use std::fmt;
+use std::time::{Duration, Instant};
+
+use crate::circuit::{decode_program, function_name};
+use crate::foreign_calls::{resolve_builtin, ForeignCallResolver};
+use crate::logging;
+use crate::profile::{ExecutionProfile, ProfileKey, ProfileSamples};
+use crate::source_map::SourceMap;
+use crate::secret::{SecretWitnessMap, SecretWitnessStack, WipeFreed, Zeroizing};
+use crate::witness::select_witnesses;
+
+// Reading the clock at every opcode would slow tight Brillig loops down
+const TIMEOUT_CHECK_INTERVAL: u64 = 1024;
+
+/// Bounds on the execution of a program, so that unconstrained code that
+/// loops forever can't hang the calling thread
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ExecutionLimits {
+    /// Maximum number of Brillig opcodes executed across the whole program
+    pub max_brillig_steps: Option<u64>,
+    /// Maximum wall-clock duration of the execution, foreign calls included.
+    /// A foreign call isn't interrupted: the deadline is checked before and
+    /// after each one, so a handler that blocks delays the timeout until it returns
+    pub timeout: Option<Duration>,
+}
+
+/// Where an execution stopped
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ExecutionLocation {
+    pub acir_function: String,
+    pub acir_index: usize,
+    /// Name of the unconstrained function being run and its program counter
+    pub brillig: Option<(String, usize)>,
+}
+
+impl fmt::Display for ExecutionLocation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} at ACIR opcode {}", self.acir_function, self.acir_index)?;
+        if let Some((function, pc)) = &self.brillig {
+            write!(f, ", in unconstrained function {} at Brillig opcode {}", function, pc)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+pub enum ExecutionError {
+    Failed(String),
+    StepBudgetExceeded { max_steps: u64, location: ExecutionLocation },
+    TimedOut { timeout: Duration, location: ExecutionLocation },
+}
+
+impl ExecutionError {
+    /// Java exception thrown for this error
+    pub fn exception_class(&self) -> &'static str {
+        match self {
+            ExecutionError::Failed(_) => "java/lang/RuntimeException",
+            _ => "com/noirandroid/lib/ExecutionLimitException",
+        }
+    }
+}
+
+impl fmt::Display for ExecutionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ExecutionError::Failed(e) => write!(f, "{}", e),
+            ExecutionError::StepBudgetExceeded { max_steps, location } => {
+                write!(f, "Brillig step budget of {} exceeded in {}", max_steps, location)
+            }
+            ExecutionError::TimedOut { timeout, location } => {
+                write!(f, "Execution timed out after {} ms in {}", timeout.as_millis(), location)
+            }
+        }
+    }
+}
+
+impl From<String> for ExecutionError {
+    fn from(e: String) -> Self {
+        ExecutionError::Failed(e)
+    }
+}
+
+impl From<ExecutionError> for String {
+    fn from(e: ExecutionError) -> Self {
+        e.to_string()
+    }
+}
+
+/// Solves every ACIR function of a program, following the calls between them
+/// the way `nargo execute` does. Opcodes are solved one at a time, Brillig ones
+/// included, so the limits can stop the execution anywhere.
+struct ProgramExecutor<'a, 'r> {
+    functions: &'a [Circuit<FieldElement>],
+    unconstrained_functions: &'a [BrilligBytecode<FieldElement>],
+    blackbox_solver: &'a Bn254BlackBoxSolver,
+    foreign_call_resolver: &'r mut dyn ForeignCallResolver,
+    // Wiped if the execution fails
+    witness_stack: SecretWitnessStack,
+    limits: ExecutionLimits,
+    started: Instant,
+    brillig_steps: u64,
+    steps: u64,
+    profile: Option<ProfileSamples>,
+}
+
+impl<'a, 'r> ProgramExecutor<'a, 'r> {
+    fn new(
+        program: &'a Program<FieldElement>,
+        blackbox_solver: &'a Bn254BlackBoxSolver,
+        foreign_call_resolver: &'r mut dyn ForeignCallResolver,
+        limits: ExecutionLimits,
+        profiling: bool,
+    ) -> Self {
+        ProgramExecutor {
+            functions: &program.functions,
+            unconstrained_functions: &program.unconstrained_functions,
+            blackbox_solver,
+            foreign_call_resolver,
+            witness_stack: SecretWitnessStack::default(),
+            limits,
+            started: Instant::now(),
+            brillig_steps: 0,
+            steps: 0,
+            profile: profiling.then(ProfileSamples::default),
+        }
+    }
+
+    // Unconstrained function called by an ACIR opcode
+    fn brillig_function_id(&self, index: usize, acir_index: usize) -> Option<usize> {
+        match self.functions[index].opcodes.get(acir_index) {
+            Some(Opcode::BrilligCall { id, .. }) => Some(id.as_usize()),
+            _ => None,
+        }
+    }
+
+    fn profile_key(
+        &self,
+        index: usize,
+        acir_index: usize,
+        brillig_solver: Option<&BrilligSolver<FieldElement, Bn254BlackBoxSolver>>,
+    ) -> ProfileKey {
+        match (brillig_solver, self.brillig_function_id(index, acir_index)) {
+            (Some(solver), Some(brillig_function)) => ProfileKey::Brillig {
+                function: index,
+                opcode: acir_index,
+                brillig_function,
+                pc: solver.program_counter(),
+            },
+            _ => ProfileKey::Acir { function: index, opcode: acir_index },
+        }
+    }
+
+    fn location(
+        &self,
+        index: usize,
+        acir_index: usize,
+        brillig_solver: Option<&BrilligSolver<FieldElement, Bn254BlackBoxSolver>>,
+    ) -> ExecutionLocation {
+        let circuit = &self.functions[index];
+        let brillig = match (brillig_solver, self.brillig_function_id(index, acir_index)) {
+            (Some(solver), Some(id)) => {
+                let name = self
+                    .unconstrained_functions
+                    .get(id)
+                    .map(|function| function.function_name.clone())
+                    .filter(|name| !name.is_empty())
+                    .unwrap_or_else(|| format!("{}", id));
+                Some((name, solver.program_counter()))
+            }
+            _ => None,
+        };
+        ExecutionLocation { acir_function: function_name(circuit, index), acir_index, brillig }
+    }
+
+    // Counts one more opcode and fails if a limit is hit
+    fn check_limits(
+        &mut self,
+        index: usize,
+        acir_index: usize,
+        brillig_solver: Option<&BrilligSolver<FieldElement, Bn254BlackBoxSolver>>,
+    ) -> Result<(), ExecutionError> {
+        self.steps += 1;
+        if brillig_solver.is_some() {
+            self.brillig_steps += 1;
+            if let Some(max_steps) = self.limits.max_brillig_steps {
+                if self.brillig_steps > max_steps {
+                    let location = self.location(index, acir_index, brillig_solver);
+                    return Err(ExecutionError::StepBudgetExceeded { max_steps, location });
+                }
+            }
+        }
+        if self.steps.is_multiple_of(TIMEOUT_CHECK_INTERVAL) {
+            self.check_deadline(index, acir_index, brillig_solver)?;
+        }
+        Ok(())
+    }
+
+    fn check_deadline(
+        &self,
+        index: usize,
+        acir_index: usize,
+        brillig_solver: Option<&BrilligSolver<FieldElement, Bn254BlackBoxSolver>>,
+    ) -> Result<(), ExecutionError> {
+        if let Some(timeout) = self.limits.timeout {
+            if self.started.elapsed() > timeout {
+                let location = self.location(index, acir_index, brillig_solver);
+                return Err(ExecutionError::TimedOut { timeout, location });
+            }
+        }
+        Ok(())
+    }
+
+    // The call itself can't be interrupted, so the deadline is checked around it:
+    // a handler that blocks fails the execution once it returns
+    fn resolve_foreign_call(
+        &mut self,
+        index: usize,
+        acir_index: usize,
+        brillig_solver: Option<&BrilligSolver<FieldElement, Bn254BlackBoxSolver>>,
+        foreign_call: &ForeignCallWaitInfo<FieldElement>,
+    ) -> Result<ForeignCallResult<FieldElement>, ExecutionError> {
+        debug!("Resolving foreign call {}", foreign_call.function);
+        self.check_deadline(index, acir_index, brillig_solver)?;
+        let result = match resolve_builtin(foreign_call, &mut *self.foreign_call_resolver) {
+            Some(result) => result,
+            None => self.foreign_call_resolver.resolve(foreign_call),
+        };
+        self.check_deadline(index, acir_index, brillig_solver)?;
+        // The error of the resolver can hold the arguments of the call
+        Ok(result.map_err(|e| format!("Foreign call {} failed: {}", foreign_call.function, logging::secret(e)))?)
+    }
+
+    fn execute_circuit(
+        &mut self,
+        index: usize,
+        initial_witness: WitnessMap<FieldElement>,
+    ) -> Result<WitnessMap<FieldElement>, ExecutionError> {
+        let functions = self.functions;
+        let circuit = &functions[index];
+        debug!("Executing ACIR function {}", function_name(circuit, index));
+        let _span = tracing::info_span!("acir_function", name = %function_name(circuit, index), index).entered();
+        let mut acvm = ACVM::new(
+            self.blackbox_solver,
+            &circuit.opcodes,
+            initial_witness,
+            self.unconstrained_functions,
+            &circuit.assert_messages,
+        );
+        if circuit.opcodes.is_empty() {
+            return Ok(acvm.finalize());
+        }
+        match self.solve(index, &mut acvm) {
+            Ok(()) => Ok(acvm.finalize()),
+            Err(e) => {
+                wipe_acvm_witness(&mut acvm);
+                Err(e)
+            }
+        }
+    }
+
+    fn solve(
+        &mut self,
+        index: usize,
+        acvm: &mut ACVM<'a, FieldElement, Bn254BlackBoxSolver>,
+    ) -> Result<(), ExecutionError> {
+        let functions = self.functions;
+        let circuit = &functions[index];
+        // Set while an unconstrained function is being run
+        let mut brillig_solver: Option<BrilligSolver<FieldElement, Bn254BlackBoxSolver>> = None;
+        loop {
+            let acir_index = acvm.instruction_pointer();
+            self.check_limits(index, acir_index, brillig_solver.as_ref())?;
+            let sample = self
+                .profile
+                .is_some()
+                .then(|| (self.profile_key(index, acir_index, brillig_solver.as_ref()), Instant::now()));
+            let status = self.step(index, acir_index, acvm, &mut brillig_solver)?;
+            if let (Some(profile), Some((key, started))) = (self.profile.as_mut(), sample) {
+                profile.record(key, started.elapsed());
+            }
+            let Some(status) = status else { continue };
+
+            match status {
+                ACVMStatus::Solved => return Ok(()),
+                ACVMStatus::InProgress => {}
+                ACVMStatus::Failure(e) => {
+                    // The failure can hold witness values, e.g. in an assertion message
+                    return Err(format!("Execution failed in {}: {}", function_name(circuit, index), logging::secret(e)).into());
+                }
+                ACVMStatus::RequiresForeignCall(foreign_call) => {
+                    let result = self.resolve_foreign_call(index, acir_index, None, &foreign_call)?;
+                    acvm.resolve_pending_foreign_call(result);
+                }
+                ACVMStatus::RequiresAcirCall(call_info) => {
+                    let callee = call_info.id.as_usize();
+                    if callee >= functions.len() {
+                        return Err(format!("Call to unknown ACIR function {}", callee).into());
+                    }
+                    let call_solved_witness = SecretWitnessMap::new(self.execute_circuit(callee, call_info.initial_witness)?);
+                    let mut call_resolved_outputs = Vec::new();
+                    for return_witness in functions[callee].return_values.0.iter() {
+                        let value = call_solved_witness.get(return_witness).ok_or_else(|| {
+                            format!(
+                                "Return value {} of {} was not solved",
+                                return_witness.0,
+                                function_name(&functions[callee], callee)
+                            )
+                        })?;
+                        call_resolved_outputs.push(*value);
+                    }
+                    acvm.resolve_pending_acir_call(call_resolved_outputs);
+                    self.witness_stack.push(callee as u32, call_solved_witness.into_inner());
+                }
+            }
+        }
+    }
+
+    // Solves the next ACIR opcode, or runs the next Brillig opcode while inside an
+    // unconstrained function, returning the new status once an ACIR opcode is done
+    fn step(
+        &mut self,
+        index: usize,
+        acir_index: usize,
+        acvm: &mut ACVM<'a, FieldElement, Bn254BlackBoxSolver>,
+        brillig_solver: &mut Option<BrilligSolver<'a, FieldElement, Bn254BlackBoxSolver>>,
+    ) -> Result<Option<ACVMStatus<FieldElement>>, ExecutionError> {
+        let status = match brillig_solver.take() {
+            Some(mut solver) => match solver.step() {
+                Ok(BrilligSolverStatus::InProgress) => {
+                    *brillig_solver = Some(solver);
+                    None
+                }
+                Ok(BrilligSolverStatus::Finished) => Some(acvm.finish_brillig_with_solver(solver)),
+                Ok(BrilligSolverStatus::ForeignCallWait(foreign_call)) => {
+                    let result = self.resolve_foreign_call(index, acir_index, Some(&solver), &foreign_call)?;
+                    solver.resolve_pending_foreign_call(result);
+                    *brillig_solver = Some(solver);
+                    None
+                }
+                Err(e) => Some(ACVMStatus::Failure(e)),
+            },
+            None => match acvm.step_into_brillig() {
+                StepResult::IntoBrillig(solver) => {
+                    *brillig_solver = Some(solver);
+                    None
+                }
+                StepResult::Status(status) => Some(status),
+            },
+        };
+        Ok(status)
+    }
+}
+
+#[cfg(test)]
+thread_local! {
+    static LAST_FAILED_WITNESS: std::cell::RefCell<Option<WitnessMap<FieldElement>>> = const { std::cell::RefCell::new(None) };
+}
+
+/// Witness left in the ACVM by the last execution that failed on this thread,
+/// to check that it was wiped
+#[cfg(test)]
+pub fn last_failed_witness() -> Option<WitnessMap<FieldElement>> {
+    LAST_FAILED_WITNESS.with(|witness| witness.borrow().clone())
+}
+
+// ACVM::finalize panics unless the circuit was solved, so the witness of a
+// failed execution is overwritten in place instead
+fn wipe_acvm_witness(acvm: &mut ACVM<'_, FieldElement, Bn254BlackBoxSolver>) {
+    let witnesses: Vec<Witness> = acvm.witness_map().into_iter().map(|(witness, _)| *witness).collect();
+    for witness in witnesses {
+        acvm.overwrite_witness(witness, FieldElement::zero());
+    }
+    #[cfg(test)]
+    LAST_FAILED_WITNESS.with(|witness| *witness.borrow_mut() = Some(acvm.witness_map().clone()));
+}
+
+fn run_program(
+    program: &Program<FieldElement>,
+    initial_witness: WitnessMap<FieldElement>,
+    foreign_call_resolver: &mut dyn ForeignCallResolver,
+    limits: ExecutionLimits,
+    profiling: bool,
+) -> Result<(SecretWitnessStack, Option<ProfileSamples>), ExecutionError> {
+    if program.functions.is_empty() {
+        return Err("Program has no ACIR function".to_owned().into());
+    }
+    // Covers what the ACVM and the Brillig VM free, such as the memory of an
+    // unconstrained function that failed
+    let _wipe_freed = WipeFreed::start();
+    let blackbox_solver = Bn254BlackBoxSolver(false);
+    let mut executor = ProgramExecutor::new(program, &blackbox_solver, foreign_call_resolver, limits, profiling);
+    let main_witness = executor.execute_circuit(0, initial_witness)?;
+    executor.witness_stack.push(0, main_witness);
+    debug!(
+        "Program solved with {} ACIR function calls, {} Brillig opcodes executed",
+        executor.witness_stack.length(),
+        executor.brillig_steps
+    );
+    let elapsed = executor.started.elapsed();
+    let samples = executor.profile.map(|mut samples| {
+        samples.total = elapsed;
+        samples
+    });
+    Ok((executor.witness_stack, samples))
+}
+
+/// Executes a program and returns the witness of every ACIR function call,
+/// with the main function on top of the stack. Foreign calls other than the
+/// built-in ones go to `foreign_call_resolver`. The witnesses are wiped once
+/// the returned stack is dropped.
+pub fn execute_program(
+    circuit_bytecode: &str,
+    initial_witness: WitnessMap<FieldElement>,
+    foreign_call_resolver: &mut dyn ForeignCallResolver,
+    limits: ExecutionLimits,
+) -> Result<SecretWitnessStack, ExecutionError> {
+    let program = decode_program(circuit_bytecode)?;
+    let (witness_stack, _) = run_program(&program, initial_witness, foreign_call_resolver, limits, false)?;
+    Ok(witness_stack)
+}
+
+/// Executes a program like `execute_program` while timing every opcode. The
+/// profile is mapped back to the Noir source if `source_map` is given.
+pub fn profile_program(
+    circuit_bytecode: &str,
+    initial_witness: WitnessMap<FieldElement>,
+    foreign_call_resolver: &mut dyn ForeignCallResolver,
+    limits: ExecutionLimits,
+    source_map: Option<&SourceMap>,
+) -> Result<(SecretWitnessStack, ExecutionProfile), ExecutionError> {
+    let program = decode_program(circuit_bytecode)?;
+    let (witness_stack, samples) = run_program(&program, initial_witness, foreign_call_resolver, limits, true)?;
+    let profile = ExecutionProfile::new(&program, samples.unwrap_or_default(), source_map);
+    Ok((witness_stack, profile))
+}
+
+/// Solved witness of one ACIR function call, as returned by `execute_witness_stack`
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FunctionWitness {
+    /// Index of the function in the program, 0 being main
+    pub function: u32,
+    pub name: String,
+    pub indices: Vec<u32>,
+    pub values: Zeroizing<Vec<String>>,
+}
+
+/// Lists the witnesses of a solved stack in call order, main coming last
+pub fn function_witnesses(
+    program: &Program<FieldElement>,
+    witness_stack: &WitnessStack<FieldElement>,
+) -> Result<Vec<FunctionWitness>, String> {
+    let mut stack = SecretWitnessStack::new(witness_stack.clone());
+    let mut witnesses = Vec::with_capacity(stack.length());
+    while let Some(item) = stack.pop() {
+        let name = program
+            .functions
+            .get(item.index as usize)
+            .map(|circuit| function_name(circuit, item.index as usize))
+            .ok_or_else(|| format!("Witness stack refers to unknown ACIR function {}", item.index))?;
+        let witness = SecretWitnessMap::new(item.witness);
+        let (indices, values) = select_witnesses(&witness, None)?;
+        witnesses.push(FunctionWitness { function: item.index, name, indices, values });
+    }
+    witnesses.reverse();
+    Ok(witnesses)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_limit_errors_name_the_location() {
+        let location = ExecutionLocation {
+            acir_function: "main".to_owned(),
+            acir_index: 3,
+            brillig: Some(("find_root".to_owned(), 42)),
+        };
+        let error = ExecutionError::StepBudgetExceeded { max_steps: 1000, location: location.clone() };
+        assert_eq!(
+            error.to_string(),
+            "Brillig step budget of 1000 exceeded in main at ACIR opcode 3, in unconstrained function find_root at Brillig opcode 42"
+        );
+        assert_eq!(error.exception_class(), "com/noirandroid/lib/ExecutionLimitException");
+
+        let error = ExecutionError::TimedOut { timeout: Duration::from_millis(500), location };
+        assert!(error.to_string().starts_with("Execution timed out after 500 ms in main"));
+        assert_eq!(ExecutionError::from("failed".to_owned()).exception_class(), "java/lang/RuntimeException");
+    }
+}
@@ -0,0 +1,191 @@
+use acvm::{
+    acir::brillig::{ForeignCallParam, ForeignCallResult},
+    pwg::ForeignCallWaitInfo,
+    AcirField, FieldElement,
+};
+use log::{error, info};
+use noirc_printable_type::PrintableValueDisplay;
+use serde::{Deserialize, Serialize};
+
+use crate::logging;
+
+/// Resolves the foreign calls (oracles) made by unconstrained code during execution
+pub trait ForeignCallResolver {
+    fn resolve(&mut self, call: &ForeignCallWaitInfo<FieldElement>) -> Result<ForeignCallResult<FieldElement>, String>;
+
+    /// Receives the output of `print` and `println`, which is also logged,
+    /// ending with a newline for `println`
+    fn print(&mut self, _message: &str) {}
+}
+
+/// Resolver for programs that only use the built-in foreign calls
+pub struct NoForeignCalls;
+
+impl ForeignCallResolver for NoForeignCalls {
+    fn resolve(&mut self, call: &ForeignCallWaitInfo<FieldElement>) -> Result<ForeignCallResult<FieldElement>, String> {
+        Err(format!("Unsupported foreign call: {}, pass a ForeignCallHandler to resolve it", call.function))
+    }
+}
+
+/// Resolves the foreign calls made by the Noir standard library itself,
+/// returning `None` for the ones the caller has to resolve
+pub fn resolve_builtin(
+    call: &ForeignCallWaitInfo<FieldElement>,
+    resolver: &mut dyn ForeignCallResolver,
+) -> Option<Result<ForeignCallResult<FieldElement>, String>> {
+    match call.function.as_str() {
+        "print" => {
+            match decode_print(&call.inputs) {
+                Ok(message) => {
+                    // Prints often show witness values
+                    info!("{}", logging::secret(message.trim_end_matches('\n')));
+                    resolver.print(&message);
+                }
+                // Printing is only a debugging aid, so it never fails the execution
+                Err(e) => error!("Failed to decode print call: {}", e),
+            }
+            Some(Ok(ForeignCallResult::default()))
+        }
+        _ => None,
+    }
+}
+
+/// Formats the arguments of a `print` call: whether to end with a newline, then
+/// the values with the type information the compiler embeds next to them
+pub fn decode_print(inputs: &[ForeignCallParam<FieldElement>]) -> Result<String, String> {
+    let (newline, values) = inputs.split_first().ok_or("print call has no inputs")?;
+    let newline = match newline {
+        ForeignCallParam::Single(newline) => !newline.is_zero(),
+        ForeignCallParam::Array(_) => return Err("print call has no newline flag".to_owned()),
+    };
+    let display = PrintableValueDisplay::<FieldElement>::try_from(values).map_err(|e| e.to_string())?;
+    Ok(format!("{}{}", display, if newline { "\n" } else { "" }))
+}
+
+/// A foreign call argument or return value as exchanged with Java,
+/// serialized as `{"single": "0x..."}` or `{"array": ["0x...", ...]}`
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ForeignCallValue {
+    Single(String),
+    Array(Vec<String>),
+}
+
+impl From<&ForeignCallParam<FieldElement>> for ForeignCallValue {
+    fn from(param: &ForeignCallParam<FieldElement>) -> Self {
+        match param {
+            ForeignCallParam::Single(value) => ForeignCallValue::Single(format!("0x{}", value.to_hex())),
+            ForeignCallParam::Array(values) => {
+                ForeignCallValue::Array(values.iter().map(|value| format!("0x{}", value.to_hex())).collect())
+            }
+        }
+    }
+}
+
+impl TryFrom<ForeignCallValue> for ForeignCallParam<FieldElement> {
+    type Error = String;
+
+    fn try_from(value: ForeignCallValue) -> Result<Self, String> {
+        let parse = |value: &str| {
+            FieldElement::try_from_str(value).ok_or_else(|| format!("Invalid field element '{}' returned by foreign call", logging::secret(value)))
+        };
+        match value {
+            ForeignCallValue::Single(value) => Ok(ForeignCallParam::Single(parse(&value)?)),
+            ForeignCallValue::Array(values) => {
+                Ok(ForeignCallParam::Array(values.iter().map(|value| parse(value)).collect::<Result<_, _>>()?))
+            }
+        }
+    }
+}
+
+/// JSON array of the arguments of a foreign call
+pub fn encode_inputs(call: &ForeignCallWaitInfo<FieldElement>) -> Result<String, String> {
+    let inputs: Vec<ForeignCallValue> = call.inputs.iter().map(ForeignCallValue::from).collect();
+    serde_json::to_string(&inputs).map_err(|e| format!("Failed to serialize foreign call inputs: {:?}", e))
+}
+
+/// Parses the JSON array of values returned for a foreign call
+pub fn decode_outputs(outputs: &str) -> Result<ForeignCallResult<FieldElement>, String> {
+    let outputs: Vec<ForeignCallValue> =
+        serde_json::from_str(outputs).map_err(|e| format!("Failed to parse foreign call outputs: {}", e))?;
+    let values = outputs.into_iter().map(ForeignCallParam::try_from).collect::<Result<Vec<_>, _>>()?;
+    Ok(ForeignCallResult { values })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_inputs_and_outputs_round_trip() {
+        let call = ForeignCallWaitInfo {
+            function: "get_hint".to_owned(),
+            inputs: vec![
+                ForeignCallParam::Single(FieldElement::from(5u128)),
+                ForeignCallParam::Array(vec![FieldElement::from(1u128), FieldElement::from(2u128)]),
+            ],
+        };
+        let inputs = encode_inputs(&call).unwrap();
+        assert!(inputs.starts_with(r#"[{"single":"0x"#));
+
+        let result = decode_outputs(&inputs).unwrap();
+        assert_eq!(result.values, call.inputs);
+    }
+
+    #[test]
+    fn test_outputs_accept_decimal_and_reject_garbage() {
+        let result = decode_outputs(r#"[{"single": "42"}, {"array": []}]"#).unwrap();
+        assert_eq!(result.values[0], ForeignCallParam::Single(FieldElement::from(42u128)));
+        assert!(decode_outputs(r#"[{"single": "not a field"}]"#).is_err());
+        assert!(decode_outputs(r#"{"single": "1"}"#).is_err());
+    }
+
+    struct RecordPrints(Vec<String>);
+
+    impl ForeignCallResolver for RecordPrints {
+        fn resolve(&mut self, call: &ForeignCallWaitInfo<FieldElement>) -> Result<ForeignCallResult<FieldElement>, String> {
+            NoForeignCalls.resolve(call)
+        }
+
+        fn print(&mut self, message: &str) {
+            self.0.push(message.to_owned());
+        }
+    }
+
+    fn string_param(value: &str) -> ForeignCallParam<FieldElement> {
+        ForeignCallParam::Array(value.bytes().map(|b| FieldElement::from(b as u128)).collect())
+    }
+
+    // Inputs of `println("hi")` or `print("hi")` as generated by the compiler
+    fn print_call(newline: bool) -> ForeignCallWaitInfo<FieldElement> {
+        ForeignCallWaitInfo {
+            function: "print".to_owned(),
+            inputs: vec![
+                ForeignCallParam::Single(FieldElement::from(newline as u128)),
+                string_param("hi"),
+                string_param(r#"{"kind":"string","length":2}"#),
+                ForeignCallParam::Single(FieldElement::from(0u128)),
+            ],
+        }
+    }
+
+    #[test]
+    fn test_print_is_decoded_and_forwarded() {
+        let mut resolver = RecordPrints(Vec::new());
+        assert!(resolve_builtin(&print_call(true), &mut resolver).unwrap().is_ok());
+        assert!(resolve_builtin(&print_call(false), &mut resolver).unwrap().is_ok());
+        assert_eq!(resolver.0, vec!["hi\n", "hi"]);
+
+        // A malformed print is logged without failing the execution
+        let empty = ForeignCallWaitInfo { function: "print".to_owned(), inputs: vec![] };
+        assert!(resolve_builtin(&empty, &mut resolver).unwrap().is_ok());
+        assert_eq!(resolver.0.len(), 2);
+    }
+
+    #[test]
+    fn test_only_print_is_builtin() {
+        let call = ForeignCallWaitInfo { function: "get_hint".to_owned(), inputs: vec![] };
+        assert!(resolve_builtin(&call, &mut NoForeignCalls).is_none());
+        assert!(NoForeignCalls.resolve(&call).unwrap_err().contains("get_hint"));
+    }
+}
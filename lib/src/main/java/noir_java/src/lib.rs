@@ -1,18 +1,47 @@
-use jni::objects::{JClass, JObject, JString};
-use jni::sys::{jboolean, jobject, jint, jlong};
+use jni::objects::{JByteArray, JClass, JLongArray, JObject, JObjectArray, JString, JValue};
+use jni::sys::{jboolean, jbyteArray, jobject, jint, jlong};
 use jni::JNIEnv;
 use noir_rs::{
-    acir::native_types::{Witness, WitnessMap},
+    acir::native_types::Witness,
     barretenberg::{
         prove::{prove_ultra_honk, prove_ultra_honk_keccak},
         verify::{verify_ultra_honk, get_ultra_honk_verification_key, verify_ultra_honk_keccak, get_ultra_honk_keccak_verification_key},
-        srs::{setup_srs, setup_srs_from_bytecode},
+        srs::setup_srs,
+        utils::get_circuit_size,
     },
     FieldElement,
     AcirField,
-    execute::execute,
 };
-use log::{info, error, debug};
+use acvm::{acir::brillig::ForeignCallResult, pwg::ForeignCallWaitInfo};
+use foreign_calls::ForeignCallResolver;
+use log::{info, warn, error, debug};
+
+pub mod batch;
+pub mod circuit;
+pub mod compat;
+pub mod executor;
+pub mod foreign_calls;
+pub mod logging;
+mod log_sink;
+pub mod manifest;
+pub mod memory;
+mod metrics;
+mod release;
+mod scratch;
+pub mod profile;
+pub mod secret;
+pub mod source_map;
+mod srs;
+mod trace;
+mod vk_cache;
+pub mod witness;
+
+// Wipes what is freed while witnesses are being handled, see secret::WipeFreed.
+// Only in the Android library (and the tests), so that crates linking this one,
+// like the host command line tool, keep their own allocator
+#[cfg(any(target_os = "android", test))]
+#[global_allocator]
+static ALLOCATOR: secret::WipingAllocator = secret::WipingAllocator;
 
 #[cfg(test)]
 mod test_utils;
@@ -27,36 +56,210 @@ fn throw_exception(env: &mut JNIEnv, exception_class: &str, message: &str) {
     }
 }
 
-// Initialize Android logger if not already initialized
-fn init_logger() {
-    #[cfg(target_os = "android")]
-    {
-        use android_logger::{Config, FilterBuilder};
-        use log::LevelFilter;
+// Reads a Java Map<String, String> of witness indices to hex values. What was
+// read is wiped if a later entry fails to parse
+fn read_witness_map(env: &mut JNIEnv, witness_jobject: &JObject) -> Result<secret::SecretWitnessMap, String> {
+    let java_map = env.get_map(witness_jobject).map_err(|e| format!("Failed to get witness map: {:?}", e))?;
+    let mut witness_iter = java_map.iter(env).map_err(|e| format!("Failed to create iterator: {:?}", e))?;
 
-        static LOGGER_INITIALIZED: std::sync::Once = std::sync::Once::new();
-        
-        LOGGER_INITIALIZED.call_once(|| {
-            android_logger::init_once(
-                Config::default()
-                    .with_max_level(LevelFilter::Debug)
-                    .with_tag("NoirAndroid")
-                    .with_filter(FilterBuilder::new().parse("debug").build()),
-            );
-            debug!("Android logger initialized for NoirAndroid");
-        });
-    }
-
-    #[cfg(not(target_os = "android"))]
-    {
-        use env_logger;
-        static LOGGER_INITIALIZED: std::sync::Once = std::sync::Once::new();
-        
-        LOGGER_INITIALIZED.call_once(|| {
-            env_logger::init();
-            debug!("Env logger initialized for NoirAndroid");
-        });
+    let mut witness_map = secret::SecretWitnessMap::default();
+    while let Ok(Some((key, value))) = witness_iter.next(env) {
+        let key_str = JString::from(key);
+        let value_str = JString::from(value);
+        let key: String = env.get_string(&key_str).map_err(|e| format!("Failed to get key string: {:?}", e))?.into();
+        let value = secret::Zeroizing::new(String::from(
+            env.get_string(&value_str).map_err(|e| format!("Failed to get value string: {:?}", e))?,
+        ));
+
+        let witness_key = key.parse().map(Witness).map_err(|e| format!("Failed to parse witness key '{}': {:?}", logging::secret(&key), e))?;
+        let field_element = FieldElement::try_from_str(&value)
+            .ok_or_else(|| format!("Failed to parse value of witness {}: not a valid field element", logging::secret(&key)))?;
+        witness_map.insert(witness_key, field_element);
+    }
+    Ok(witness_map)
+}
+
+// Reads a Java String[] into a Vec<String>
+fn read_string_array(env: &mut JNIEnv, array: &JObjectArray) -> Result<Vec<String>, String> {
+    let length = env.get_array_length(array).map_err(|e| format!("Failed to get array length: {:?}", e))?;
+    let mut strings = Vec::with_capacity(length as usize);
+    for i in 0..length {
+        let element = env
+            .get_object_array_element(array, i)
+            .map_err(|e| format!("Failed to get array element at index {}: {:?}", i, e))?;
+        let element = JString::from(element);
+        let value: String = env
+            .get_string(&element)
+            .map_err(|e| format!("Failed to get string at index {}: {:?}", i, e))?
+            .into();
+        env.delete_local_ref(element).map_err(|e| format!("Failed to delete local reference: {:?}", e))?;
+        strings.push(value);
+    }
+    Ok(strings)
+}
+
+// Resolves foreign calls by calling `resolve(name, inputsJson)` on a Java ForeignCallBridge,
+// and forwards the output of print to a Java PrintListener
+struct JavaForeignCallResolver<'a, 'local> {
+    env: &'a mut JNIEnv<'local>,
+    handler: Option<&'a JObject<'local>>,
+    print_listener: Option<&'a JObject<'local>>,
+}
+
+impl<'a, 'local> JavaForeignCallResolver<'a, 'local> {
+    // Either object may be null
+    fn new(env: &'a mut JNIEnv<'local>, handler: &'a JObject<'local>, print_listener: &'a JObject<'local>) -> Self {
+        JavaForeignCallResolver {
+            env,
+            handler: Some(handler).filter(|handler| !handler.is_null()),
+            print_listener: Some(print_listener).filter(|listener| !listener.is_null()),
+        }
+    }
+
+    // Clears the exception thrown by the handler and returns its description
+    fn take_exception(&mut self) -> String {
+        let throwable = match self.env.exception_occurred() {
+            Ok(throwable) => throwable,
+            Err(e) => return format!("{:?}", e),
+        };
+        let _ = self.env.exception_clear();
+        let description = self
+            .env
+            .call_method(&throwable, "toString", "()Ljava/lang/String;", &[])
+            .and_then(|value| value.l())
+            .and_then(|value| self.env.get_string(&JString::from(value)).map(String::from));
+        let _ = self.env.exception_clear();
+        description.unwrap_or_else(|e| format!("{:?}", e))
+    }
+}
+
+// The handler runs on the calling thread and isn't interrupted by the execution
+// timeout, which the executor checks before and after each call
+impl ForeignCallResolver for JavaForeignCallResolver<'_, '_> {
+    fn resolve(
+        &mut self,
+        call: &ForeignCallWaitInfo<FieldElement>,
+    ) -> Result<ForeignCallResult<FieldElement>, String> {
+        let handler = match self.handler {
+            Some(handler) => handler,
+            None => return foreign_calls::NoForeignCalls.resolve(call),
+        };
+        let inputs = foreign_calls::encode_inputs(call)?;
+        let name_jstr = self.env.new_string(&call.function).map_err(|e| format!("Failed to create Java string: {:?}", e))?;
+        let inputs_jstr = self.env.new_string(inputs).map_err(|e| format!("Failed to create Java string: {:?}", e))?;
+
+        let outputs = self.env.call_method(
+            handler,
+            "resolve",
+            "(Ljava/lang/String;Ljava/lang/String;)Ljava/lang/String;",
+            &[JValue::Object(&name_jstr), JValue::Object(&inputs_jstr)],
+        );
+        let outputs = match outputs.and_then(|value| value.l()) {
+            Ok(outputs) => outputs,
+            Err(_) if self.env.exception_check().unwrap_or(false) => return Err(self.take_exception()),
+            Err(e) => return Err(format!("Failed to call foreign call handler: {:?}", e)),
+        };
+        let outputs_jstr = JString::from(outputs);
+        let outputs: String = self
+            .env
+            .get_string(&outputs_jstr)
+            .map_err(|e| format!("Failed to get foreign call outputs: {:?}", e))?
+            .into();
+
+        // Programs can make many foreign calls, so the local references can't wait for the JNI call to return
+        for local in [name_jstr, inputs_jstr, outputs_jstr] {
+            if let Err(e) = self.env.delete_local_ref(local) {
+                error!("Failed to delete local reference: {:?}", e);
+            }
+        }
+        foreign_calls::decode_outputs(&outputs)
+    }
+
+    fn print(&mut self, message: &str) {
+        let listener = match self.print_listener {
+            Some(listener) => listener,
+            None => return,
+        };
+        let message_jstr = match self.env.new_string(message) {
+            Ok(s) => s,
+            Err(e) => {
+                error!("Failed to create Java string for print output: {:?}", e);
+                return;
+            }
+        };
+        // An exception thrown by the listener is logged, the execution goes on
+        if self.env.call_method(listener, "onPrint", "(Ljava/lang/String;)V", &[JValue::Object(&message_jstr)]).is_err() {
+            let e = self.take_exception();
+            error!("Print listener failed: {}", e);
+        }
+        if let Err(e) = self.env.delete_local_ref(message_jstr) {
+            error!("Failed to delete local reference: {:?}", e);
+        }
+    }
+}
+
+// Executes the program, resolving its foreign calls with the Java handler and
+// forwarding its prints to the Java listener, if any
+fn execute_with_handler<'local>(
+    env: &mut JNIEnv<'local>,
+    circuit_bytecode: &str,
+    witness_map: secret::SecretWitnessMap,
+    foreign_call_handler: &JObject<'local>,
+    print_listener: &JObject<'local>,
+    limits: executor::ExecutionLimits,
+) -> Result<secret::SecretWitnessStack, executor::ExecutionError> {
+    let mut resolver = JavaForeignCallResolver::new(env, foreign_call_handler, print_listener);
+    executor::execute_program(circuit_bytecode, witness_map.into_inner(), &mut resolver, limits)
+}
+
+// Limits of an execution from the JNI arguments, where 0 means unlimited
+fn execution_limits(max_brillig_steps: jlong, timeout_millis: jlong) -> executor::ExecutionLimits {
+    executor::ExecutionLimits {
+        max_brillig_steps: Some(max_brillig_steps as u64).filter(|&steps| steps > 0),
+        timeout: Some(timeout_millis as u64).filter(|&millis| millis > 0).map(std::time::Duration::from_millis),
+    }
+}
+
+// Storage cap from the JNI argument. In low memory mode the scratch directory
+// must have room for it, so that proving doesn't fail once the disk is full
+fn checked_storage_cap(max_storage_usage: jlong, low_memory_mode: bool) -> Result<u64, String> {
+    let storage_cap = u64::try_from(max_storage_usage).map_err(|_| format!("Invalid storage cap: {}", max_storage_usage))?;
+    if low_memory_mode && storage_cap > 0 {
+        scratch::check_free_space(storage_cap)?;
     }
+    Ok(storage_cap)
+}
+
+fn read_memory_estimate(env: &mut JNIEnv, circuit_size: jint, proof_type_jstr: &JString) -> Result<memory::MemoryEstimate, String> {
+    let circuit_size = u32::try_from(circuit_size).map_err(|_| format!("Invalid circuit size: {}", circuit_size))?;
+    let proof_type: String = env
+        .get_string(proof_type_jstr)
+        .map_err(|e| format!("Failed to get proof type string: {:?}", e))?
+        .into();
+    memory::estimate(circuit_size, &proof_type)
+}
+
+// Initialize the logger if not already initialized, writing to logcat on
+// Android and to stderr elsewhere, as well as to the Java log sink if set
+fn init_logger() {
+    static LOGGER_INITIALIZED: std::sync::Once = std::sync::Once::new();
+
+    LOGGER_INITIALIZED.call_once(|| {
+        // Everything goes through, the levels set with set_log_level and
+        // set_log_sink filter
+        #[cfg(target_os = "android")]
+        let platform = android_logger::AndroidLogger::new(
+            android_logger::Config::default()
+                .with_max_level(log::LevelFilter::Trace)
+                .with_tag("NoirAndroid"),
+        );
+        // RUST_LOG can still narrow it down by module
+        #[cfg(not(target_os = "android"))]
+        let platform = env_logger::Builder::new().filter_level(log::LevelFilter::Trace).parse_default_env().build();
+
+        logging::install(Box::new(platform));
+        debug!("Logger initialized for NoirAndroid");
+    });
 }
 
 #[no_mangle]
@@ -96,7 +299,25 @@ pub extern "system" fn Java_com_noirandroid_lib_Noir_00024Companion_setup_1srs<'
         },
     };
 
-    let num_points = match setup_srs(circuit_size.try_into().unwrap(), srs_path.as_deref()) {
+    let circuit_size = match u32::try_from(circuit_size) {
+        Ok(size) if size > 0 => size,
+        _ => {
+            error!("Invalid circuit size: {}", circuit_size);
+            throw_exception(&mut env, "java/lang/IllegalArgumentException", &format!("Invalid circuit size: {}", circuit_size));
+            return -1;
+        }
+    };
+
+    // noir_rs loads one point more than the circuit size
+    if let Some(path) = srs_path.as_deref() {
+        if let Err(e) = srs::verify_local_srs(path, circuit_size + 1, None) {
+            error!("SRS verification failed: {}", e);
+            throw_exception(&mut env, "com/noirandroid/lib/SrsVerificationException", &format!("SRS verification failed: {}", e));
+            return -1;
+        }
+    }
+
+    let num_points = match setup_srs(circuit_size, srs_path.as_deref()) {
         Ok(num) => {
             info!("SRS setup successful with {} points", num);
             num
@@ -173,7 +394,21 @@ pub extern "system" fn Java_com_noirandroid_lib_Noir_00024Companion_setup_1srs_1
         },
     };
 
-    let num_points = match setup_srs_from_bytecode(&circuit_bytecode, srs_path.as_deref(), false) {
+    // Sized as setup_srs_from_bytecode does, so that a local SRS is verified
+    // before barretenberg loads it
+    let circuit_size = get_circuit_size(&circuit_bytecode, false);
+    debug!("Circuit size: {}", circuit_size);
+
+    // noir_rs loads one point more than the circuit size
+    if let Some(path) = srs_path.as_deref() {
+        if let Err(e) = srs::verify_local_srs(path, circuit_size + 1, None) {
+            error!("SRS verification failed: {}", e);
+            throw_exception(&mut env, "com/noirandroid/lib/SrsVerificationException", &format!("SRS verification failed: {}", e));
+            return -1;
+        }
+    }
+
+    let num_points = match setup_srs(circuit_size, srs_path.as_deref()) {
         Ok(num) => {
             info!("SRS setup from bytecode successful with {} points", num);
             num
@@ -196,237 +431,218 @@ pub extern "system" fn Java_com_noirandroid_lib_Noir_00024Companion_setup_1srs_1
 }
 
 #[no_mangle]
-pub extern "system" fn Java_com_noirandroid_lib_Noir_00024Companion_execute<'local>(
+pub extern "system" fn Java_com_noirandroid_lib_Noir_00024Companion_setup_1srs_1from_1source<'local>(
     mut env: JNIEnv<'local>,
     _class: JClass<'local>,
-    circuit_bytecode_jstr: JString<'local>,
-    witness_jobject: JObject<'local>,
-) -> jobject {
+    circuit_size: jint,
+    base_url_jstr: JString<'local>,
+    cache_dir_jstr: JString<'local>,
+    listener: JObject<'local>,
+) -> jint {
     init_logger();
-    info!("Executing circuit");
-    
-    let witness_map = match env.get_map(&witness_jobject) {
-        Ok(map) => {
-            debug!("Successfully retrieved witness map from Java");
-            map
+    info!("Setting up SRS from configured source with circuit size: {}", circuit_size);
+
+    let base_url = match base_url_jstr.is_null() {
+        true => {
+            debug!("SRS base URL is null, using {}", srs::DEFAULT_SRS_URL);
+            None
         },
-        Err(e) => {
-            error!("Failed to get witness map: {:?}", e);
-            throw_exception(&mut env, "java/lang/RuntimeException", &format!("Failed to get witness map: {:?}", e));
-            return std::ptr::null_mut();
+        false => {
+            let url = match env.get_string(&base_url_jstr) {
+                Ok(s) => s,
+                Err(e) => {
+                    error!("Failed to get base URL string: {:?}", e);
+                    throw_exception(&mut env, "java/lang/RuntimeException", &format!("Failed to get base URL string: {:?}", e));
+                    return -1;
+                }
+            };
+            match url.to_str() {
+                Ok(s) => Some(s.to_owned()),
+                Err(e) => {
+                    error!("Failed to convert base URL to Rust string: {:?}", e);
+                    throw_exception(&mut env, "java/lang/RuntimeException", &format!("Failed to convert base URL to Rust string: {:?}", e));
+                    return -1;
+                }
+            }
         },
     };
-    let mut witness_iter = match witness_map.iter(&mut env) {
-        Ok(iter) => iter,
-        Err(e) => {
-            error!("Failed to create iterator for witness map: {:?}", e);
-            throw_exception(&mut env, "java/lang/RuntimeException", &format!("Failed to create iterator: {:?}", e));
-            return std::ptr::null_mut();
-        }
-    };
 
-    let circuit_bytecode = match env.get_string(&circuit_bytecode_jstr) {
+    let cache_dir = match env.get_string(&cache_dir_jstr) {
         Ok(s) => s,
         Err(e) => {
-            error!("Failed to get bytecode string: {:?}", e);
-            throw_exception(&mut env, "java/lang/RuntimeException", &format!("Failed to get bytecode string: {:?}", e));
-            return std::ptr::null_mut();
+            error!("Failed to get cache directory string: {:?}", e);
+            throw_exception(&mut env, "java/lang/RuntimeException", &format!("Failed to get cache directory string: {:?}", e));
+            return -1;
         }
     };
-    let circuit_bytecode = match circuit_bytecode.to_str() {
+    let cache_dir = match cache_dir.to_str() {
         Ok(s) => s.to_owned(),
         Err(e) => {
-            error!("Failed to convert bytecode to Rust string: {:?}", e);
-            throw_exception(&mut env, "java/lang/RuntimeException", &format!("Failed to convert bytecode to Rust string: {:?}", e));
-            return std::ptr::null_mut();
+            error!("Failed to convert cache directory to Rust string: {:?}", e);
+            throw_exception(&mut env, "java/lang/RuntimeException", &format!("Failed to convert cache directory to Rust string: {:?}", e));
+            return -1;
         }
     };
-    debug!("Circuit bytecode length: {}", circuit_bytecode.len());
-
-    let mut witness_map = WitnessMap::new();
-    let mut witness_count = 0;
+    debug!("Using SRS cache directory: {}", cache_dir);
 
-    while let Ok(Some((key, value))) = witness_iter.next(&mut env) {
-        witness_count += 1;
-        let key_str = key.into();
-        let value_str = value.into();
-
-        let key_jstr = match env.get_string(&key_str) {
-            Ok(s) => s,
-            Err(e) => {
-                error!("Failed to get key string: {:?}", e);
-                throw_exception(&mut env, "java/lang/RuntimeException", &format!("Failed to get key string: {:?}", e));
-                return std::ptr::null_mut();
-            }
-        };
-        let value_jstr = match env.get_string(&value_str) {
-            Ok(s) => s,
-            Err(e) => {
-                error!("Failed to get value string: {:?}", e);
-                throw_exception(&mut env, "java/lang/RuntimeException", &format!("Failed to get value string: {:?}", e));
-                return std::ptr::null_mut();
-            }
-        };
+    let circuit_size = match u32::try_from(circuit_size) {
+        Ok(size) if size > 0 => size,
+        _ => {
+            error!("Invalid circuit size: {}", circuit_size);
+            throw_exception(&mut env, "java/lang/IllegalArgumentException", &format!("Invalid circuit size: {}", circuit_size));
+            return -1;
+        }
+    };
 
-        let key = match key_jstr.to_str() {
-            Ok(s) => s,
-            Err(e) => {
-                error!("Failed to convert key to Rust string: {:?}", e);
-                throw_exception(&mut env, "java/lang/RuntimeException", &format!("Failed to convert key to Rust string: {:?}", e));
-                return std::ptr::null_mut();
-            }
-        };
-        let value = match value_jstr.to_str() {
-            Ok(s) => s,
-            Err(e) => {
-                error!("Failed to convert value to Rust string: {:?}", e);
-                throw_exception(&mut env, "java/lang/RuntimeException", &format!("Failed to convert value to Rust string: {:?}", e));
-                return std::ptr::null_mut();
-            }
-        };
-        
-        let witness_key = match key.parse() {
-            Ok(k) => Witness(k),
-            Err(e) => {
-                error!("Failed to parse witness key '{}': {:?}", key, e);
-                throw_exception(&mut env, "java/lang/RuntimeException", &format!("Failed to parse witness key '{}': {:?}", key, e));
-                return std::ptr::null_mut();
-            }
-        };
-        
-        let field_element = match FieldElement::try_from_str(value) {
-            Some(fe) => fe,
-            None => {
-                error!("Failed to parse witness value '{}': not a valid field element", value);
-                throw_exception(&mut env, "java/lang/RuntimeException", &format!("Failed to parse witness value '{}': not a valid field element", value));
-                return std::ptr::null_mut();
+    // One extra point, as when downloading through noir_rs
+    let source = srs::SrsSource::new(base_url.as_deref(), &cache_dir);
+    let mut listener_exception = None;
+    let fetched = source.fetch(circuit_size + 1, &mut |downloaded, total| {
+        if listener.is_null() {
+            return Ok(());
+        }
+        if let Err(e) = env.call_method(&listener, "onProgress", "(JJ)V", &[JValue::Long(downloaded as jlong), JValue::Long(total as jlong)]) {
+            error!("Failed to report SRS download progress: {:?}", e);
+        }
+        // The exception has to be cleared before any other JNI call, and is
+        // thrown again once the download is stopped
+        if env.exception_check().unwrap_or(false) {
+            listener_exception = env.exception_occurred().ok();
+            let _ = env.exception_clear();
+            return Err("SRS download listener threw an exception".to_owned());
+        }
+        Ok(())
+    });
+    let srs_data = match fetched {
+        Ok(srs_data) => srs_data,
+        Err(e) => {
+            error!("Failed to download SRS: {}", e);
+            match listener_exception {
+                Some(exception) => {
+                    let _ = env.throw(exception);
+                },
+                None => throw_exception(&mut env, "java/lang/RuntimeException", &format!("Failed to download SRS: {}", e)),
             }
-        };
+            return -1;
+        }
+    };
 
-        witness_map.insert(witness_key, field_element);
+    if let Err(e) = srs::verify_srs(&srs_data, None, None) {
+        error!("SRS verification failed: {}", e);
+        throw_exception(&mut env, "com/noirandroid/lib/SrsVerificationException", &format!("SRS verification failed: {}", e));
+        return -1;
     }
-    
-    info!("Loaded {} witness values", witness_count);
 
-    let solved_witness = match execute(&circuit_bytecode, witness_map) {
-        Ok(witness) => {
-            info!("Circuit execution successful");
-            witness
+    let num_points = match srs::load_srs(srs_data, circuit_size, std::path::Path::new(&cache_dir)) {
+        Ok(num) => {
+            info!("SRS setup successful with {} points", num);
+            num
         },
         Err(e) => {
-            error!("Circuit execution failed: {:?}", e);
-            throw_exception(&mut env, "java/lang/RuntimeException", &format!("Circuit execution failed: {:?}", e));
-            return std::ptr::null_mut();
+            error!("Failed to setup SRS: {}", e);
+            throw_exception(&mut env, "java/lang/RuntimeException", &format!("Failed to setup SRS: {}", e));
+            return -1;
         }
     };
-    
-    let witness_map = match solved_witness.peek().into_iter().last() {
-        Some(w) => {
-            debug!("Successfully retrieved witness from execution result");
-            &w.witness
-        },
-        None => {
-            error!("No witness found in execution result");
-            throw_exception(&mut env, "java/lang/RuntimeException", "No witness found in execution result");
+
+    match jint::try_from(num_points) {
+        Ok(result) => result,
+        Err(e) => {
+            error!("Failed to convert num_points to jint: {:?}", e);
+            throw_exception(&mut env, "java/lang/RuntimeException", &format!("Failed to convert num_points to jint: {:?}", e));
+            -1
+        }
+    }
+}
+
+#[no_mangle]
+pub extern "system" fn Java_com_noirandroid_lib_Noir_00024Companion_verify_1srs<'local>(
+    mut env: JNIEnv<'local>,
+    _class: JClass<'local>,
+    srs_path_jstr: JString<'local>,
+    num_points: jint,
+    expected_hash_jstr: JString<'local>,
+) -> jobject {
+    init_logger();
+    info!("Verifying SRS with {} points", num_points);
+
+    let srs_path = match env.get_string(&srs_path_jstr) {
+        Ok(s) => s,
+        Err(e) => {
+            error!("Failed to get srs path string: {:?}", e);
+            throw_exception(&mut env, "java/lang/RuntimeException", &format!("Failed to get srs path string: {:?}", e));
             return std::ptr::null_mut();
         }
     };
-    
-    let witness_vec: Vec<String> = witness_map.clone().into_iter().map(|(_, val)| format!("0x{}", val.to_hex())).collect();
-    debug!("Generated {} witness values", witness_vec.len());
-
-    // Create a Java String array - breaking down the operations to avoid multiple mutable borrows
-    let string_class = match env.find_class("java/lang/String") {
-        Ok(class) => class,
+    let srs_path = match srs_path.to_str() {
+        Ok(s) => s.to_owned(),
         Err(e) => {
-            error!("Failed to find String class: {:?}", e);
-            throw_exception(&mut env, "java/lang/RuntimeException", &format!("Failed to find String class: {:?}", e));
+            error!("Failed to convert srs path to Rust string: {:?}", e);
+            throw_exception(&mut env, "java/lang/RuntimeException", &format!("Failed to convert srs path to Rust string: {:?}", e));
             return std::ptr::null_mut();
         }
     };
-    
-    let empty_string = match env.new_string("") {
-        Ok(s) => s,
+
+    let expected_hash = match expected_hash_jstr.is_null() {
+        true => None,
+        false => {
+            let hash = match env.get_string(&expected_hash_jstr) {
+                Ok(s) => s,
+                Err(e) => {
+                    error!("Failed to get expected hash string: {:?}", e);
+                    throw_exception(&mut env, "java/lang/RuntimeException", &format!("Failed to get expected hash string: {:?}", e));
+                    return std::ptr::null_mut();
+                }
+            };
+            match hash.to_str() {
+                Ok(s) => Some(s.to_owned()),
+                Err(e) => {
+                    error!("Failed to convert expected hash to Rust string: {:?}", e);
+                    throw_exception(&mut env, "java/lang/RuntimeException", &format!("Failed to convert expected hash to Rust string: {:?}", e));
+                    return std::ptr::null_mut();
+                }
+            }
+        },
+    };
+
+    let num_points = match u32::try_from(num_points) {
+        Ok(n) => n,
         Err(e) => {
-            error!("Failed to create empty string: {:?}", e);
-            throw_exception(&mut env, "java/lang/RuntimeException", &format!("Failed to create empty string: {:?}", e));
+            error!("Invalid number of points {}: {:?}", num_points, e);
+            throw_exception(&mut env, "java/lang/IllegalArgumentException", &format!("Invalid number of points {}: {:?}", num_points, e));
             return std::ptr::null_mut();
         }
     };
-    
-    let string_array = match env.new_object_array(
-        witness_vec.len() as i32,
-        string_class,
-        &empty_string,
-    ) {
-        Ok(arr) => arr,
+
+    let hash = match srs::verify_local_srs(&srs_path, num_points, expected_hash.as_deref()) {
+        Ok(hash) => hash,
         Err(e) => {
-            error!("Failed to create string array: {:?}", e);
-            throw_exception(&mut env, "java/lang/RuntimeException", &format!("Failed to create string array: {:?}", e));
+            error!("SRS verification failed: {}", e);
+            throw_exception(&mut env, "com/noirandroid/lib/SrsVerificationException", &format!("SRS verification failed: {}", e));
             return std::ptr::null_mut();
         }
     };
 
-    // Fill the array with witness values
-    for (i, value) in witness_vec.iter().enumerate() {
-        let jstring = match env.new_string(value) {
-            Ok(s) => s,
-            Err(e) => {
-                error!("Failed to create Java string for witness value {}: {:?}", i, e);
-                throw_exception(&mut env, "java/lang/RuntimeException", &format!("Failed to create Java string for witness value {}: {:?}", i, e));
-                return std::ptr::null_mut();
-            }
-        };
-        
-        if let Err(e) = env.set_object_array_element(&string_array, i as i32, &jstring) {
-            error!("Failed to set array element at index {}: {:?}", i, e);
-            throw_exception(&mut env, "java/lang/RuntimeException", &format!("Failed to set array element at index {}: {:?}", i, e));
+    let hash_jstr = match env.new_string(hash) {
+        Ok(s) => s,
+        Err(e) => {
+            error!("Failed to create Java string for SRS hash: {:?}", e);
+            throw_exception(&mut env, "java/lang/RuntimeException", &format!("Failed to create Java string for SRS hash: {:?}", e));
             return std::ptr::null_mut();
         }
-    }
+    };
 
-    info!("Successfully prepared witness array with {} elements", witness_vec.len());
-    string_array.as_raw()
+    hash_jstr.into_raw()
 }
 
 #[no_mangle]
-pub extern "system" fn Java_com_noirandroid_lib_Noir_00024Companion_prove<'local>(
+pub extern "system" fn Java_com_noirandroid_lib_Noir_00024Companion_circuit_1info<'local>(
     mut env: JNIEnv<'local>,
     _class: JClass<'local>,
     circuit_bytecode_jstr: JString<'local>,
-    witness_jobject: JObject<'local>,
-    vk_jstr: JString<'local>,
-    proof_type_jstr: JString<'local>,
-    low_memory_mode: jboolean,
-    max_storage_usage: jlong
 ) -> jobject {
     init_logger();
-    info!("Starting proof generation");
-    
-    let use_low_memory = low_memory_mode != 0;
-    debug!("Low memory mode: {}", use_low_memory);
-    let storage_cap = max_storage_usage as u64;
-    debug!("Max storage usage: {}", storage_cap);
-    // Use more descriptive variable names and handle errors gracefully
-    let witness_map = match env.get_map(&witness_jobject) {
-        Ok(map) => {
-            debug!("Successfully retrieved witness map from Java");
-            map
-        },
-        Err(e) => {
-            error!("Failed to get witness map: {:?}", e);
-            throw_exception(&mut env, "java/lang/RuntimeException", &format!("Failed to get witness map: {:?}", e));
-            return std::ptr::null_mut();
-        },
-    };
-    let mut witness_iter = match witness_map.iter(&mut env) {
-        Ok(iter) => iter,
-        Err(e) => {
-            error!("Failed to create iterator for witness map: {:?}", e);
-            throw_exception(&mut env, "java/lang/RuntimeException", &format!("Failed to create iterator: {:?}", e));
-            return std::ptr::null_mut();
-        }
-    };
+    info!("Getting circuit info");
 
     let circuit_bytecode = match env.get_string(&circuit_bytecode_jstr) {
         Ok(s) => s,
@@ -446,319 +662,1763 @@ pub extern "system" fn Java_com_noirandroid_lib_Noir_00024Companion_prove<'local
     };
     debug!("Circuit bytecode length: {}", circuit_bytecode.len());
 
-    let proof_type = match env.get_string(&proof_type_jstr) {
-        Ok(s) => s,
+    let info = match circuit::circuit_info(&circuit_bytecode) {
+        Ok(info) => {
+            info!("Circuit has {} gates, circuit size {}", info.gate_count, info.circuit_size);
+            info
+        },
         Err(e) => {
-            error!("Failed to get proof type string: {:?}", e);
-            throw_exception(&mut env, "java/lang/RuntimeException", &format!("Failed to get proof type string: {:?}", e));
+            error!("Failed to get circuit info: {}", e);
+            throw_exception(&mut env, "java/lang/RuntimeException", &format!("Failed to get circuit info: {}", e));
             return std::ptr::null_mut();
         }
     };
-    let proof_type = match proof_type.to_str() {
-        Ok(s) => s.to_owned(),
+
+    let info_json = match serde_json::to_string(&info) {
+        Ok(json) => json,
         Err(e) => {
-            error!("Failed to convert proof type to Rust string: {:?}", e);
-            throw_exception(&mut env, "java/lang/RuntimeException", &format!("Failed to convert proof type to Rust string: {:?}", e));
+            error!("Failed to serialize circuit info: {:?}", e);
+            throw_exception(&mut env, "java/lang/RuntimeException", &format!("Failed to serialize circuit info: {:?}", e));
             return std::ptr::null_mut();
         }
     };
-    info!("Using proof type: {}", proof_type);
 
-    let vk_str = match env.get_string(&vk_jstr) {
+    let info_jstr = match env.new_string(info_json) {
         Ok(s) => s,
         Err(e) => {
-            error!("Failed to get verification key string: {:?}", e);
-            throw_exception(&mut env, "java/lang/RuntimeException", &format!("Failed to get verification key string: {:?}", e));
+            error!("Failed to create Java string for circuit info: {:?}", e);
+            throw_exception(&mut env, "java/lang/RuntimeException", &format!("Failed to create Java string for circuit info: {:?}", e));
             return std::ptr::null_mut();
         }
     };
-    let vk_str = match vk_str.to_str() {
+
+    info_jstr.into_raw()
+}
+
+#[no_mangle]
+pub extern "system" fn Java_com_noirandroid_lib_Noir_00024Companion_parse_1manifest<'local>(
+    mut env: JNIEnv<'local>,
+    _class: JClass<'local>,
+    manifest_jstr: JString<'local>,
+) -> jobject {
+    init_logger();
+    info!("Parsing circuit manifest");
+
+    let manifest = match env.get_string(&manifest_jstr) {
+        Ok(s) => s,
+        Err(e) => {
+            error!("Failed to get manifest string: {:?}", e);
+            throw_exception(&mut env, "java/lang/RuntimeException", &format!("Failed to get manifest string: {:?}", e));
+            return std::ptr::null_mut();
+        }
+    };
+    let manifest = match manifest.to_str() {
         Ok(s) => s.to_owned(),
         Err(e) => {
-            error!("Failed to convert verification key to Rust string: {:?}", e);
-            throw_exception(&mut env, "java/lang/RuntimeException", &format!("Failed to convert verification key to Rust string: {:?}", e));
+            error!("Failed to convert manifest to Rust string: {:?}", e);
+            throw_exception(&mut env, "java/lang/RuntimeException", &format!("Failed to convert manifest to Rust string: {:?}", e));
             return std::ptr::null_mut();
         }
     };
-    debug!("Verification key length: {}", vk_str.len());
 
-    let verification_key = match hex::decode(vk_str) {
-        Ok(vk) => {
-            debug!("Successfully decoded verification key, size: {} bytes", vk.len());
-            vk
+    let manifest = match manifest::Manifest::parse(&manifest) {
+        Ok(manifest) => {
+            info!("Circuit {} has {} parameters", manifest.hash, manifest.abi.parameters.len());
+            manifest
         },
         Err(e) => {
-            error!("Failed to decode verification key: {:?}", e);
-            throw_exception(&mut env, "java/lang/RuntimeException", &format!("Failed to decode verification key: {:?}", e));
+            error!("{}", e);
+            throw_exception(&mut env, "java/lang/IllegalArgumentException", &e);
             return std::ptr::null_mut();
         }
     };
 
-    let mut witness_map = WitnessMap::new();
-    let mut witness_count = 0;
-
-    while let Ok(Some((key, value))) = witness_iter.next(&mut env) {
-        witness_count += 1;
-        let key_str = key.into();
-        let value_str = value.into();
-
-        let key_jstr = match env.get_string(&key_str) {
-            Ok(s) => s,
-            Err(e) => {
-                error!("Failed to get key string: {:?}", e);
-                throw_exception(&mut env, "java/lang/RuntimeException", &format!("Failed to get key string: {:?}", e));
-                return std::ptr::null_mut();
-            }
-        };
-        let value_jstr = match env.get_string(&value_str) {
-            Ok(s) => s,
-            Err(e) => {
-                error!("Failed to get value string: {:?}", e);
-                throw_exception(&mut env, "java/lang/RuntimeException", &format!("Failed to get value string: {:?}", e));
-                return std::ptr::null_mut();
-            }
-        };
-
-        let key = match key_jstr.to_str() {
-            Ok(s) => s,
-            Err(e) => {
-                error!("Failed to convert key to Rust string: {:?}", e);
-                throw_exception(&mut env, "java/lang/RuntimeException", &format!("Failed to convert key to Rust string: {:?}", e));
-                return std::ptr::null_mut();
-            }
-        };
-        let value = match value_jstr.to_str() {
-            Ok(s) => s,
-            Err(e) => {
-                error!("Failed to convert value to Rust string: {:?}", e);
-                throw_exception(&mut env, "java/lang/RuntimeException", &format!("Failed to convert value to Rust string: {:?}", e));
-                return std::ptr::null_mut();
-            }
-        };
-        
-        let witness_key = match key.parse() {
-            Ok(k) => Witness(k),
-            Err(e) => {
-                error!("Failed to parse witness key '{}': {:?}", key, e);
-                throw_exception(&mut env, "java/lang/RuntimeException", &format!("Failed to parse witness key '{}': {:?}", key, e));
-                return std::ptr::null_mut();
-            }
-        };
-        
-        let field_element = match FieldElement::try_from_str(value) {
-            Some(fe) => fe,
-            None => {
-                error!("Failed to parse witness value '{}': not a valid field element", value);
-                throw_exception(&mut env, "java/lang/RuntimeException", &format!("Failed to parse witness value '{}': not a valid field element", value));
-                return std::ptr::null_mut();
-            }
-        };
-
-        witness_map.insert(witness_key, field_element);
-    }
-    
-    info!("Loaded {} witness values", witness_count);
-
-    let proof = if proof_type == "ultra_honk" { 
-        info!("Generating UltraHonk proof");
-        match prove_ultra_honk(&circuit_bytecode, witness_map, verification_key, use_low_memory, Some(storage_cap)) {
-            Ok(p) => {
-                info!("Proof generation successful, proof size: {} bytes", p.len());
-                p
-            },
-            Err(e) => {
-                error!("Proof generation failed: {:?}", e);
-                throw_exception(&mut env, "java/lang/RuntimeException", &format!("Proof generation failed: {:?}", e));
-                return std::ptr::null_mut();
-            }
-        }
-    } else if proof_type == "ultra_honk_keccak" {
-        info!("Generating UltraHonkKeccak proof");
-        match prove_ultra_honk_keccak(&circuit_bytecode, witness_map, verification_key, false, use_low_memory, Some(storage_cap)) {
-            Ok(p) => {
-                info!("Proof generation successful, proof size: {} bytes", p.len());
-                p
-            },
-            Err(e) => {
-                error!("Proof generation failed: {:?}", e);
-                throw_exception(&mut env, "java/lang/RuntimeException", &format!("Proof generation failed: {:?}", e));
-                return std::ptr::null_mut();
-            }
+    let view = match manifest.view() {
+        Ok(view) => view,
+        Err(e) => {
+            error!("{}", e);
+            throw_exception(&mut env, "java/lang/IllegalArgumentException", &e);
+            return std::ptr::null_mut();
         }
-    } else { 
-        error!("Unsupported proof type: {}", proof_type);
-        throw_exception(&mut env, "java/lang/IllegalArgumentException", &format!("Unsupported proof type: {}", proof_type));
-        return std::ptr::null_mut();
     };
 
-    let proof_str = hex::encode(&proof);
-    debug!("Encoded proof length: {}", proof_str.len());
-
-    // Create and return a Java string containing the proof
-    let proof_jstr = match env.new_string(proof_str) {
-        Ok(s) => s,
+    let manifest_json = match serde_json::to_string(&view) {
+        Ok(json) => json,
         Err(e) => {
-            error!("Failed to create Java string for proof: {:?}", e);
-            throw_exception(&mut env, "java/lang/RuntimeException", &format!("Failed to create Java string for proof: {:?}", e));
+            error!("Failed to serialize manifest: {:?}", e);
+            throw_exception(&mut env, "java/lang/RuntimeException", &format!("Failed to serialize manifest: {:?}", e));
             return std::ptr::null_mut();
         }
     };
 
-    info!("Successfully prepared proof for return to Java");
-    proof_jstr.into_raw()
+    match env.new_string(manifest_json) {
+        Ok(s) => s.into_raw(),
+        Err(e) => {
+            error!("Failed to create Java string for manifest: {:?}", e);
+            throw_exception(&mut env, "java/lang/RuntimeException", &format!("Failed to create Java string for manifest: {:?}", e));
+            std::ptr::null_mut()
+        }
+    }
 }
 
 #[no_mangle]
-pub extern "system" fn Java_com_noirandroid_lib_Noir_00024Companion_verify<'local>(
+pub extern "system" fn Java_com_noirandroid_lib_Noir_00024Companion_check_1compatibility<'local>(
     mut env: JNIEnv<'local>,
     _class: JClass<'local>,
-    proof_jstr: JString<'local>,
-    vk_jstr: JString<'local>,
-    proof_type_jstr: JString<'local>
-) -> jboolean {
+    manifest_jstr: JString<'local>,
+) -> jobject {
     init_logger();
-    info!("Starting proof verification");
-    
-    let proof_str = match env.get_string(&proof_jstr) {
-        Ok(s) => s,
-        Err(e) => {
-            error!("Failed to get proof string: {:?}", e);
-            throw_exception(&mut env, "java/lang/RuntimeException", &format!("Failed to get proof string: {:?}", e));
-            return 0;
-        }
-    };
-    let proof_str = match proof_str.to_str() {
-        Ok(s) => s.to_owned(),
-        Err(e) => {
-            error!("Failed to convert proof to Rust string: {:?}", e);
-            throw_exception(&mut env, "java/lang/RuntimeException", &format!("Failed to convert proof to Rust string: {:?}", e));
-            return 0;
-        }
-    };
-    debug!("Proof string length: {}", proof_str.len());
+    info!("Checking circuit compatibility");
 
-    let vk_str = match env.get_string(&vk_jstr) {
+    let manifest = match env.get_string(&manifest_jstr) {
         Ok(s) => s,
         Err(e) => {
-            error!("Failed to get verification key string: {:?}", e);
-            throw_exception(&mut env, "java/lang/RuntimeException", &format!("Failed to get verification key string: {:?}", e));
-            return 0;
+            error!("Failed to get manifest string: {:?}", e);
+            throw_exception(&mut env, "java/lang/RuntimeException", &format!("Failed to get manifest string: {:?}", e));
+            return std::ptr::null_mut();
         }
     };
-    let vk_str = match vk_str.to_str() {
+    let manifest = match manifest.to_str() {
         Ok(s) => s.to_owned(),
         Err(e) => {
-            error!("Failed to convert verification key to Rust string: {:?}", e);
-            throw_exception(&mut env, "java/lang/RuntimeException", &format!("Failed to convert verification key to Rust string: {:?}", e));
-            return 0;
+            error!("Failed to convert manifest to Rust string: {:?}", e);
+            throw_exception(&mut env, "java/lang/RuntimeException", &format!("Failed to convert manifest to Rust string: {:?}", e));
+            return std::ptr::null_mut();
         }
     };
-    debug!("Verification key length: {}", vk_str.len());
 
-    let proof = match hex::decode(proof_str) {
-        Ok(p) => {
-            debug!("Successfully decoded proof, size: {} bytes", p.len());
-            p
+    let compatibility = match compat::check_compatibility(&manifest) {
+        Ok(compatibility) => {
+            match compatibility.status {
+                compat::CompatibilityStatus::Compatible => info!("{}", compatibility.reason),
+                _ => warn!("{}", compatibility.reason),
+            }
+            compatibility
         },
         Err(e) => {
-            error!("Failed to decode proof: {:?}", e);
-            throw_exception(&mut env, "java/lang/RuntimeException", &format!("Failed to decode proof: {:?}", e));
-            return 0;
+            error!("{}", e);
+            throw_exception(&mut env, "java/lang/IllegalArgumentException", &e);
+            return std::ptr::null_mut();
         }
     };
-    
-    let verification_key = match hex::decode(vk_str) {
-        Ok(vk) => {
-            debug!("Successfully decoded verification key, size: {} bytes", vk.len());
-            vk
-        },
+
+    let compatibility_json = match serde_json::to_string(&compatibility) {
+        Ok(json) => json,
         Err(e) => {
-            error!("Failed to decode verification key: {:?}", e);
-            throw_exception(&mut env, "java/lang/RuntimeException", &format!("Failed to decode verification key: {:?}", e));
-            return 0;
+            error!("Failed to serialize compatibility: {:?}", e);
+            throw_exception(&mut env, "java/lang/RuntimeException", &format!("Failed to serialize compatibility: {:?}", e));
+            return std::ptr::null_mut();
         }
     };
 
-    let proof_type = match env.get_string(&proof_type_jstr) {
-        Ok(s) => s,
+    match env.new_string(compatibility_json) {
+        Ok(s) => s.into_raw(),
         Err(e) => {
-            error!("Failed to get proof type string: {:?}", e);
-            throw_exception(&mut env, "java/lang/RuntimeException", &format!("Failed to get proof type string: {:?}", e));
-            return 0;
+            error!("Failed to create Java string for compatibility: {:?}", e);
+            throw_exception(&mut env, "java/lang/RuntimeException", &format!("Failed to create Java string for compatibility: {:?}", e));
+            std::ptr::null_mut()
         }
-    };
-    let proof_type = match proof_type.to_str() {
-        Ok(s) => s.to_owned(),
+    }
+}
+
+#[no_mangle]
+pub extern "system" fn Java_com_noirandroid_lib_Noir_00024Companion_version_1info<'local>(
+    mut env: JNIEnv<'local>,
+    _class: JClass<'local>,
+) -> jobject {
+    init_logger();
+
+    let versions = compat::version_info();
+    debug!("Versions: {:?}", versions);
+
+    let versions_json = match serde_json::to_string(&versions) {
+        Ok(json) => json,
         Err(e) => {
-            error!("Failed to convert proof type to Rust string: {:?}", e);
-            throw_exception(&mut env, "java/lang/RuntimeException", &format!("Failed to convert proof type to Rust string: {:?}", e));
-            return 0;
+            error!("Failed to serialize version info: {:?}", e);
+            throw_exception(&mut env, "java/lang/RuntimeException", &format!("Failed to serialize version info: {:?}", e));
+            return std::ptr::null_mut();
         }
     };
-    info!("Using proof type: {}", proof_type);
 
-    let verdict = if proof_type == "ultra_honk" {
-        match verify_ultra_honk(proof, verification_key) {
-            Ok(v) => {
-                info!("Verification complete, result: {}", v);
-                v
-            },  
-            Err(e) => {
-                error!("Verification failed with error: {:?}", e);
-                throw_exception(&mut env, "java/lang/RuntimeException", &format!("Verification failed: {:?}", e));
-                return 0;
-            }
-        }
-    } else if proof_type == "ultra_honk_keccak" {
-        match verify_ultra_honk_keccak(proof, verification_key, false) {
-            Ok(v) => {
-                info!("Verification complete, result: {}", v);
-                v
-            },
-            Err(e) => {
-                error!("Verification failed with error: {:?}", e);
-                throw_exception(&mut env, "java/lang/RuntimeException", &format!("Verification failed: {:?}", e));
-                return 0;
-            }
+    match env.new_string(versions_json) {
+        Ok(s) => s.into_raw(),
+        Err(e) => {
+            error!("Failed to create Java string for version info: {:?}", e);
+            throw_exception(&mut env, "java/lang/RuntimeException", &format!("Failed to create Java string for version info: {:?}", e));
+            std::ptr::null_mut()
         }
-    } else {
-        error!("Unsupported proof type: {}", proof_type);
-        throw_exception(&mut env, "java/lang/IllegalArgumentException", "Ultra honk and Ultra honk keccak are the only proof types supported for now");
-        return 0;
-    };
-
-    jboolean::from(verdict)
+    }
 }
 
 #[no_mangle]
-pub extern "system" fn Java_com_noirandroid_lib_Noir_00024Companion_get_1verification_1key<'local>(
+pub extern "system" fn Java_com_noirandroid_lib_Noir_00024Companion_estimate_1memory<'local>(
     mut env: JNIEnv<'local>,
     _class: JClass<'local>,
-    circuit_bytecode_jstr: JString<'local>,
+    circuit_size: jint,
     proof_type_jstr: JString<'local>,
-    low_memory_mode: jboolean,
-    max_storage_usage: jlong
 ) -> jobject {
     init_logger();
-    info!("Getting verification key");
-    
-    let use_low_memory = low_memory_mode != 0;
-    debug!("Low memory mode: {}", use_low_memory);
-    let storage_cap = max_storage_usage as u64;
-    debug!("Max storage usage: {}", storage_cap);
-    
-    let circuit_bytecode = match env.get_string(&circuit_bytecode_jstr) {
-        Ok(s) => s,
+    info!("Estimating proving memory for circuit size: {}", circuit_size);
+
+    let estimate = match read_memory_estimate(&mut env, circuit_size, &proof_type_jstr) {
+        Ok(estimate) => estimate,
         Err(e) => {
-            error!("Failed to get bytecode string: {:?}", e);
-            throw_exception(&mut env, "java/lang/RuntimeException", &format!("Failed to get bytecode string: {:?}", e));
+            error!("{}", e);
+            throw_exception(&mut env, "java/lang/IllegalArgumentException", &e);
             return std::ptr::null_mut();
         }
     };
-    let circuit_bytecode = match circuit_bytecode.to_str() {
-        Ok(s) => s.to_owned(),
+    info!("Estimate: {}", estimate);
+
+    let estimate_json = match serde_json::to_string(&estimate) {
+        Ok(json) => json,
         Err(e) => {
-            error!("Failed to convert bytecode to Rust string: {:?}", e);
+            error!("Failed to serialize memory estimate: {:?}", e);
+            throw_exception(&mut env, "java/lang/RuntimeException", &format!("Failed to serialize memory estimate: {:?}", e));
+            return std::ptr::null_mut();
+        }
+    };
+
+    match env.new_string(estimate_json) {
+        Ok(s) => s.into_raw(),
+        Err(e) => {
+            error!("Failed to create Java string for memory estimate: {:?}", e);
+            throw_exception(&mut env, "java/lang/RuntimeException", &format!("Failed to create Java string for memory estimate: {:?}", e));
+            std::ptr::null_mut()
+        }
+    }
+}
+
+#[no_mangle]
+pub extern "system" fn Java_com_noirandroid_lib_Noir_00024Companion_set_1memory_1model<'local>(
+    mut env: JNIEnv<'local>,
+    _class: JClass<'local>,
+    proof_type_jstr: JString<'local>,
+    bytes_per_gate: jlong,
+    resident_bytes_per_gate: jlong,
+) {
+    init_logger();
+
+    let proof_type: String = match env.get_string(&proof_type_jstr) {
+        Ok(s) => s.into(),
+        Err(e) => {
+            error!("Failed to get proof type string: {:?}", e);
+            throw_exception(&mut env, "java/lang/RuntimeException", &format!("Failed to get proof type string: {:?}", e));
+            return;
+        }
+    };
+    let model = match (u64::try_from(bytes_per_gate), u64::try_from(resident_bytes_per_gate)) {
+        (Ok(bytes_per_gate), Ok(resident_bytes_per_gate)) => memory::MemoryModel { bytes_per_gate, resident_bytes_per_gate },
+        _ => {
+            let e = format!("Invalid memory model: {} bytes per gate, {} of them resident in low memory mode", bytes_per_gate, resident_bytes_per_gate);
+            error!("{}", e);
+            throw_exception(&mut env, "java/lang/IllegalArgumentException", &e);
+            return;
+        }
+    };
+    match memory::set_memory_model(&proof_type, model) {
+        Ok(()) => info!("Memory model of {}: {} bytes per gate, {} resident in low memory mode", proof_type, bytes_per_gate, resident_bytes_per_gate),
+        Err(e) => {
+            error!("{}", e);
+            throw_exception(&mut env, "java/lang/IllegalArgumentException", &e);
+        }
+    }
+}
+
+#[no_mangle]
+pub extern "system" fn Java_com_noirandroid_lib_Noir_00024Companion_select_1memory_1mode<'local>(
+    mut env: JNIEnv<'local>,
+    _class: JClass<'local>,
+    circuit_size: jint,
+    proof_type_jstr: JString<'local>,
+    memory_budget: jlong,
+    max_storage: jlong,
+) -> jobject {
+    init_logger();
+    info!("Selecting memory mode for a budget of {} bytes", memory_budget);
+
+    let estimate = match read_memory_estimate(&mut env, circuit_size, &proof_type_jstr) {
+        Ok(estimate) => estimate,
+        Err(e) => {
+            error!("{}", e);
+            throw_exception(&mut env, "java/lang/IllegalArgumentException", &e);
+            return std::ptr::null_mut();
+        }
+    };
+    if memory_budget <= 0 || max_storage < 0 {
+        error!("Invalid memory budget {} or max storage {}", memory_budget, max_storage);
+        throw_exception(&mut env, "java/lang/IllegalArgumentException", &format!("Invalid memory budget {} or max storage {}", memory_budget, max_storage));
+        return std::ptr::null_mut();
+    }
+
+    // Without a limit, low memory mode can use the free space of the scratch directory
+    let max_storage = match max_storage {
+        0 => scratch::free_space(&scratch::scratch_dir()).unwrap_or(0),
+        max_storage => max_storage as u64,
+    };
+    let mode = match memory::select_mode(estimate, memory_budget as u64, max_storage) {
+        Ok(mode) => {
+            info!("Low memory mode: {}, storage cap: {}", mode.low_memory_mode, mode.storage_cap);
+            mode
+        },
+        Err(e) => {
+            error!("{}", e);
+            throw_exception(&mut env, "com/noirandroid/lib/MemoryBudgetException", &e);
+            return std::ptr::null_mut();
+        }
+    };
+
+    let mode_json = match serde_json::to_string(&mode) {
+        Ok(json) => json,
+        Err(e) => {
+            error!("Failed to serialize memory mode: {:?}", e);
+            throw_exception(&mut env, "java/lang/RuntimeException", &format!("Failed to serialize memory mode: {:?}", e));
+            return std::ptr::null_mut();
+        }
+    };
+
+    match env.new_string(mode_json) {
+        Ok(s) => s.into_raw(),
+        Err(e) => {
+            error!("Failed to create Java string for memory mode: {:?}", e);
+            throw_exception(&mut env, "java/lang/RuntimeException", &format!("Failed to create Java string for memory mode: {:?}", e));
+            std::ptr::null_mut()
+        }
+    }
+}
+
+#[no_mangle]
+pub extern "system" fn Java_com_noirandroid_lib_Noir_00024Companion_execute<'local>(
+    mut env: JNIEnv<'local>,
+    _class: JClass<'local>,
+    circuit_bytecode_jstr: JString<'local>,
+    witness_jobject: JObject<'local>,
+    foreign_call_handler: JObject<'local>,
+    print_listener: JObject<'local>,
+    max_brillig_steps: jlong,
+    timeout_millis: jlong,
+) -> jobject {
+    init_logger();
+    let mut metrics = metrics::MetricsRecorder::start("execute");
+    info!("Executing circuit");
+    
+    let circuit_bytecode = match env.get_string(&circuit_bytecode_jstr) {
+        Ok(s) => s,
+        Err(e) => {
+            error!("Failed to get bytecode string: {:?}", e);
+            throw_exception(&mut env, "java/lang/RuntimeException", &format!("Failed to get bytecode string: {:?}", e));
+            return std::ptr::null_mut();
+        }
+    };
+    let circuit_bytecode = match circuit_bytecode.to_str() {
+        Ok(s) => s.to_owned(),
+        Err(e) => {
+            error!("Failed to convert bytecode to Rust string: {:?}", e);
+            throw_exception(&mut env, "java/lang/RuntimeException", &format!("Failed to convert bytecode to Rust string: {:?}", e));
+            return std::ptr::null_mut();
+        }
+    };
+    debug!("Circuit bytecode length: {}", circuit_bytecode.len());
+
+    let witness_map = match read_witness_map(&mut env, &witness_jobject) {
+        Ok(map) => map,
+        Err(e) => {
+            error!("{}", e);
+            throw_exception(&mut env, "java/lang/RuntimeException", &e);
+            return std::ptr::null_mut();
+        }
+    };
+    info!("Loaded {} witness values", (&*witness_map).into_iter().count());
+
+    metrics.phase("decode");
+    let limits = execution_limits(max_brillig_steps, timeout_millis);
+    let solved_witness = match execute_with_handler(&mut env, &circuit_bytecode, witness_map, &foreign_call_handler, &print_listener, limits) {
+        Ok(witness) => {
+            info!("Circuit execution successful");
+            witness
+        },
+        Err(e) => {
+            error!("Circuit execution failed: {}", e);
+            throw_exception(&mut env, e.exception_class(), &format!("Circuit execution failed: {}", e));
+            return std::ptr::null_mut();
+        }
+    };
+    metrics.phase("execute");
+    
+    let witness_map = match solved_witness.peek().into_iter().last() {
+        Some(w) => {
+            debug!("Successfully retrieved witness from execution result");
+            &w.witness
+        },
+        None => {
+            error!("No witness found in execution result");
+            throw_exception(&mut env, "java/lang/RuntimeException", "No witness found in execution result");
+            return std::ptr::null_mut();
+        }
+    };
+    
+    let witness_vec = secret::Zeroizing::new(witness_map.into_iter().map(|(_, val)| format!("0x{}", val.to_hex())).collect::<Vec<String>>());
+    debug!("Generated {} witness values", witness_vec.len());
+
+    // Create a Java String array - breaking down the operations to avoid multiple mutable borrows
+    let string_class = match env.find_class("java/lang/String") {
+        Ok(class) => class,
+        Err(e) => {
+            error!("Failed to find String class: {:?}", e);
+            throw_exception(&mut env, "java/lang/RuntimeException", &format!("Failed to find String class: {:?}", e));
+            return std::ptr::null_mut();
+        }
+    };
+    
+    let empty_string = match env.new_string("") {
+        Ok(s) => s,
+        Err(e) => {
+            error!("Failed to create empty string: {:?}", e);
+            throw_exception(&mut env, "java/lang/RuntimeException", &format!("Failed to create empty string: {:?}", e));
+            return std::ptr::null_mut();
+        }
+    };
+    
+    let string_array = match env.new_object_array(
+        witness_vec.len() as i32,
+        string_class,
+        &empty_string,
+    ) {
+        Ok(arr) => arr,
+        Err(e) => {
+            error!("Failed to create string array: {:?}", e);
+            throw_exception(&mut env, "java/lang/RuntimeException", &format!("Failed to create string array: {:?}", e));
+            return std::ptr::null_mut();
+        }
+    };
+
+    // Fill the array with witness values
+    for (i, value) in witness_vec.iter().enumerate() {
+        let jstring = match env.new_string(value) {
+            Ok(s) => s,
+            Err(e) => {
+                error!("Failed to create Java string for witness value {}: {:?}", i, e);
+                throw_exception(&mut env, "java/lang/RuntimeException", &format!("Failed to create Java string for witness value {}: {:?}", i, e));
+                return std::ptr::null_mut();
+            }
+        };
+        
+        if let Err(e) = env.set_object_array_element(&string_array, i as i32, &jstring) {
+            error!("Failed to set array element at index {}: {:?}", i, e);
+            throw_exception(&mut env, "java/lang/RuntimeException", &format!("Failed to set array element at index {}: {:?}", i, e));
+            return std::ptr::null_mut();
+        }
+    }
+
+    info!("Successfully prepared witness array with {} elements", witness_vec.len());
+    metrics.phase("encode");
+    metrics.finish();
+
+    string_array.as_raw()
+}
+
+#[no_mangle]
+pub extern "system" fn Java_com_noirandroid_lib_Noir_00024Companion_execute_1indexed<'local>(
+    mut env: JNIEnv<'local>,
+    _class: JClass<'local>,
+    circuit_bytecode_jstr: JString<'local>,
+    witness_jobject: JObject<'local>,
+    requested_indices_jarray: JLongArray<'local>,
+    return_values_only: jboolean,
+    foreign_call_handler: JObject<'local>,
+    print_listener: JObject<'local>,
+    max_brillig_steps: jlong,
+    timeout_millis: jlong,
+) -> jobject {
+    init_logger();
+    let mut metrics = metrics::MetricsRecorder::start("execute_indexed");
+    info!("Executing circuit with indexed output");
+
+    let circuit_bytecode = match env.get_string(&circuit_bytecode_jstr) {
+        Ok(s) => s,
+        Err(e) => {
+            error!("Failed to get bytecode string: {:?}", e);
+            throw_exception(&mut env, "java/lang/RuntimeException", &format!("Failed to get bytecode string: {:?}", e));
+            return std::ptr::null_mut();
+        }
+    };
+    let circuit_bytecode = match circuit_bytecode.to_str() {
+        Ok(s) => s.to_owned(),
+        Err(e) => {
+            error!("Failed to convert bytecode to Rust string: {:?}", e);
+            throw_exception(&mut env, "java/lang/RuntimeException", &format!("Failed to convert bytecode to Rust string: {:?}", e));
+            return std::ptr::null_mut();
+        }
+    };
+    debug!("Circuit bytecode length: {}", circuit_bytecode.len());
+
+    // Either the return values, the requested indices or the whole witness
+    let requested = if return_values_only != 0 {
+        match circuit::return_witnesses(&circuit_bytecode) {
+            Ok(indices) => {
+                debug!("Returning {} return witnesses", indices.len());
+                Some(indices)
+            },
+            Err(e) => {
+                error!("Failed to get return witnesses: {}", e);
+                throw_exception(&mut env, "java/lang/RuntimeException", &format!("Failed to get return witnesses: {}", e));
+                return std::ptr::null_mut();
+            }
+        }
+    } else if !requested_indices_jarray.is_null() {
+        let length = match env.get_array_length(&requested_indices_jarray) {
+            Ok(length) => length,
+            Err(e) => {
+                error!("Failed to get requested indices length: {:?}", e);
+                throw_exception(&mut env, "java/lang/RuntimeException", &format!("Failed to get requested indices length: {:?}", e));
+                return std::ptr::null_mut();
+            }
+        };
+        let mut indices = vec![0 as jlong; length as usize];
+        if let Err(e) = env.get_long_array_region(&requested_indices_jarray, 0, &mut indices) {
+            error!("Failed to get requested indices: {:?}", e);
+            throw_exception(&mut env, "java/lang/RuntimeException", &format!("Failed to get requested indices: {:?}", e));
+            return std::ptr::null_mut();
+        }
+        match indices.iter().map(|&i| u32::try_from(i)).collect::<Result<Vec<_>, _>>() {
+            Ok(indices) => Some(indices),
+            Err(e) => {
+                error!("Invalid witness index: {:?}", e);
+                throw_exception(&mut env, "java/lang/IllegalArgumentException", &format!("Invalid witness index: {:?}", e));
+                return std::ptr::null_mut();
+            }
+        }
+    } else {
+        None
+    };
+
+    let witness_map = match read_witness_map(&mut env, &witness_jobject) {
+        Ok(map) => map,
+        Err(e) => {
+            error!("{}", e);
+            throw_exception(&mut env, "java/lang/RuntimeException", &e);
+            return std::ptr::null_mut();
+        }
+    };
+
+    metrics.phase("decode");
+    let limits = execution_limits(max_brillig_steps, timeout_millis);
+    let solved_witness = match execute_with_handler(&mut env, &circuit_bytecode, witness_map, &foreign_call_handler, &print_listener, limits) {
+        Ok(witness) => {
+            info!("Circuit execution successful");
+            witness
+        },
+        Err(e) => {
+            error!("Circuit execution failed: {}", e);
+            throw_exception(&mut env, e.exception_class(), &format!("Circuit execution failed: {}", e));
+            return std::ptr::null_mut();
+        }
+    };
+    metrics.phase("execute");
+
+    let selected = witness::main_witness(&solved_witness)
+        .and_then(|witness_map| witness::select_witnesses(&witness_map, requested.as_deref()));
+    let (indices, values) = match selected {
+        Ok(selected) => selected,
+        Err(e) => {
+            error!("{}", e);
+            throw_exception(&mut env, "java/lang/RuntimeException", &e);
+            return std::ptr::null_mut();
+        }
+    };
+    debug!("Selected {} witness values", indices.len());
+
+    let indices_array = match env.new_long_array(indices.len() as i32) {
+        Ok(array) => array,
+        Err(e) => {
+            error!("Failed to create long array: {:?}", e);
+            throw_exception(&mut env, "java/lang/RuntimeException", &format!("Failed to create long array: {:?}", e));
+            return std::ptr::null_mut();
+        }
+    };
+    let indices: Vec<jlong> = indices.into_iter().map(jlong::from).collect();
+    if let Err(e) = env.set_long_array_region(&indices_array, 0, &indices) {
+        error!("Failed to fill long array: {:?}", e);
+        throw_exception(&mut env, "java/lang/RuntimeException", &format!("Failed to fill long array: {:?}", e));
+        return std::ptr::null_mut();
+    }
+
+    let values_array = match env.new_object_array(values.len() as i32, "java/lang/String", JObject::null()) {
+        Ok(array) => array,
+        Err(e) => {
+            error!("Failed to create string array: {:?}", e);
+            throw_exception(&mut env, "java/lang/RuntimeException", &format!("Failed to create string array: {:?}", e));
+            return std::ptr::null_mut();
+        }
+    };
+    for (i, value) in values.iter().enumerate() {
+        let jstring = match env.new_string(value) {
+            Ok(s) => s,
+            Err(e) => {
+                error!("Failed to create Java string for witness value {}: {:?}", i, e);
+                throw_exception(&mut env, "java/lang/RuntimeException", &format!("Failed to create Java string for witness value {}: {:?}", i, e));
+                return std::ptr::null_mut();
+            }
+        };
+        if let Err(e) = env.set_object_array_element(&values_array, i as i32, &jstring) {
+            error!("Failed to set array element at index {}: {:?}", i, e);
+            throw_exception(&mut env, "java/lang/RuntimeException", &format!("Failed to set array element at index {}: {:?}", i, e));
+            return std::ptr::null_mut();
+        }
+        if let Err(e) = env.delete_local_ref(jstring) {
+            error!("Failed to delete local reference: {:?}", e);
+        }
+    }
+
+    metrics.phase("encode");
+    metrics.finish();
+
+    match env.new_object(
+        "com/noirandroid/lib/ExecutionWitness",
+        "([J[Ljava/lang/String;)V",
+        &[JValue::Object(&indices_array), JValue::Object(&values_array)],
+    ) {
+        Ok(result) => {
+            info!("Successfully prepared {} indexed witness values", values.len());
+            result.into_raw()
+        },
+        Err(e) => {
+            error!("Failed to create ExecutionWitness: {:?}", e);
+            throw_exception(&mut env, "java/lang/RuntimeException", &format!("Failed to create ExecutionWitness: {:?}", e));
+            std::ptr::null_mut()
+        }
+    }
+}
+
+#[no_mangle]
+pub extern "system" fn Java_com_noirandroid_lib_Noir_00024Companion_execute_1to_1witness_1stack<'local>(
+    mut env: JNIEnv<'local>,
+    _class: JClass<'local>,
+    circuit_bytecode_jstr: JString<'local>,
+    witness_jobject: JObject<'local>,
+    foreign_call_handler: JObject<'local>,
+    print_listener: JObject<'local>,
+    max_brillig_steps: jlong,
+    timeout_millis: jlong,
+) -> jbyteArray {
+    init_logger();
+    let mut metrics = metrics::MetricsRecorder::start("execute_to_witness_stack");
+    info!("Executing circuit to witness stack");
+
+    let circuit_bytecode = match env.get_string(&circuit_bytecode_jstr) {
+        Ok(s) => s,
+        Err(e) => {
+            error!("Failed to get bytecode string: {:?}", e);
+            throw_exception(&mut env, "java/lang/RuntimeException", &format!("Failed to get bytecode string: {:?}", e));
+            return std::ptr::null_mut();
+        }
+    };
+    let circuit_bytecode = match circuit_bytecode.to_str() {
+        Ok(s) => s.to_owned(),
+        Err(e) => {
+            error!("Failed to convert bytecode to Rust string: {:?}", e);
+            throw_exception(&mut env, "java/lang/RuntimeException", &format!("Failed to convert bytecode to Rust string: {:?}", e));
+            return std::ptr::null_mut();
+        }
+    };
+    debug!("Circuit bytecode length: {}", circuit_bytecode.len());
+
+    let witness_map = match read_witness_map(&mut env, &witness_jobject) {
+        Ok(map) => map,
+        Err(e) => {
+            error!("{}", e);
+            throw_exception(&mut env, "java/lang/RuntimeException", &e);
+            return std::ptr::null_mut();
+        }
+    };
+
+    metrics.phase("decode");
+    let limits = execution_limits(max_brillig_steps, timeout_millis);
+    let solved_witness = match execute_with_handler(&mut env, &circuit_bytecode, witness_map, &foreign_call_handler, &print_listener, limits) {
+        Ok(witness) => {
+            info!("Circuit execution successful");
+            witness
+        },
+        Err(e) => {
+            error!("Circuit execution failed: {}", e);
+            throw_exception(&mut env, e.exception_class(), &format!("Circuit execution failed: {}", e));
+            return std::ptr::null_mut();
+        }
+    };
+    metrics.phase("execute");
+
+    let serialized = match witness::serialize_witness_stack(&solved_witness) {
+        Ok(bytes) => {
+            debug!("Serialized witness stack size: {} bytes", bytes.len());
+            bytes
+        },
+        Err(e) => {
+            error!("{}", e);
+            throw_exception(&mut env, "java/lang/RuntimeException", &e);
+            return std::ptr::null_mut();
+        }
+    };
+
+    metrics.phase("encode");
+    metrics.finish();
+
+    match env.byte_array_from_slice(&serialized) {
+        Ok(array) => array.into_raw(),
+        Err(e) => {
+            error!("Failed to create Java byte array for witness stack: {:?}", e);
+            throw_exception(&mut env, "java/lang/RuntimeException", &format!("Failed to create Java byte array for witness stack: {:?}", e));
+            std::ptr::null_mut()
+        }
+    }
+}
+
+#[no_mangle]
+pub extern "system" fn Java_com_noirandroid_lib_Noir_00024Companion_execute_1witness_1stack<'local>(
+    mut env: JNIEnv<'local>,
+    _class: JClass<'local>,
+    circuit_bytecode_jstr: JString<'local>,
+    witness_jobject: JObject<'local>,
+    foreign_call_handler: JObject<'local>,
+    print_listener: JObject<'local>,
+    max_brillig_steps: jlong,
+    timeout_millis: jlong,
+) -> jobject {
+    init_logger();
+    let mut metrics = metrics::MetricsRecorder::start("execute_witness_stack");
+    info!("Executing circuit to witnesses of every function");
+
+    let circuit_bytecode = match env.get_string(&circuit_bytecode_jstr) {
+        Ok(s) => s,
+        Err(e) => {
+            error!("Failed to get bytecode string: {:?}", e);
+            throw_exception(&mut env, "java/lang/RuntimeException", &format!("Failed to get bytecode string: {:?}", e));
+            return std::ptr::null_mut();
+        }
+    };
+    let circuit_bytecode = match circuit_bytecode.to_str() {
+        Ok(s) => s.to_owned(),
+        Err(e) => {
+            error!("Failed to convert bytecode to Rust string: {:?}", e);
+            throw_exception(&mut env, "java/lang/RuntimeException", &format!("Failed to convert bytecode to Rust string: {:?}", e));
+            return std::ptr::null_mut();
+        }
+    };
+    debug!("Circuit bytecode length: {}", circuit_bytecode.len());
+
+    let witness_map = match read_witness_map(&mut env, &witness_jobject) {
+        Ok(map) => map,
+        Err(e) => {
+            error!("{}", e);
+            throw_exception(&mut env, "java/lang/RuntimeException", &e);
+            return std::ptr::null_mut();
+        }
+    };
+
+    metrics.phase("decode");
+    let limits = execution_limits(max_brillig_steps, timeout_millis);
+    let solved_witness = match execute_with_handler(&mut env, &circuit_bytecode, witness_map, &foreign_call_handler, &print_listener, limits) {
+        Ok(witness) => {
+            info!("Circuit execution successful");
+            witness
+        },
+        Err(e) => {
+            error!("Circuit execution failed: {}", e);
+            throw_exception(&mut env, e.exception_class(), &format!("Circuit execution failed: {}", e));
+            return std::ptr::null_mut();
+        }
+    };
+    metrics.phase("execute");
+
+    let witnesses = match circuit::decode_program(&circuit_bytecode).and_then(|program| executor::function_witnesses(&program, &solved_witness)) {
+        Ok(witnesses) => {
+            debug!("Witness stack holds {} function calls", witnesses.len());
+            witnesses
+        },
+        Err(e) => {
+            error!("{}", e);
+            throw_exception(&mut env, "java/lang/RuntimeException", &e);
+            return std::ptr::null_mut();
+        }
+    };
+
+    let witnesses_json = match serde_json::to_string(&witnesses) {
+        Ok(json) => secret::Zeroizing::new(json),
+        Err(e) => {
+            error!("Failed to serialize witness stack: {:?}", e);
+            throw_exception(&mut env, "java/lang/RuntimeException", &format!("Failed to serialize witness stack: {:?}", e));
+            return std::ptr::null_mut();
+        }
+    };
+
+    metrics.phase("encode");
+    metrics.finish();
+
+    match env.new_string(witnesses_json.as_str()) {
+        Ok(s) => s.into_raw(),
+        Err(e) => {
+            error!("Failed to create Java string for witness stack: {:?}", e);
+            throw_exception(&mut env, "java/lang/RuntimeException", &format!("Failed to create Java string for witness stack: {:?}", e));
+            std::ptr::null_mut()
+        }
+    }
+}
+
+#[no_mangle]
+pub extern "system" fn Java_com_noirandroid_lib_Noir_00024Companion_profile_1execution<'local>(
+    mut env: JNIEnv<'local>,
+    _class: JClass<'local>,
+    circuit_bytecode_jstr: JString<'local>,
+    witness_jobject: JObject<'local>,
+    debug_artifact_jstr: JString<'local>,
+    format_jstr: JString<'local>,
+    foreign_call_handler: JObject<'local>,
+    print_listener: JObject<'local>,
+    max_brillig_steps: jlong,
+    timeout_millis: jlong,
+) -> jobject {
+    init_logger();
+    info!("Profiling circuit execution");
+
+    let circuit_bytecode = match env.get_string(&circuit_bytecode_jstr) {
+        Ok(s) => s,
+        Err(e) => {
+            error!("Failed to get bytecode string: {:?}", e);
+            throw_exception(&mut env, "java/lang/RuntimeException", &format!("Failed to get bytecode string: {:?}", e));
+            return std::ptr::null_mut();
+        }
+    };
+    let circuit_bytecode = match circuit_bytecode.to_str() {
+        Ok(s) => s.to_owned(),
+        Err(e) => {
+            error!("Failed to convert bytecode to Rust string: {:?}", e);
+            throw_exception(&mut env, "java/lang/RuntimeException", &format!("Failed to convert bytecode to Rust string: {:?}", e));
+            return std::ptr::null_mut();
+        }
+    };
+    debug!("Circuit bytecode length: {}", circuit_bytecode.len());
+
+    let format: String = match env.get_string(&format_jstr) {
+        Ok(s) => s.into(),
+        Err(e) => {
+            error!("Failed to get profile format string: {:?}", e);
+            throw_exception(&mut env, "java/lang/RuntimeException", &format!("Failed to get profile format string: {:?}", e));
+            return std::ptr::null_mut();
+        }
+    };
+    if format != "json" && format != "folded" {
+        error!("Unsupported profile format: {}", format);
+        throw_exception(&mut env, "java/lang/IllegalArgumentException", &format!("Unsupported profile format: {}, expected json or folded", format));
+        return std::ptr::null_mut();
+    }
+
+    // Without debug symbols the profile stops at ACIR and Brillig functions
+    let source_map = match debug_artifact_jstr.is_null() {
+        true => {
+            debug!("No debug symbols given");
+            None
+        },
+        false => {
+            let debug_artifact: String = match env.get_string(&debug_artifact_jstr) {
+                Ok(s) => s.into(),
+                Err(e) => {
+                    error!("Failed to get debug symbols string: {:?}", e);
+                    throw_exception(&mut env, "java/lang/RuntimeException", &format!("Failed to get debug symbols string: {:?}", e));
+                    return std::ptr::null_mut();
+                }
+            };
+            // Unreadable debug symbols only cost the Noir functions of the profile
+            match source_map::SourceMap::from_manifest(&debug_artifact) {
+                Ok(source_map) => Some(source_map),
+                Err(e) => {
+                    warn!("{}, profiling without source functions", e);
+                    None
+                }
+            }
+        },
+    };
+
+    let witness_map = match read_witness_map(&mut env, &witness_jobject) {
+        Ok(map) => map,
+        Err(e) => {
+            error!("{}", e);
+            throw_exception(&mut env, "java/lang/RuntimeException", &e);
+            return std::ptr::null_mut();
+        }
+    };
+
+    let limits = execution_limits(max_brillig_steps, timeout_millis);
+    let mut resolver = JavaForeignCallResolver::new(&mut env, &foreign_call_handler, &print_listener);
+    let result = executor::profile_program(&circuit_bytecode, witness_map.into_inner(), &mut resolver, limits, source_map.as_ref());
+    let profile = match result {
+        Ok((_, profile)) => {
+            info!("Circuit executed in {} ms", profile.total_nanos / 1_000_000);
+            profile
+        },
+        Err(e) => {
+            error!("Circuit execution failed: {}", e);
+            throw_exception(&mut env, e.exception_class(), &format!("Circuit execution failed: {}", e));
+            return std::ptr::null_mut();
+        }
+    };
+
+    let output = match format.as_str() {
+        "folded" => profile.to_folded(),
+        _ => match profile.to_json() {
+            Ok(json) => json,
+            Err(e) => {
+                error!("{}", e);
+                throw_exception(&mut env, "java/lang/RuntimeException", &e);
+                return std::ptr::null_mut();
+            }
+        },
+    };
+
+    match env.new_string(output) {
+        Ok(s) => s.into_raw(),
+        Err(e) => {
+            error!("Failed to create Java string for profile: {:?}", e);
+            throw_exception(&mut env, "java/lang/RuntimeException", &format!("Failed to create Java string for profile: {:?}", e));
+            std::ptr::null_mut()
+        }
+    }
+}
+
+#[no_mangle]
+pub extern "system" fn Java_com_noirandroid_lib_Noir_00024Companion_prove_1from_1witness<'local>(
+    mut env: JNIEnv<'local>,
+    _class: JClass<'local>,
+    circuit_bytecode_jstr: JString<'local>,
+    witness_stack_jarray: JByteArray<'local>,
+    vk_jstr: JString<'local>,
+    proof_type_jstr: JString<'local>,
+    low_memory_mode: jboolean,
+    max_storage_usage: jlong
+) -> jobject {
+    init_logger();
+    let mut metrics = metrics::MetricsRecorder::start("prove_from_witness");
+    let _srs_in_use = release::SrsInUse::acquire();
+    info!("Starting proof generation from solved witness");
+
+    let use_low_memory = low_memory_mode != 0;
+    debug!("Low memory mode: {}", use_low_memory);
+    let storage_cap = match checked_storage_cap(max_storage_usage, use_low_memory) {
+        Ok(cap) => cap,
+        Err(e) => {
+            error!("{}", e);
+            throw_exception(&mut env, "java/lang/IllegalArgumentException", &e);
+            return std::ptr::null_mut();
+        }
+    };
+    debug!("Max storage usage: {}", storage_cap);
+
+    let circuit_bytecode = match env.get_string(&circuit_bytecode_jstr) {
+        Ok(s) => s,
+        Err(e) => {
+            error!("Failed to get bytecode string: {:?}", e);
+            throw_exception(&mut env, "java/lang/RuntimeException", &format!("Failed to get bytecode string: {:?}", e));
+            return std::ptr::null_mut();
+        }
+    };
+    let circuit_bytecode = match circuit_bytecode.to_str() {
+        Ok(s) => s.to_owned(),
+        Err(e) => {
+            error!("Failed to convert bytecode to Rust string: {:?}", e);
+            throw_exception(&mut env, "java/lang/RuntimeException", &format!("Failed to convert bytecode to Rust string: {:?}", e));
+            return std::ptr::null_mut();
+        }
+    };
+    debug!("Circuit bytecode length: {}", circuit_bytecode.len());
+
+    let proof_type = match env.get_string(&proof_type_jstr) {
+        Ok(s) => s,
+        Err(e) => {
+            error!("Failed to get proof type string: {:?}", e);
+            throw_exception(&mut env, "java/lang/RuntimeException", &format!("Failed to get proof type string: {:?}", e));
+            return std::ptr::null_mut();
+        }
+    };
+    let proof_type = match proof_type.to_str() {
+        Ok(s) => s.to_owned(),
+        Err(e) => {
+            error!("Failed to convert proof type to Rust string: {:?}", e);
+            throw_exception(&mut env, "java/lang/RuntimeException", &format!("Failed to convert proof type to Rust string: {:?}", e));
+            return std::ptr::null_mut();
+        }
+    };
+    info!("Using proof type: {}", proof_type);
+
+    let vk_str = match env.get_string(&vk_jstr) {
+        Ok(s) => s,
+        Err(e) => {
+            error!("Failed to get verification key string: {:?}", e);
+            throw_exception(&mut env, "java/lang/RuntimeException", &format!("Failed to get verification key string: {:?}", e));
+            return std::ptr::null_mut();
+        }
+    };
+    let vk_str = match vk_str.to_str() {
+        Ok(s) => s.to_owned(),
+        Err(e) => {
+            error!("Failed to convert verification key to Rust string: {:?}", e);
+            throw_exception(&mut env, "java/lang/RuntimeException", &format!("Failed to convert verification key to Rust string: {:?}", e));
+            return std::ptr::null_mut();
+        }
+    };
+    let verification_key = match hex::decode(vk_str) {
+        Ok(vk) => {
+            debug!("Successfully decoded verification key, size: {} bytes", vk.len());
+            vk
+        },
+        Err(e) => {
+            error!("Failed to decode verification key: {:?}", e);
+            throw_exception(&mut env, "java/lang/RuntimeException", &format!("Failed to decode verification key: {:?}", e));
+            return std::ptr::null_mut();
+        }
+    };
+
+    let witness_stack_bytes = match env.convert_byte_array(&witness_stack_jarray) {
+        Ok(bytes) => secret::Zeroizing::new(bytes),
+        Err(e) => {
+            error!("Failed to get witness stack bytes: {:?}", e);
+            throw_exception(&mut env, "java/lang/RuntimeException", &format!("Failed to get witness stack bytes: {:?}", e));
+            return std::ptr::null_mut();
+        }
+    };
+
+    metrics.phase("decode");
+    if let Err(e) = circuit::ensure_single_function(&circuit_bytecode) {
+        error!("{}", e);
+        throw_exception(&mut env, "java/lang/IllegalArgumentException", &e);
+        return std::ptr::null_mut();
+    }
+
+    // The whole circuit is executed again from the solved witness before proving,
+    // Brillig and black box calls included, so this costs as much as `prove`
+    let proof = match batch::prove_from_witness_stack(&proof_type, &circuit_bytecode, &witness_stack_bytes, verification_key, use_low_memory, storage_cap) {
+        Ok(p) => {
+            info!("Proof generation successful, proof size: {} bytes", p.len());
+            p
+        },
+        Err(e) => {
+            error!("Proof generation failed: {:?}", e);
+            throw_exception(&mut env, "java/lang/RuntimeException", &format!("Proof generation failed: {:?}", e));
+            return std::ptr::null_mut();
+        }
+    };
+    metrics.phase("prove");
+    metrics.finish();
+    metrics.srs_points(&circuit_bytecode);
+
+    match env.new_string(hex::encode(&proof)) {
+        Ok(s) => s.into_raw(),
+        Err(e) => {
+            error!("Failed to create Java string for proof: {:?}", e);
+            throw_exception(&mut env, "java/lang/RuntimeException", &format!("Failed to create Java string for proof: {:?}", e));
+            std::ptr::null_mut()
+        }
+    }
+}
+
+#[no_mangle]
+pub extern "system" fn Java_com_noirandroid_lib_Noir_00024Companion_write_1witness_1file<'local>(
+    mut env: JNIEnv<'local>,
+    _class: JClass<'local>,
+    witness_stack_jarray: JByteArray<'local>,
+    path_jstr: JString<'local>,
+) {
+    init_logger();
+    info!("Writing witness file");
+
+    let path = match env.get_string(&path_jstr) {
+        Ok(s) => s,
+        Err(e) => {
+            error!("Failed to get path string: {:?}", e);
+            throw_exception(&mut env, "java/lang/RuntimeException", &format!("Failed to get path string: {:?}", e));
+            return;
+        }
+    };
+    let path = match path.to_str() {
+        Ok(s) => s.to_owned(),
+        Err(e) => {
+            error!("Failed to convert path to Rust string: {:?}", e);
+            throw_exception(&mut env, "java/lang/RuntimeException", &format!("Failed to convert path to Rust string: {:?}", e));
+            return;
+        }
+    };
+
+    let witness_stack_bytes = match env.convert_byte_array(&witness_stack_jarray) {
+        Ok(bytes) => secret::Zeroizing::new(bytes),
+        Err(e) => {
+            error!("Failed to get witness stack bytes: {:?}", e);
+            throw_exception(&mut env, "java/lang/RuntimeException", &format!("Failed to get witness stack bytes: {:?}", e));
+            return;
+        }
+    };
+
+    // Deserializing first makes sure only valid witness files are written
+    let result = witness::deserialize_witness_stack(&witness_stack_bytes)
+        .and_then(|stack| witness::write_witness_file(&path, &stack));
+    if let Err(e) = result {
+        error!("{}", e);
+        throw_exception(&mut env, "java/lang/RuntimeException", &e);
+    }
+}
+
+#[no_mangle]
+pub extern "system" fn Java_com_noirandroid_lib_Noir_00024Companion_read_1witness_1file<'local>(
+    mut env: JNIEnv<'local>,
+    _class: JClass<'local>,
+    path_jstr: JString<'local>,
+) -> jbyteArray {
+    init_logger();
+    info!("Reading witness file");
+
+    let path = match env.get_string(&path_jstr) {
+        Ok(s) => s,
+        Err(e) => {
+            error!("Failed to get path string: {:?}", e);
+            throw_exception(&mut env, "java/lang/RuntimeException", &format!("Failed to get path string: {:?}", e));
+            return std::ptr::null_mut();
+        }
+    };
+    let path = match path.to_str() {
+        Ok(s) => s.to_owned(),
+        Err(e) => {
+            error!("Failed to convert path to Rust string: {:?}", e);
+            throw_exception(&mut env, "java/lang/RuntimeException", &format!("Failed to convert path to Rust string: {:?}", e));
+            return std::ptr::null_mut();
+        }
+    };
+
+    let serialized = match witness::read_witness_file(&path).and_then(|stack| witness::serialize_witness_stack(&stack)) {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            error!("{}", e);
+            throw_exception(&mut env, "java/lang/RuntimeException", &e);
+            return std::ptr::null_mut();
+        }
+    };
+
+    match env.byte_array_from_slice(&serialized) {
+        Ok(array) => array.into_raw(),
+        Err(e) => {
+            error!("Failed to create Java byte array for witness stack: {:?}", e);
+            throw_exception(&mut env, "java/lang/RuntimeException", &format!("Failed to create Java byte array for witness stack: {:?}", e));
+            std::ptr::null_mut()
+        }
+    }
+}
+
+#[no_mangle]
+pub extern "system" fn Java_com_noirandroid_lib_Noir_00024Companion_prove<'local>(
+    mut env: JNIEnv<'local>,
+    _class: JClass<'local>,
+    circuit_bytecode_jstr: JString<'local>,
+    witness_jobject: JObject<'local>,
+    vk_jstr: JString<'local>,
+    proof_type_jstr: JString<'local>,
+    low_memory_mode: jboolean,
+    max_storage_usage: jlong
+) -> jobject {
+    init_logger();
+    let mut metrics = metrics::MetricsRecorder::start("prove");
+    let _srs_in_use = release::SrsInUse::acquire();
+    info!("Starting proof generation");
+    
+    let use_low_memory = low_memory_mode != 0;
+    debug!("Low memory mode: {}", use_low_memory);
+    let storage_cap = match checked_storage_cap(max_storage_usage, use_low_memory) {
+        Ok(cap) => cap,
+        Err(e) => {
+            error!("{}", e);
+            throw_exception(&mut env, "java/lang/IllegalArgumentException", &e);
+            return std::ptr::null_mut();
+        }
+    };
+    debug!("Max storage usage: {}", storage_cap);
+    let circuit_bytecode = match env.get_string(&circuit_bytecode_jstr) {
+        Ok(s) => s,
+        Err(e) => {
+            error!("Failed to get bytecode string: {:?}", e);
+            throw_exception(&mut env, "java/lang/RuntimeException", &format!("Failed to get bytecode string: {:?}", e));
+            return std::ptr::null_mut();
+        }
+    };
+    let circuit_bytecode = match circuit_bytecode.to_str() {
+        Ok(s) => s.to_owned(),
+        Err(e) => {
+            error!("Failed to convert bytecode to Rust string: {:?}", e);
+            throw_exception(&mut env, "java/lang/RuntimeException", &format!("Failed to convert bytecode to Rust string: {:?}", e));
+            return std::ptr::null_mut();
+        }
+    };
+    debug!("Circuit bytecode length: {}", circuit_bytecode.len());
+
+    let proof_type = match env.get_string(&proof_type_jstr) {
+        Ok(s) => s,
+        Err(e) => {
+            error!("Failed to get proof type string: {:?}", e);
+            throw_exception(&mut env, "java/lang/RuntimeException", &format!("Failed to get proof type string: {:?}", e));
+            return std::ptr::null_mut();
+        }
+    };
+    let proof_type = match proof_type.to_str() {
+        Ok(s) => s.to_owned(),
+        Err(e) => {
+            error!("Failed to convert proof type to Rust string: {:?}", e);
+            throw_exception(&mut env, "java/lang/RuntimeException", &format!("Failed to convert proof type to Rust string: {:?}", e));
+            return std::ptr::null_mut();
+        }
+    };
+    info!("Using proof type: {}", proof_type);
+
+    let vk_str = match env.get_string(&vk_jstr) {
+        Ok(s) => s,
+        Err(e) => {
+            error!("Failed to get verification key string: {:?}", e);
+            throw_exception(&mut env, "java/lang/RuntimeException", &format!("Failed to get verification key string: {:?}", e));
+            return std::ptr::null_mut();
+        }
+    };
+    let vk_str = match vk_str.to_str() {
+        Ok(s) => s.to_owned(),
+        Err(e) => {
+            error!("Failed to convert verification key to Rust string: {:?}", e);
+            throw_exception(&mut env, "java/lang/RuntimeException", &format!("Failed to convert verification key to Rust string: {:?}", e));
+            return std::ptr::null_mut();
+        }
+    };
+    debug!("Verification key length: {}", vk_str.len());
+
+    let verification_key = match hex::decode(vk_str) {
+        Ok(vk) => {
+            debug!("Successfully decoded verification key, size: {} bytes", vk.len());
+            vk
+        },
+        Err(e) => {
+            error!("Failed to decode verification key: {:?}", e);
+            throw_exception(&mut env, "java/lang/RuntimeException", &format!("Failed to decode verification key: {:?}", e));
+            return std::ptr::null_mut();
+        }
+    };
+
+    let witness_map = match read_witness_map(&mut env, &witness_jobject) {
+        Ok(map) => map,
+        Err(e) => {
+            error!("{}", e);
+            throw_exception(&mut env, "java/lang/RuntimeException", &e);
+            return std::ptr::null_mut();
+        }
+    };
+    info!("Loaded {} witness values", (&*witness_map).into_iter().count());
+
+    if let Err(e) = circuit::ensure_single_function(&circuit_bytecode) {
+        error!("{}", e);
+        throw_exception(&mut env, "java/lang/IllegalArgumentException", &e);
+        return std::ptr::null_mut();
+    }
+
+    metrics.phase("decode");
+    // noir_rs drops its copies of the witness without wiping them
+    let _wipe_freed = secret::WipeFreed::start();
+    let proof = if proof_type == "ultra_honk" { 
+        info!("Generating UltraHonk proof");
+        match prove_ultra_honk(&circuit_bytecode, witness_map.into_inner(), verification_key, use_low_memory, Some(storage_cap)) {
+            Ok(p) => {
+                info!("Proof generation successful, proof size: {} bytes", p.len());
+                p
+            },
+            Err(e) => {
+                error!("Proof generation failed: {:?}", e);
+                throw_exception(&mut env, "java/lang/RuntimeException", &format!("Proof generation failed: {:?}", e));
+                return std::ptr::null_mut();
+            }
+        }
+    } else if proof_type == "ultra_honk_keccak" {
+        info!("Generating UltraHonkKeccak proof");
+        match prove_ultra_honk_keccak(&circuit_bytecode, witness_map.into_inner(), verification_key, false, use_low_memory, Some(storage_cap)) {
+            Ok(p) => {
+                info!("Proof generation successful, proof size: {} bytes", p.len());
+                p
+            },
+            Err(e) => {
+                error!("Proof generation failed: {:?}", e);
+                throw_exception(&mut env, "java/lang/RuntimeException", &format!("Proof generation failed: {:?}", e));
+                return std::ptr::null_mut();
+            }
+        }
+    } else { 
+        error!("Unsupported proof type: {}", proof_type);
+        throw_exception(&mut env, "java/lang/IllegalArgumentException", &format!("Unsupported proof type: {}", proof_type));
+        return std::ptr::null_mut();
+    };
+    metrics.phase("prove");
+
+    let proof_str = hex::encode(&proof);
+    debug!("Encoded proof length: {}", proof_str.len());
+
+    // Create and return a Java string containing the proof
+    let proof_jstr = match env.new_string(proof_str) {
+        Ok(s) => s,
+        Err(e) => {
+            error!("Failed to create Java string for proof: {:?}", e);
+            throw_exception(&mut env, "java/lang/RuntimeException", &format!("Failed to create Java string for proof: {:?}", e));
+            return std::ptr::null_mut();
+        }
+    };
+
+    info!("Successfully prepared proof for return to Java");
+    metrics.finish();
+    metrics.srs_points(&circuit_bytecode);
+
+    proof_jstr.into_raw()
+}
+
+#[no_mangle]
+pub extern "system" fn Java_com_noirandroid_lib_Noir_00024Companion_prove_1batch<'local>(
+    mut env: JNIEnv<'local>,
+    _class: JClass<'local>,
+    circuit_bytecode_jstr: JString<'local>,
+    witnesses_jobject: JObject<'local>,
+    vk_jstr: JString<'local>,
+    proof_type_jstr: JString<'local>,
+    low_memory_mode: jboolean,
+    max_storage_usage: jlong,
+    max_parallel: jint,
+    memory_budget: jlong,
+    circuit_size: jint
+) -> jobject {
+    init_logger();
+    let mut metrics = metrics::MetricsRecorder::start("prove_batch");
+    let _srs_in_use = release::SrsInUse::acquire();
+    info!("Starting batch proof generation");
+
+    let use_low_memory = low_memory_mode != 0;
+    debug!("Low memory mode: {}", use_low_memory);
+    let storage_cap = match checked_storage_cap(max_storage_usage, use_low_memory) {
+        Ok(cap) => cap,
+        Err(e) => {
+            error!("{}", e);
+            throw_exception(&mut env, "java/lang/IllegalArgumentException", &e);
+            return std::ptr::null_mut();
+        }
+    };
+    debug!("Max storage usage: {}", storage_cap);
+    let options = batch::BatchOptions {
+        max_parallel: max_parallel.max(1) as usize,
+        memory_budget: memory_budget.max(0) as u64,
+        circuit_size: circuit_size.max(0) as u32,
+    };
+    debug!("Max parallel proofs: {}, memory budget: {}", options.max_parallel, options.memory_budget);
+
+    let circuit_bytecode = match env.get_string(&circuit_bytecode_jstr) {
+        Ok(s) => s,
+        Err(e) => {
+            error!("Failed to get bytecode string: {:?}", e);
+            throw_exception(&mut env, "java/lang/RuntimeException", &format!("Failed to get bytecode string: {:?}", e));
+            return std::ptr::null_mut();
+        }
+    };
+    let circuit_bytecode = match circuit_bytecode.to_str() {
+        Ok(s) => s.to_owned(),
+        Err(e) => {
+            error!("Failed to convert bytecode to Rust string: {:?}", e);
+            throw_exception(&mut env, "java/lang/RuntimeException", &format!("Failed to convert bytecode to Rust string: {:?}", e));
+            return std::ptr::null_mut();
+        }
+    };
+    debug!("Circuit bytecode length: {}", circuit_bytecode.len());
+
+    let proof_type = match env.get_string(&proof_type_jstr) {
+        Ok(s) => s,
+        Err(e) => {
+            error!("Failed to get proof type string: {:?}", e);
+            throw_exception(&mut env, "java/lang/RuntimeException", &format!("Failed to get proof type string: {:?}", e));
+            return std::ptr::null_mut();
+        }
+    };
+    let proof_type = match proof_type.to_str() {
+        Ok(s) => s.to_owned(),
+        Err(e) => {
+            error!("Failed to convert proof type to Rust string: {:?}", e);
+            throw_exception(&mut env, "java/lang/RuntimeException", &format!("Failed to convert proof type to Rust string: {:?}", e));
+            return std::ptr::null_mut();
+        }
+    };
+    info!("Using proof type: {}", proof_type);
+    if proof_type != "ultra_honk" && proof_type != "ultra_honk_keccak" {
+        error!("Unsupported proof type: {}", proof_type);
+        throw_exception(&mut env, "java/lang/IllegalArgumentException", &format!("Unsupported proof type: {}", proof_type));
+        return std::ptr::null_mut();
+    }
+
+    let vk_str = match env.get_string(&vk_jstr) {
+        Ok(s) => s,
+        Err(e) => {
+            error!("Failed to get verification key string: {:?}", e);
+            throw_exception(&mut env, "java/lang/RuntimeException", &format!("Failed to get verification key string: {:?}", e));
+            return std::ptr::null_mut();
+        }
+    };
+    let vk_str = match vk_str.to_str() {
+        Ok(s) => s.to_owned(),
+        Err(e) => {
+            error!("Failed to convert verification key to Rust string: {:?}", e);
+            throw_exception(&mut env, "java/lang/RuntimeException", &format!("Failed to convert verification key to Rust string: {:?}", e));
+            return std::ptr::null_mut();
+        }
+    };
+    let verification_key = match hex::decode(vk_str) {
+        Ok(vk) => {
+            debug!("Successfully decoded verification key, size: {} bytes", vk.len());
+            vk
+        },
+        Err(e) => {
+            error!("Failed to decode verification key: {:?}", e);
+            throw_exception(&mut env, "java/lang/RuntimeException", &format!("Failed to decode verification key: {:?}", e));
+            return std::ptr::null_mut();
+        }
+    };
+
+    let witness_list = match env.get_list(&witnesses_jobject) {
+        Ok(list) => list,
+        Err(e) => {
+            error!("Failed to get witness list: {:?}", e);
+            throw_exception(&mut env, "java/lang/RuntimeException", &format!("Failed to get witness list: {:?}", e));
+            return std::ptr::null_mut();
+        }
+    };
+    let mut witness_iter = match witness_list.iter(&mut env) {
+        Ok(iter) => iter,
+        Err(e) => {
+            error!("Failed to create iterator for witness list: {:?}", e);
+            throw_exception(&mut env, "java/lang/RuntimeException", &format!("Failed to create iterator: {:?}", e));
+            return std::ptr::null_mut();
+        }
+    };
+
+    // An input that can't be read only fails its own item
+    let mut witnesses = Vec::new();
+    loop {
+        let item = match witness_iter.next(&mut env) {
+            Ok(Some(item)) => item,
+            Ok(None) => break,
+            Err(e) => {
+                error!("Failed to read witness list: {:?}", e);
+                let _ = env.exception_clear();
+                throw_exception(&mut env, "java/lang/RuntimeException", &format!("Failed to read witness list: {:?}", e));
+                return std::ptr::null_mut();
+            }
+        };
+        match read_witness_map(&mut env, &item) {
+            // A JNI call failed rather than the input, leaving a Java exception pending
+            Err(e) if env.exception_check().unwrap_or(false) => {
+                error!("{}", e);
+                let _ = env.exception_clear();
+                throw_exception(&mut env, "java/lang/RuntimeException", &e);
+                return std::ptr::null_mut();
+            }
+            witness => witnesses.push(witness),
+        }
+        if let Err(e) = env.delete_local_ref(item) {
+            error!("Failed to delete local reference: {:?}", e);
+        }
+    }
+    info!("Loaded {} witnesses", witnesses.len());
+
+    if let Err(e) = circuit::ensure_single_function(&circuit_bytecode) {
+        error!("{}", e);
+        throw_exception(&mut env, "java/lang/IllegalArgumentException", &e);
+        return std::ptr::null_mut();
+    }
+
+    metrics.phase("decode");
+    let results = batch::prove_each(&proof_type, &circuit_bytecode, witnesses, verification_key, use_low_memory, storage_cap, &options);
+    metrics.phase("prove");
+
+    let failed = results.iter().filter(|r| r.is_err()).count();
+    info!("Batch proof generation done, {} succeeded, {} failed", results.len() - failed, failed);
+
+    let results: Vec<batch::BatchItemResult> = results.into_iter().map(Into::into).collect();
+    let results_json = match serde_json::to_string(&results) {
+        Ok(json) => json,
+        Err(e) => {
+            error!("Failed to serialize batch results: {:?}", e);
+            throw_exception(&mut env, "java/lang/RuntimeException", &format!("Failed to serialize batch results: {:?}", e));
+            return std::ptr::null_mut();
+        }
+    };
+
+    metrics.finish();
+    metrics.srs_points(&circuit_bytecode);
+
+    match env.new_string(results_json) {
+        Ok(s) => s.into_raw(),
+        Err(e) => {
+            error!("Failed to create Java string for batch results: {:?}", e);
+            throw_exception(&mut env, "java/lang/RuntimeException", &format!("Failed to create Java string for batch results: {:?}", e));
+            std::ptr::null_mut()
+        }
+    }
+}
+
+#[no_mangle]
+pub extern "system" fn Java_com_noirandroid_lib_Noir_00024Companion_verify<'local>(
+    mut env: JNIEnv<'local>,
+    _class: JClass<'local>,
+    proof_jstr: JString<'local>,
+    vk_jstr: JString<'local>,
+    proof_type_jstr: JString<'local>
+) -> jboolean {
+    init_logger();
+    let mut metrics = metrics::MetricsRecorder::start("verify");
+    info!("Starting proof verification");
+    
+    let proof_str = match env.get_string(&proof_jstr) {
+        Ok(s) => s,
+        Err(e) => {
+            error!("Failed to get proof string: {:?}", e);
+            throw_exception(&mut env, "java/lang/RuntimeException", &format!("Failed to get proof string: {:?}", e));
+            return 0;
+        }
+    };
+    let proof_str = match proof_str.to_str() {
+        Ok(s) => s.to_owned(),
+        Err(e) => {
+            error!("Failed to convert proof to Rust string: {:?}", e);
+            throw_exception(&mut env, "java/lang/RuntimeException", &format!("Failed to convert proof to Rust string: {:?}", e));
+            return 0;
+        }
+    };
+    debug!("Proof string length: {}", proof_str.len());
+
+    let vk_str = match env.get_string(&vk_jstr) {
+        Ok(s) => s,
+        Err(e) => {
+            error!("Failed to get verification key string: {:?}", e);
+            throw_exception(&mut env, "java/lang/RuntimeException", &format!("Failed to get verification key string: {:?}", e));
+            return 0;
+        }
+    };
+    let vk_str = match vk_str.to_str() {
+        Ok(s) => s.to_owned(),
+        Err(e) => {
+            error!("Failed to convert verification key to Rust string: {:?}", e);
+            throw_exception(&mut env, "java/lang/RuntimeException", &format!("Failed to convert verification key to Rust string: {:?}", e));
+            return 0;
+        }
+    };
+    debug!("Verification key length: {}", vk_str.len());
+
+    let proof = match hex::decode(proof_str) {
+        Ok(p) => {
+            debug!("Successfully decoded proof, size: {} bytes", p.len());
+            p
+        },
+        Err(e) => {
+            error!("Failed to decode proof: {:?}", e);
+            throw_exception(&mut env, "java/lang/RuntimeException", &format!("Failed to decode proof: {:?}", e));
+            return 0;
+        }
+    };
+    
+    let verification_key = match hex::decode(vk_str) {
+        Ok(vk) => {
+            debug!("Successfully decoded verification key, size: {} bytes", vk.len());
+            vk
+        },
+        Err(e) => {
+            error!("Failed to decode verification key: {:?}", e);
+            throw_exception(&mut env, "java/lang/RuntimeException", &format!("Failed to decode verification key: {:?}", e));
+            return 0;
+        }
+    };
+
+    let proof_type = match env.get_string(&proof_type_jstr) {
+        Ok(s) => s,
+        Err(e) => {
+            error!("Failed to get proof type string: {:?}", e);
+            throw_exception(&mut env, "java/lang/RuntimeException", &format!("Failed to get proof type string: {:?}", e));
+            return 0;
+        }
+    };
+    let proof_type = match proof_type.to_str() {
+        Ok(s) => s.to_owned(),
+        Err(e) => {
+            error!("Failed to convert proof type to Rust string: {:?}", e);
+            throw_exception(&mut env, "java/lang/RuntimeException", &format!("Failed to convert proof type to Rust string: {:?}", e));
+            return 0;
+        }
+    };
+    info!("Using proof type: {}", proof_type);
+
+    metrics.phase("decode");
+    let verdict = if proof_type == "ultra_honk" {
+        match verify_ultra_honk(proof, verification_key) {
+            Ok(v) => {
+                info!("Verification complete, result: {}", v);
+                v
+            },  
+            Err(e) => {
+                error!("Verification failed with error: {:?}", e);
+                throw_exception(&mut env, "java/lang/RuntimeException", &format!("Verification failed: {:?}", e));
+                return 0;
+            }
+        }
+    } else if proof_type == "ultra_honk_keccak" {
+        match verify_ultra_honk_keccak(proof, verification_key, false) {
+            Ok(v) => {
+                info!("Verification complete, result: {}", v);
+                v
+            },
+            Err(e) => {
+                error!("Verification failed with error: {:?}", e);
+                throw_exception(&mut env, "java/lang/RuntimeException", &format!("Verification failed: {:?}", e));
+                return 0;
+            }
+        }
+    } else {
+        error!("Unsupported proof type: {}", proof_type);
+        throw_exception(&mut env, "java/lang/IllegalArgumentException", "Ultra honk and Ultra honk keccak are the only proof types supported for now");
+        return 0;
+    };
+    metrics.phase("verify");
+    metrics.finish();
+
+    jboolean::from(verdict)
+}
+
+#[no_mangle]
+pub extern "system" fn Java_com_noirandroid_lib_Noir_00024Companion_verify_1batch<'local>(
+    mut env: JNIEnv<'local>,
+    _class: JClass<'local>,
+    proofs_jarray: JObjectArray<'local>,
+    vks_jarray: JObjectArray<'local>,
+    proof_types_jarray: JObjectArray<'local>,
+    threads: jint
+) -> jobject {
+    init_logger();
+    let mut metrics = metrics::MetricsRecorder::start("verify_batch");
+    info!("Starting batch proof verification");
+
+    let proofs = match read_string_array(&mut env, &proofs_jarray) {
+        Ok(proofs) => proofs,
+        Err(e) => {
+            error!("Failed to read proofs: {}", e);
+            throw_exception(&mut env, "java/lang/RuntimeException", &format!("Failed to read proofs: {}", e));
+            return std::ptr::null_mut();
+        }
+    };
+    let vks = match read_string_array(&mut env, &vks_jarray) {
+        Ok(vks) => vks,
+        Err(e) => {
+            error!("Failed to read verification keys: {}", e);
+            throw_exception(&mut env, "java/lang/RuntimeException", &format!("Failed to read verification keys: {}", e));
+            return std::ptr::null_mut();
+        }
+    };
+    let proof_types = match read_string_array(&mut env, &proof_types_jarray) {
+        Ok(proof_types) => proof_types,
+        Err(e) => {
+            error!("Failed to read proof types: {}", e);
+            throw_exception(&mut env, "java/lang/RuntimeException", &format!("Failed to read proof types: {}", e));
+            return std::ptr::null_mut();
+        }
+    };
+    if proofs.len() != vks.len() || proofs.len() != proof_types.len() {
+        error!("Mismatched batch lengths: {} proofs, {} keys, {} proof types", proofs.len(), vks.len(), proof_types.len());
+        throw_exception(&mut env, "java/lang/IllegalArgumentException", &format!("Expected as many verification keys and proof types as proofs, got {} proofs, {} keys and {} proof types", proofs.len(), vks.len(), proof_types.len()));
+        return std::ptr::null_mut();
+    }
+
+    let items = proofs
+        .into_iter()
+        .zip(vks)
+        .zip(proof_types)
+        .map(|((proof, vk), proof_type)| batch::VerifyItem { proof, vk, proof_type })
+        .collect();
+    metrics.phase("decode");
+    let results = batch::verify_batch(items, threads.max(1) as usize);
+    metrics.phase("verify");
+    let failed = results.iter().filter(|r| !matches!(r, Ok(true))).count();
+    info!("Batch verification done, {} verified, {} rejected or failed", results.len() - failed, failed);
+
+    let results: Vec<batch::VerifyItemResult> = results.into_iter().map(Into::into).collect();
+    let results_json = match serde_json::to_string(&results) {
+        Ok(json) => json,
+        Err(e) => {
+            error!("Failed to serialize batch results: {:?}", e);
+            throw_exception(&mut env, "java/lang/RuntimeException", &format!("Failed to serialize batch results: {:?}", e));
+            return std::ptr::null_mut();
+        }
+    };
+
+    metrics.finish();
+
+    match env.new_string(results_json) {
+        Ok(s) => s.into_raw(),
+        Err(e) => {
+            error!("Failed to create Java string for batch results: {:?}", e);
+            throw_exception(&mut env, "java/lang/RuntimeException", &format!("Failed to create Java string for batch results: {:?}", e));
+            std::ptr::null_mut()
+        }
+    }
+}
+
+#[no_mangle]
+pub extern "system" fn Java_com_noirandroid_lib_Noir_00024Companion_set_1verify_1pool_1size<'local>(
+    mut env: JNIEnv<'local>,
+    _class: JClass<'local>,
+    threads: jint,
+) {
+    init_logger();
+
+    match batch::set_verify_pool_size(threads.max(0) as usize) {
+        Ok(()) => info!("Verification pool size: {} threads", threads),
+        Err(e) => {
+            error!("{}", e);
+            throw_exception(&mut env, "java/lang/IllegalStateException", &e);
+        }
+    }
+}
+
+#[no_mangle]
+pub extern "system" fn Java_com_noirandroid_lib_Noir_00024Companion_get_1verification_1key<'local>(
+    mut env: JNIEnv<'local>,
+    _class: JClass<'local>,
+    circuit_bytecode_jstr: JString<'local>,
+    proof_type_jstr: JString<'local>,
+    low_memory_mode: jboolean,
+    max_storage_usage: jlong
+) -> jobject {
+    init_logger();
+    let mut metrics = metrics::MetricsRecorder::start("get_verification_key");
+    let _srs_in_use = release::SrsInUse::acquire();
+    info!("Getting verification key");
+    
+    let use_low_memory = low_memory_mode != 0;
+    debug!("Low memory mode: {}", use_low_memory);
+    let storage_cap = match checked_storage_cap(max_storage_usage, use_low_memory) {
+        Ok(cap) => cap,
+        Err(e) => {
+            error!("{}", e);
+            throw_exception(&mut env, "java/lang/IllegalArgumentException", &e);
+            return std::ptr::null_mut();
+        }
+    };
+    debug!("Max storage usage: {}", storage_cap);
+    
+    let circuit_bytecode = match env.get_string(&circuit_bytecode_jstr) {
+        Ok(s) => s,
+        Err(e) => {
+            error!("Failed to get bytecode string: {:?}", e);
+            throw_exception(&mut env, "java/lang/RuntimeException", &format!("Failed to get bytecode string: {:?}", e));
+            return std::ptr::null_mut();
+        }
+    };
+    let circuit_bytecode = match circuit_bytecode.to_str() {
+        Ok(s) => s.to_owned(),
+        Err(e) => {
+            error!("Failed to convert bytecode to Rust string: {:?}", e);
             throw_exception(&mut env, "java/lang/RuntimeException", &format!("Failed to convert bytecode to Rust string: {:?}", e));
             return std::ptr::null_mut();
         }
@@ -783,35 +2443,39 @@ pub extern "system" fn Java_com_noirandroid_lib_Noir_00024Companion_get_1verific
     };
     info!("Using proof type: {}", proof_type);
 
-    let vk = if proof_type == "ultra_honk" {
-        match get_ultra_honk_verification_key(&circuit_bytecode, use_low_memory, Some(storage_cap)) {
-            Ok(key) => {
-                info!("Successfully retrieved verification key, size: {} bytes", key.len());
-                key
-            },
-            Err(e) => {
-                error!("Failed to get verification key: {:?}", e);
-                throw_exception(&mut env, "java/lang/RuntimeException", &format!("Failed to get verification key: {:?}", e));
-                return std::ptr::null_mut();
-            }
-        }
-    } else if proof_type == "ultra_honk_keccak" {
-        match get_ultra_honk_keccak_verification_key(&circuit_bytecode, false, use_low_memory, Some(storage_cap)) {
-            Ok(key) => {
-                info!("Successfully retrieved verification key, size: {} bytes", key.len());
-                key
-            },
-            Err(e) => {
-                error!("Failed to get verification key: {:?}", e);
-                throw_exception(&mut env, "java/lang/RuntimeException", &format!("Failed to get verification key: {:?}", e));
-                return std::ptr::null_mut();
-            }
-        }
-    } else {
+    if proof_type != "ultra_honk" && proof_type != "ultra_honk_keccak" {
         error!("Unsupported proof type: {}", proof_type);
         throw_exception(&mut env, "java/lang/IllegalArgumentException", "Ultra honk and Ultra honk keccak are the only proof types supported for now");
         return std::ptr::null_mut();
+    }
+
+    if let Err(e) = circuit::ensure_single_function(&circuit_bytecode) {
+        error!("{}", e);
+        throw_exception(&mut env, "java/lang/IllegalArgumentException", &e);
+        return std::ptr::null_mut();
+    }
+
+    metrics.phase("decode");
+    let cache_key = vk_cache::VkCacheKey::new(&circuit_bytecode, &proof_type, false);
+    let vk_result = vk_cache::vk_cache().get_or_compute(cache_key, || {
+        if proof_type == "ultra_honk" {
+            get_ultra_honk_verification_key(&circuit_bytecode, use_low_memory, Some(storage_cap))
+        } else {
+            get_ultra_honk_keccak_verification_key(&circuit_bytecode, false, use_low_memory, Some(storage_cap))
+        }
+    });
+    let vk = match vk_result {
+        Ok(key) => {
+            info!("Successfully retrieved verification key, size: {} bytes", key.len());
+            key
+        },
+        Err(e) => {
+            error!("Failed to get verification key: {:?}", e);
+            throw_exception(&mut env, "java/lang/RuntimeException", &format!("Failed to get verification key: {:?}", e));
+            return std::ptr::null_mut();
+        }
     };
+    metrics.phase("verification_key");
 
     let vk_str = hex::encode(&vk);
     debug!("Encoded verification key length: {}", vk_str.len());
@@ -827,12 +2491,393 @@ pub extern "system" fn Java_com_noirandroid_lib_Noir_00024Companion_get_1verific
     };
 
     info!("Successfully prepared verification key for return to Java");
+    metrics.finish();
+    metrics.srs_points(&circuit_bytecode);
+
     vk_jstr.into_raw()
 }
 
+#[no_mangle]
+pub extern "system" fn Java_com_noirandroid_lib_Noir_00024Companion_set_1vk_1cache_1dir<'local>(
+    mut env: JNIEnv<'local>,
+    _class: JClass<'local>,
+    cache_dir_jstr: JString<'local>,
+) {
+    init_logger();
+
+    let cache_dir = match cache_dir_jstr.is_null() {
+        true => {
+            debug!("Cache directory is null, keeping verification keys in memory only");
+            None
+        },
+        false => {
+            let dir = match env.get_string(&cache_dir_jstr) {
+                Ok(s) => s,
+                Err(e) => {
+                    error!("Failed to get cache directory string: {:?}", e);
+                    throw_exception(&mut env, "java/lang/RuntimeException", &format!("Failed to get cache directory string: {:?}", e));
+                    return;
+                }
+            };
+            match dir.to_str() {
+                Ok(s) => Some(std::path::PathBuf::from(s)),
+                Err(e) => {
+                    error!("Failed to convert cache directory to Rust string: {:?}", e);
+                    throw_exception(&mut env, "java/lang/RuntimeException", &format!("Failed to convert cache directory to Rust string: {:?}", e));
+                    return;
+                }
+            }
+        },
+    };
+
+    if let Err(e) = vk_cache::vk_cache().set_disk_dir(cache_dir) {
+        error!("Failed to set verification key cache directory: {}", e);
+        throw_exception(&mut env, "java/lang/RuntimeException", &format!("Failed to set verification key cache directory: {}", e));
+    }
+}
+
+#[no_mangle]
+pub extern "system" fn Java_com_noirandroid_lib_Noir_00024Companion_release_1resources<'local>(
+    mut env: JNIEnv<'local>,
+    _class: JClass<'local>,
+    level: jint,
+) -> jobject {
+    init_logger();
+    info!("Releasing resources at level {}", level);
+
+    let report = release::release_resources(level);
+    let report_json = match serde_json::to_string(&report) {
+        Ok(json) => json,
+        Err(e) => {
+            error!("Failed to serialize release report: {:?}", e);
+            throw_exception(&mut env, "java/lang/RuntimeException", &format!("Failed to serialize release report: {:?}", e));
+            return std::ptr::null_mut();
+        }
+    };
+
+    match env.new_string(report_json) {
+        Ok(s) => s.into_raw(),
+        Err(e) => {
+            error!("Failed to create Java string for release report: {:?}", e);
+            throw_exception(&mut env, "java/lang/RuntimeException", &format!("Failed to create Java string for release report: {:?}", e));
+            std::ptr::null_mut()
+        }
+    }
+}
+
+#[no_mangle]
+pub extern "system" fn Java_com_noirandroid_lib_Noir_00024Companion_set_1scratch_1dir<'local>(
+    mut env: JNIEnv<'local>,
+    _class: JClass<'local>,
+    parent_dir_jstr: JString<'local>,
+) -> jobject {
+    init_logger();
+
+    let parent_dir: String = match env.get_string(&parent_dir_jstr) {
+        Ok(s) => s.into(),
+        Err(e) => {
+            error!("Failed to get scratch directory string: {:?}", e);
+            throw_exception(&mut env, "java/lang/RuntimeException", &format!("Failed to get scratch directory string: {:?}", e));
+            return std::ptr::null_mut();
+        }
+    };
+
+    let scratch = match scratch::set_scratch_dir(std::path::Path::new(&parent_dir)) {
+        Ok(scratch) => scratch,
+        Err(e) => {
+            error!("Failed to set scratch directory: {}", e);
+            throw_exception(&mut env, "java/lang/RuntimeException", &format!("Failed to set scratch directory: {}", e));
+            return std::ptr::null_mut();
+        }
+    };
+
+    let scratch_json = match serde_json::to_string(&scratch) {
+        Ok(json) => json,
+        Err(e) => {
+            error!("Failed to serialize scratch directory: {:?}", e);
+            throw_exception(&mut env, "java/lang/RuntimeException", &format!("Failed to serialize scratch directory: {:?}", e));
+            return std::ptr::null_mut();
+        }
+    };
+
+    match env.new_string(scratch_json) {
+        Ok(s) => s.into_raw(),
+        Err(e) => {
+            error!("Failed to create Java string for scratch directory: {:?}", e);
+            throw_exception(&mut env, "java/lang/RuntimeException", &format!("Failed to create Java string for scratch directory: {:?}", e));
+            std::ptr::null_mut()
+        }
+    }
+}
+
+#[no_mangle]
+pub extern "system" fn Java_com_noirandroid_lib_Noir_00024Companion_get_1vk_1cache_1stats<'local>(
+    mut env: JNIEnv<'local>,
+    _class: JClass<'local>,
+) -> jobject {
+    init_logger();
+
+    let stats = vk_cache::vk_cache().stats();
+    debug!("Verification key cache stats: {:?}", stats);
+
+    let stats_json = match serde_json::to_string(&stats) {
+        Ok(json) => json,
+        Err(e) => {
+            error!("Failed to serialize cache stats: {:?}", e);
+            throw_exception(&mut env, "java/lang/RuntimeException", &format!("Failed to serialize cache stats: {:?}", e));
+            return std::ptr::null_mut();
+        }
+    };
+
+    match env.new_string(stats_json) {
+        Ok(s) => s.into_raw(),
+        Err(e) => {
+            error!("Failed to create Java string for cache stats: {:?}", e);
+            throw_exception(&mut env, "java/lang/RuntimeException", &format!("Failed to create Java string for cache stats: {:?}", e));
+            std::ptr::null_mut()
+        }
+    }
+}
+
+#[no_mangle]
+pub extern "system" fn Java_com_noirandroid_lib_Noir_00024Companion_clear_1vk_1cache<'local>(
+    _env: JNIEnv<'local>,
+    _class: JClass<'local>,
+) {
+    init_logger();
+    info!("Clearing verification key cache");
+    vk_cache::vk_cache().clear();
+}
+
+#[no_mangle]
+pub extern "system" fn Java_com_noirandroid_lib_Noir_00024Companion_set_1log_1level<'local>(
+    mut env: JNIEnv<'local>,
+    _class: JClass<'local>,
+    level_jstr: JString<'local>,
+) {
+    init_logger();
+
+    let level = match env.get_string(&level_jstr) {
+        Ok(s) => s,
+        Err(e) => {
+            error!("Failed to get log level string: {:?}", e);
+            throw_exception(&mut env, "java/lang/RuntimeException", &format!("Failed to get log level string: {:?}", e));
+            return;
+        }
+    };
+    let level = match level.to_str() {
+        Ok(s) => s.to_owned(),
+        Err(e) => {
+            error!("Failed to convert log level to Rust string: {:?}", e);
+            throw_exception(&mut env, "java/lang/RuntimeException", &format!("Failed to convert log level to Rust string: {:?}", e));
+            return;
+        }
+    };
+
+    match logging::set_level(&level) {
+        Ok(level) => info!("Log level set to {}", level),
+        Err(e) => {
+            error!("{}", e);
+            throw_exception(&mut env, "java/lang/IllegalArgumentException", &e);
+        }
+    }
+}
+
+#[no_mangle]
+pub extern "system" fn Java_com_noirandroid_lib_Noir_00024Companion_set_1log_1redaction<'local>(
+    _env: JNIEnv<'local>,
+    _class: JClass<'local>,
+    enabled: jboolean,
+) {
+    init_logger();
+    logging::set_redaction(enabled != 0);
+    info!("Log redaction {}", if enabled != 0 { "enabled" } else { "disabled" });
+}
+
+#[no_mangle]
+pub extern "system" fn Java_com_noirandroid_lib_Noir_00024Companion_set_1log_1sink<'local>(
+    mut env: JNIEnv<'local>,
+    _class: JClass<'local>,
+    sink: JObject<'local>,
+    level_jstr: JString<'local>,
+) {
+    init_logger();
+
+    if sink.is_null() {
+        log_sink::remove_sink();
+        info!("Log sink removed");
+        return;
+    }
+
+    let level = match env.get_string(&level_jstr) {
+        Ok(s) => s,
+        Err(e) => {
+            error!("Failed to get log level string: {:?}", e);
+            throw_exception(&mut env, "java/lang/RuntimeException", &format!("Failed to get log level string: {:?}", e));
+            return;
+        }
+    };
+    let level: log::LevelFilter = match level.to_str().map(|s| s.parse()) {
+        Ok(Ok(level)) => level,
+        _ => {
+            error!("Unknown log level for the log sink");
+            throw_exception(&mut env, "java/lang/IllegalArgumentException", "Unknown log level for the log sink");
+            return;
+        }
+    };
+
+    let vm = match env.get_java_vm() {
+        Ok(vm) => vm,
+        Err(e) => {
+            error!("Failed to get Java VM: {:?}", e);
+            throw_exception(&mut env, "java/lang/RuntimeException", &format!("Failed to get Java VM: {:?}", e));
+            return;
+        }
+    };
+    let sink = match env.new_global_ref(&sink) {
+        Ok(sink) => sink,
+        Err(e) => {
+            error!("Failed to create global reference to the log sink: {:?}", e);
+            throw_exception(&mut env, "java/lang/RuntimeException", &format!("Failed to create global reference to the log sink: {:?}", e));
+            return;
+        }
+    };
+
+    match log_sink::set_sink(vm, sink, level) {
+        Ok(()) => info!("Log sink set at level {}", level),
+        Err(e) => {
+            error!("{}", e);
+            throw_exception(&mut env, "java/lang/RuntimeException", &e);
+        }
+    }
+}
+
+#[no_mangle]
+pub extern "system" fn Java_com_noirandroid_lib_Noir_00024Companion_start_1trace<'local>(
+    mut env: JNIEnv<'local>,
+    _class: JClass<'local>,
+    path_jstr: JString<'local>,
+) {
+    init_logger();
+
+    let path = match env.get_string(&path_jstr) {
+        Ok(s) => s,
+        Err(e) => {
+            error!("Failed to get trace path string: {:?}", e);
+            throw_exception(&mut env, "java/lang/RuntimeException", &format!("Failed to get trace path string: {:?}", e));
+            return;
+        }
+    };
+    let path = match path.to_str() {
+        Ok(s) => std::path::PathBuf::from(s),
+        Err(e) => {
+            error!("Failed to convert trace path to Rust string: {:?}", e);
+            throw_exception(&mut env, "java/lang/RuntimeException", &format!("Failed to convert trace path to Rust string: {:?}", e));
+            return;
+        }
+    };
+
+    match trace::start(&path) {
+        Ok(()) => info!("Recording trace to {:?}", path),
+        Err(e) => {
+            error!("{}", e);
+            throw_exception(&mut env, "java/lang/IllegalStateException", &e);
+        }
+    }
+}
+
+#[no_mangle]
+pub extern "system" fn Java_com_noirandroid_lib_Noir_00024Companion_stop_1trace<'local>(
+    mut env: JNIEnv<'local>,
+    _class: JClass<'local>,
+) -> jobject {
+    init_logger();
+
+    let summary = match trace::stop() {
+        Ok(summary) => {
+            info!("Trace of {} spans written to {}", summary.spans, summary.path);
+            summary
+        },
+        Err(e) => {
+            error!("{}", e);
+            throw_exception(&mut env, "java/lang/IllegalStateException", &e);
+            return std::ptr::null_mut();
+        }
+    };
+
+    let summary_json = match serde_json::to_string(&summary) {
+        Ok(json) => json,
+        Err(e) => {
+            error!("Failed to serialize trace summary: {:?}", e);
+            throw_exception(&mut env, "java/lang/RuntimeException", &format!("Failed to serialize trace summary: {:?}", e));
+            return std::ptr::null_mut();
+        }
+    };
+
+    match env.new_string(summary_json) {
+        Ok(s) => s.into_raw(),
+        Err(e) => {
+            error!("Failed to create Java string for trace summary: {:?}", e);
+            throw_exception(&mut env, "java/lang/RuntimeException", &format!("Failed to create Java string for trace summary: {:?}", e));
+            std::ptr::null_mut()
+        }
+    }
+}
+
+#[no_mangle]
+pub extern "system" fn Java_com_noirandroid_lib_Noir_00024Companion_set_1collect_1metrics<'local>(
+    _env: JNIEnv<'local>,
+    _class: JClass<'local>,
+    enabled: jboolean,
+) -> jboolean {
+    init_logger();
+    let was_enabled = metrics::set_collecting(enabled != 0);
+    debug!("Metrics collection on this thread: {}", enabled != 0);
+    was_enabled as jboolean
+}
+
+#[no_mangle]
+pub extern "system" fn Java_com_noirandroid_lib_Noir_00024Companion_set_1peak_1rss_1reset<'local>(
+    _env: JNIEnv<'local>,
+    _class: JClass<'local>,
+    enabled: jboolean,
+) {
+    init_logger();
+    metrics::set_peak_rss_reset(enabled != 0);
+    debug!("Peak RSS reset at the start of measured calls: {}", enabled != 0);
+}
+
+#[no_mangle]
+pub extern "system" fn Java_com_noirandroid_lib_Noir_00024Companion_take_1metrics<'local>(
+    mut env: JNIEnv<'local>,
+    _class: JClass<'local>,
+) -> jobject {
+    init_logger();
+
+    let metrics = metrics::take_collected();
+    let metrics_json = match serde_json::to_string(&metrics) {
+        Ok(json) => json,
+        Err(e) => {
+            error!("Failed to serialize metrics: {:?}", e);
+            throw_exception(&mut env, "java/lang/RuntimeException", &format!("Failed to serialize metrics: {:?}", e));
+            return std::ptr::null_mut();
+        }
+    };
+
+    match env.new_string(metrics_json) {
+        Ok(s) => s.into_raw(),
+        Err(e) => {
+            error!("Failed to create Java string for metrics: {:?}", e);
+            throw_exception(&mut env, "java/lang/RuntimeException", &format!("Failed to create Java string for metrics: {:?}", e));
+            std::ptr::null_mut()
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use noir_rs::acir::native_types::WitnessMap;
     use std::collections::HashMap;
 
     // Mock JNI environment for testing
@@ -0,0 +1,141 @@
+use jni::objects::{GlobalRef, JValue};
+use jni::JavaVM;
+use log::{Level, LevelFilter, Record};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{self, Receiver, SyncSender, TrySendError};
+use std::sync::{Arc, RwLock};
+use std::thread;
+
+use crate::logging;
+
+// Records waiting for the sink, past which new ones are dropped
+const QUEUE_CAPACITY: usize = 512;
+
+struct QueuedRecord {
+    level: Level,
+    target: String,
+    message: String,
+}
+
+struct Sink {
+    sender: SyncSender<QueuedRecord>,
+    level: LevelFilter,
+    // Records dropped since the sink was last told about it
+    dropped: Arc<AtomicU64>,
+}
+
+impl Sink {
+    // Queues the record if the sink takes its level, never blocking
+    fn forward(&self, record: &Record) {
+        if record.level() > self.level {
+            return;
+        }
+        let queued = QueuedRecord {
+            level: record.level(),
+            target: record.target().to_owned(),
+            message: record.args().to_string(),
+        };
+        if let Err(TrySendError::Full(_)) = self.sender.try_send(queued) {
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+}
+
+static SINK: RwLock<Option<Sink>> = RwLock::new(None);
+
+/// Forwards the records up to `level` to `onLog(level, target, message)` of
+/// a Java LogSinkBridge, replacing the previous sink. The records are queued
+/// and delivered from a thread of their own, so a slow sink never holds up
+/// the thread that logs; when the queue is full, records are dropped.
+pub fn set_sink(vm: JavaVM, bridge: GlobalRef, level: LevelFilter) -> Result<(), String> {
+    let (sender, receiver) = mpsc::sync_channel(QUEUE_CAPACITY);
+    let dropped = Arc::new(AtomicU64::new(0));
+    let delivered_dropped = dropped.clone();
+    thread::Builder::new()
+        .name("noir-log-sink".to_owned())
+        .spawn(move || deliver(vm, bridge, receiver, delivered_dropped))
+        .map_err(|e| format!("Failed to start log sink thread: {}", e))?;
+    // Dropping the previous sender ends its thread once its queue is delivered
+    *SINK.write().unwrap_or_else(|e| e.into_inner()) = Some(Sink { sender, level, dropped });
+    logging::apply_level();
+    Ok(())
+}
+
+pub fn remove_sink() {
+    *SINK.write().unwrap_or_else(|e| e.into_inner()) = None;
+    logging::apply_level();
+}
+
+/// Level of the sink, `Off` without one
+pub fn level() -> LevelFilter {
+    SINK.read()
+        .unwrap_or_else(|e| e.into_inner())
+        .as_ref()
+        .map_or(LevelFilter::Off, |sink| sink.level)
+}
+
+/// Queues the record for the sink if it takes its level, never blocking
+pub fn forward(record: &Record) {
+    if let Some(sink) = SINK.read().unwrap_or_else(|e| e.into_inner()).as_ref() {
+        sink.forward(record);
+    }
+}
+
+fn deliver(vm: JavaVM, bridge: GlobalRef, receiver: Receiver<QueuedRecord>, dropped: Arc<AtomicU64>) {
+    // Failures aren't logged, as the records would be queued for this very thread
+    let Ok(mut env) = vm.attach_current_thread_as_daemon() else {
+        return;
+    };
+    while let Ok(record) = receiver.recv() {
+        let count = dropped.swap(0, Ordering::Relaxed);
+        if count > 0 {
+            let notice = QueuedRecord {
+                level: Level::Warn,
+                target: module_path!().to_owned(),
+                message: format!("Dropped {} log records, the log sink is too slow", count),
+            };
+            on_log(&mut env, &bridge, &notice);
+        }
+        on_log(&mut env, &bridge, &record);
+    }
+}
+
+fn on_log(env: &mut jni::JNIEnv, bridge: &GlobalRef, record: &QueuedRecord) {
+    // The thread never returns to Java, so its local references have to be freed here
+    let result = env.with_local_frame(4, |env| -> jni::errors::Result<()> {
+        let target = env.new_string(&record.target)?;
+        let message = env.new_string(&record.message)?;
+        env.call_method(
+            bridge,
+            "onLog",
+            "(ILjava/lang/String;Ljava/lang/String;)V",
+            &[JValue::Int(record.level as i32), JValue::Object(&target), JValue::Object(&message)],
+        )?;
+        Ok(())
+    });
+    // An exception thrown by the sink only loses its record
+    if result.is_err() && env.exception_check().unwrap_or(false) {
+        let _ = env.exception_clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // On a sink of its own rather than the global one, which the other tests log to
+    #[test]
+    fn test_full_queue_drops_records() {
+        // Nothing takes the records, as with a sink stuck in a slow call
+        let (sender, receiver) = mpsc::sync_channel(QUEUE_CAPACITY);
+        let sink = Sink { sender, level: LevelFilter::Info, dropped: Arc::new(AtomicU64::new(0)) };
+
+        let log = |level: Level| sink.forward(&Record::builder().level(level).args(format_args!("proving")).build());
+        for _ in 0..QUEUE_CAPACITY + 3 {
+            log(Level::Info);
+        }
+        log(Level::Debug);
+        assert_eq!(sink.dropped.load(Ordering::Relaxed), 3);
+        assert_eq!(receiver.try_iter().count(), QUEUE_CAPACITY);
+    }
+}
@@ -0,0 +1,121 @@
+use log::{LevelFilter, Log, Metadata, Record};
+use std::fmt;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+use crate::log_sink;
+
+// Indexed by `LevelFilter as usize`
+const LEVELS: [LevelFilter; 6] = [
+    LevelFilter::Off,
+    LevelFilter::Error,
+    LevelFilter::Warn,
+    LevelFilter::Info,
+    LevelFilter::Debug,
+    LevelFilter::Trace,
+];
+
+static LEVEL: AtomicUsize = AtomicUsize::new(LevelFilter::Info as usize);
+// On in release builds, which is how the library ships to apps
+static REDACTION: AtomicBool = AtomicBool::new(!cfg!(debug_assertions));
+
+// Sends each record to the platform logger (logcat or stderr) and to the Java
+// log sink, each filtered by its own level
+struct NoirLogger {
+    platform: Box<dyn Log>,
+}
+
+impl Log for NoirLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= level() || metadata.level() <= log_sink::level()
+    }
+
+    fn log(&self, record: &Record) {
+        if record.level() <= level() {
+            self.platform.log(record);
+        }
+        log_sink::forward(record);
+    }
+
+    fn flush(&self) {
+        self.platform.flush();
+    }
+}
+
+/// Installs the logger of the library in front of `platform`, which should let
+/// every level through. Does nothing if a logger is already installed.
+pub fn install(platform: Box<dyn Log>) {
+    let _ = log::set_boxed_logger(Box::new(NoirLogger { platform }));
+    apply_level();
+}
+
+/// Level of the platform logs of the library, `Info` unless set otherwise
+pub fn level() -> LevelFilter {
+    LEVELS[LEVEL.load(Ordering::Relaxed)]
+}
+
+/// Sets the level from its name (`off`, `error`, `warn`, `info`, `debug` or
+/// `trace`, in any case). It applies right away, to every thread.
+pub fn set_level(name: &str) -> Result<LevelFilter, String> {
+    let level: LevelFilter = name.parse().map_err(|_| format!("Unknown log level: {}", name))?;
+    LEVEL.store(level as usize, Ordering::Relaxed);
+    apply_level();
+    Ok(level)
+}
+
+// Lets through what either the platform logger or the sink takes
+pub fn apply_level() {
+    log::set_max_level(level().max(log_sink::level()));
+}
+
+/// Whether witness values, witness keys and prints are left out of the logs
+/// and error messages
+pub fn redaction() -> bool {
+    REDACTION.load(Ordering::Relaxed)
+}
+
+pub fn set_redaction(enabled: bool) {
+    REDACTION.store(enabled, Ordering::Relaxed);
+}
+
+/// Private data in a log or error message, shown as `<redacted>` if redaction
+/// was enabled when it was created
+pub struct Secret<T> {
+    value: T,
+    redacted: bool,
+}
+
+pub fn secret<T: fmt::Display>(value: T) -> Secret<T> {
+    Secret { value, redacted: redaction() }
+}
+
+impl<T: fmt::Display> fmt::Display for Secret<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.redacted {
+            f.write_str("<redacted>")
+        } else {
+            self.value.fmt(f)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Doesn't go through set_redaction, which applies to the other tests too
+    #[test]
+    fn test_secrets_are_redacted() {
+        let key = Secret { value: "passport_number", redacted: true };
+        assert_eq!(format!("Failed to parse witness key '{}'", key), "Failed to parse witness key '<redacted>'");
+        assert_eq!(format!("{}", Secret { value: 42, redacted: false }), "42");
+    }
+
+    #[test]
+    fn test_set_level() {
+        assert_eq!(set_level("DEBUG"), Ok(LevelFilter::Debug));
+        assert_eq!(level(), LevelFilter::Debug);
+        assert!(set_level("verbose").is_err());
+        assert_eq!(level(), LevelFilter::Debug);
+        set_level("info").unwrap();
+    }
+}
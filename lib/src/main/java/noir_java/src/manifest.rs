@@ -0,0 +1,388 @@
+use serde::de::IgnoredAny;
+use serde::{Deserialize, Deserializer, Serialize};
+use std::collections::BTreeMap;
+
+/// A circuit compiled by `nargo compile`
+#[derive(Debug, Deserialize)]
+pub struct Manifest {
+    pub noir_version: Option<String>,
+    #[serde(deserialize_with = "deserialize_hash")]
+    pub hash: u64,
+    pub abi: Abi,
+    pub bytecode: String,
+    // Only checked for presence here: the debug symbols are decoded when
+    // profiling needs them, see source_map
+    #[serde(default, deserialize_with = "deserialize_presence")]
+    debug_symbols: bool,
+    #[serde(default, deserialize_with = "deserialize_presence")]
+    file_map: bool,
+}
+
+/// Interface of the main function, in the format nargo writes it. It is
+/// serialized for Java with camelCase names, like the rest of the view.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all(serialize = "camelCase"))]
+pub struct Abi {
+    pub parameters: Vec<AbiParameter>,
+    pub return_type: Option<AbiReturnType>,
+    #[serde(default)]
+    pub error_types: BTreeMap<String, AbiErrorType>,
+    // Written by the nargo releases that keep the witnesses of the parameters
+    // in the ABI, see Manifest::param_witnesses for the view
+    #[serde(default, skip_serializing)]
+    pub param_witnesses: Option<BTreeMap<String, Vec<WitnessRange>>>,
+    /// Witnesses of the return value, empty if the compiler didn't write them
+    #[serde(default)]
+    pub return_witnesses: Vec<u32>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct AbiParameter {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub typ: AbiType,
+    pub visibility: AbiVisibility,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AbiVisibility {
+    Public,
+    Private,
+    #[serde(rename = "databus")]
+    DataBus,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all(serialize = "camelCase"))]
+pub struct AbiReturnType {
+    pub abi_type: AbiType,
+    pub visibility: AbiVisibility,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Sign {
+    Unsigned,
+    Signed,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum AbiType {
+    Field,
+    Array {
+        length: u32,
+        #[serde(rename = "type")]
+        typ: Box<AbiType>,
+    },
+    Integer {
+        sign: Sign,
+        width: u32,
+    },
+    Boolean,
+    Struct {
+        path: String,
+        fields: Vec<StructField>,
+    },
+    Tuple {
+        // Unlike the fields of a struct they have no name, so Java reads them
+        // under a name of their own
+        #[serde(rename(serialize = "elements"))]
+        fields: Vec<AbiType>,
+    },
+    String {
+        length: u32,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct StructField {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub typ: AbiType,
+}
+
+impl AbiType {
+    /// Number of field elements, and so of witnesses, the type is flattened to.
+    /// Fails if it doesn't fit the witness indices.
+    pub fn field_count(&self) -> Result<u32, String> {
+        match self {
+            AbiType::Field | AbiType::Integer { .. } | AbiType::Boolean => Ok(1),
+            AbiType::Array { length, typ } => length.checked_mul(typ.field_count()?).ok_or_else(|| self.too_large()),
+            AbiType::String { length } => Ok(*length),
+            AbiType::Struct { fields, .. } => self.sum_field_counts(fields.iter().map(|field| &field.typ)),
+            AbiType::Tuple { fields } => self.sum_field_counts(fields.iter()),
+        }
+    }
+
+    fn sum_field_counts<'t>(&self, types: impl Iterator<Item = &'t AbiType>) -> Result<u32, String> {
+        types.map(AbiType::field_count).try_fold(0u32, |total, count| total.checked_add(count?).ok_or_else(|| self.too_large()))
+    }
+
+    fn too_large(&self) -> String {
+        match self {
+            AbiType::Struct { path, .. } => format!("Struct {} has too many fields", path),
+            AbiType::Array { length, .. } => format!("Array of length {} has too many fields", length),
+            _ => format!("Type {} has too many fields", self.kind()),
+        }
+    }
+
+    fn kind(&self) -> &'static str {
+        match self {
+            AbiType::Field => "field",
+            AbiType::Array { .. } => "array",
+            AbiType::Integer { .. } => "integer",
+            AbiType::Boolean => "boolean",
+            AbiType::Struct { .. } => "struct",
+            AbiType::Tuple { .. } => "tuple",
+            AbiType::String { .. } => "string",
+        }
+    }
+}
+
+/// Type of the data an assertion fails with. nargo writes the type of custom
+/// errors flattened into the error; it is serialized under `type` instead, so
+/// that Java can read it as a type of its own.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(tag = "error_kind", rename_all = "lowercase", into = "AbiErrorTypeView")]
+pub enum AbiErrorType {
+    String { string: String },
+    FmtString { length: u32, item_types: Vec<AbiType> },
+    Custom(AbiType),
+}
+
+#[derive(Serialize)]
+#[serde(tag = "errorKind", rename_all = "lowercase")]
+enum AbiErrorTypeView {
+    String {
+        string: String,
+    },
+    FmtString {
+        length: u32,
+        #[serde(rename = "itemTypes")]
+        item_types: Vec<AbiType>,
+    },
+    Custom {
+        #[serde(rename = "type")]
+        typ: AbiType,
+    },
+}
+
+impl From<AbiErrorType> for AbiErrorTypeView {
+    fn from(error_type: AbiErrorType) -> Self {
+        match error_type {
+            AbiErrorType::String { string } => AbiErrorTypeView::String { string },
+            AbiErrorType::FmtString { length, item_types } => AbiErrorTypeView::FmtString { length, item_types },
+            AbiErrorType::Custom(typ) => AbiErrorTypeView::Custom { typ },
+        }
+    }
+}
+
+/// Witnesses `start..end` of the initial witness
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub struct WitnessRange {
+    pub start: u32,
+    pub end: u32,
+}
+
+// nargo writes the hash as a string, older versions as a number
+fn deserialize_hash<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u64, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Hash {
+        Number(u64),
+        Text(String),
+    }
+    match Hash::deserialize(deserializer)? {
+        Hash::Number(hash) => Ok(hash),
+        Hash::Text(text) => text
+            .parse()
+            .map_err(|_| serde::de::Error::custom(format!("invalid circuit hash {:?}", text))),
+    }
+}
+
+// Whether a field holds something, an empty string or null counting as absent
+fn deserialize_presence<'de, D: Deserializer<'de>>(deserializer: D) -> Result<bool, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Field {
+        Text(String),
+        Other(Option<IgnoredAny>),
+    }
+    Ok(match Field::deserialize(deserializer)? {
+        Field::Text(text) => !text.is_empty(),
+        Field::Other(value) => value.is_some(),
+    })
+}
+
+/// The manifest as returned to Java by `parse_manifest`. The hash is given as
+/// a decimal string, as Java has no unsigned 64 bit integer.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ManifestView<'a> {
+    pub noir_version: Option<&'a str>,
+    pub hash: String,
+    pub abi: AbiView<'a>,
+    pub bytecode: &'a str,
+    pub has_debug_symbols: bool,
+}
+
+/// The ABI with the witnesses of each parameter, whether the compiler wrote
+/// them or they were worked out
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AbiView<'a> {
+    #[serde(flatten)]
+    pub abi: &'a Abi,
+    pub param_witnesses: BTreeMap<&'a str, Vec<WitnessRange>>,
+}
+
+impl Manifest {
+    pub fn parse(manifest_json: &str) -> Result<Manifest, String> {
+        serde_json::from_str(manifest_json).map_err(|e| format!("Failed to parse circuit manifest: {}", e))
+    }
+
+    /// Witnesses of each parameter of main, as written by the compiler. When
+    /// the manifest doesn't have them, they are worked out from the types of
+    /// the parameters, which then take the first witnesses in the order they
+    /// are declared.
+    pub fn param_witnesses(&self) -> Result<BTreeMap<&str, Vec<WitnessRange>>, String> {
+        if let Some(declared) = &self.abi.param_witnesses {
+            return Ok(declared.iter().map(|(name, ranges)| (name.as_str(), ranges.clone())).collect());
+        }
+        let mut start = 0u32;
+        self.abi
+            .parameters
+            .iter()
+            .map(|parameter| {
+                let end = parameter
+                    .typ
+                    .field_count()
+                    .and_then(|count| start.checked_add(count).ok_or_else(|| "Parameters have too many fields".to_owned()))
+                    .map_err(|e| format!("Invalid parameter {}: {}", parameter.name, e))?;
+                let range = WitnessRange { start, end };
+                start = end;
+                Ok((parameter.name.as_str(), vec![range]))
+            })
+            .collect()
+    }
+
+    pub fn has_debug_symbols(&self) -> bool {
+        self.debug_symbols && self.file_map
+    }
+
+    pub fn view(&self) -> Result<ManifestView<'_>, String> {
+        Ok(ManifestView {
+            noir_version: self.noir_version.as_deref(),
+            hash: self.hash.to_string(),
+            abi: AbiView { abi: &self.abi, param_witnesses: self.param_witnesses()? },
+            bytecode: &self.bytecode,
+            has_debug_symbols: self.has_debug_symbols(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::{json, Value};
+
+    fn manifest_json(hash: Value) -> String {
+        json!({
+            "noir_version": "1.0.0-beta.14+60ccd48e18ad8ce50d5ecda9baf813b712145051",
+            "hash": hash,
+            "abi": {
+                "parameters": [
+                    { "name": "point", "type": { "kind": "struct", "path": "Point", "fields": [
+                        { "name": "x", "type": { "kind": "field" } },
+                        { "name": "y", "type": { "kind": "integer", "sign": "unsigned", "width": 32 } }
+                    ] }, "visibility": "private" },
+                    { "name": "names", "type": { "kind": "array", "length": 3, "type": { "kind": "string", "length": 4 } }, "visibility": "private" },
+                    { "name": "result", "type": { "kind": "boolean" }, "visibility": "public" }
+                ],
+                "return_type": { "abi_type": { "kind": "tuple", "fields": [{ "kind": "field" }, { "kind": "field" }] }, "visibility": "public" },
+                "error_types": {
+                    "2920182694213909827": { "error_kind": "string", "string": "Too big" },
+                    "13154468461282470596": { "error_kind": "custom", "kind": "field" }
+                }
+            },
+            "bytecode": "H4sIAAAAAAAA/w==",
+            "debug_symbols": "H4sIAAAAAAAA/w==",
+            "file_map": {}
+        })
+        .to_string()
+    }
+
+    #[test]
+    fn test_hash_is_exact() {
+        let manifest = Manifest::parse(&manifest_json(json!("18446744073709551557"))).unwrap();
+        assert_eq!(manifest.hash, 18446744073709551557);
+        let manifest = Manifest::parse(&manifest_json(json!(7656203794231641961u64))).unwrap();
+        assert_eq!(manifest.view().unwrap().hash, "7656203794231641961");
+        assert!(Manifest::parse(&manifest_json(json!("-1"))).is_err());
+    }
+
+    #[test]
+    fn test_param_witnesses() {
+        let manifest = Manifest::parse(&manifest_json(json!("1"))).unwrap();
+        let witnesses = manifest.param_witnesses().unwrap();
+        assert_eq!(witnesses["point"], vec![WitnessRange { start: 0, end: 2 }]);
+        assert_eq!(witnesses["names"], vec![WitnessRange { start: 2, end: 14 }]);
+        assert_eq!(witnesses["result"], vec![WitnessRange { start: 14, end: 15 }]);
+        assert!(manifest.has_debug_symbols());
+    }
+
+    #[test]
+    fn test_declared_param_witnesses() {
+        let mut manifest: Value = serde_json::from_str(&manifest_json(json!("1"))).unwrap();
+        manifest["abi"]["param_witnesses"] = json!({
+            "point": [{ "start": 4, "end": 5 }, { "start": 0, "end": 1 }],
+            "names": [{ "start": 5, "end": 17 }],
+            "result": [{ "start": 1, "end": 2 }]
+        });
+        manifest["abi"]["return_witnesses"] = json!([17, 18]);
+        let manifest = Manifest::parse(&manifest.to_string()).unwrap();
+        let witnesses = manifest.param_witnesses().unwrap();
+        assert_eq!(witnesses["point"], vec![WitnessRange { start: 4, end: 5 }, WitnessRange { start: 0, end: 1 }]);
+        assert_eq!(witnesses["result"], vec![WitnessRange { start: 1, end: 2 }]);
+
+        let view = serde_json::to_value(manifest.view().unwrap()).unwrap();
+        assert_eq!(view["abi"]["paramWitnesses"]["names"], json!([{ "start": 5, "end": 17 }]));
+        assert_eq!(view["abi"]["returnWitnesses"], json!([17, 18]));
+        assert!(view["abi"].get("param_witnesses").is_none());
+    }
+
+    #[test]
+    fn test_empty_debug_symbols_are_absent() {
+        let mut manifest: Value = serde_json::from_str(&manifest_json(json!("1"))).unwrap();
+        manifest["debug_symbols"] = json!("");
+        assert!(!Manifest::parse(&manifest.to_string()).unwrap().has_debug_symbols());
+        manifest["debug_symbols"] = Value::Null;
+        assert!(!Manifest::parse(&manifest.to_string()).unwrap().has_debug_symbols());
+        manifest.as_object_mut().unwrap().remove("debug_symbols");
+        assert!(!Manifest::parse(&manifest.to_string()).unwrap().has_debug_symbols());
+    }
+
+    #[test]
+    fn test_view() {
+        let manifest = Manifest::parse(&manifest_json(json!("1"))).unwrap();
+        let view = serde_json::to_value(manifest.view().unwrap()).unwrap();
+        assert_eq!(view["noirVersion"], manifest.noir_version.as_deref().unwrap());
+        assert_eq!(view["hash"], "1");
+        assert_eq!(view["abi"]["parameters"][1]["type"]["type"]["length"], 4);
+        assert_eq!(view["abi"]["returnType"]["abiType"]["elements"], json!([{ "kind": "field" }, { "kind": "field" }]));
+        assert_eq!(view["abi"]["errorTypes"]["13154468461282470596"], json!({ "errorKind": "custom", "type": { "kind": "field" } }));
+        assert_eq!(view["abi"]["paramWitnesses"]["names"], json!([{ "start": 2, "end": 14 }]));
+        assert_eq!(view["abi"]["returnWitnesses"], json!([]));
+    }
+
+    #[test]
+    fn test_field_count_overflow() {
+        let mut manifest: Value = serde_json::from_str(&manifest_json(json!("1"))).unwrap();
+        manifest["abi"]["parameters"][1]["type"]["length"] = json!(u32::MAX);
+        let manifest = Manifest::parse(&manifest.to_string()).unwrap();
+        assert_eq!(manifest.param_witnesses().unwrap_err(), "Invalid parameter names: Array of length 4294967295 has too many fields");
+        assert!(manifest.view().is_err());
+    }
+}
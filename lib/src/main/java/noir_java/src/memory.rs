@@ -0,0 +1,193 @@
+use serde::Serialize;
+use std::fmt;
+use std::sync::RwLock;
+
+// Extra storage allowed over what the estimate says is needed, as the
+// polynomials moved to disk aren't chosen to fit the cap exactly
+const STORAGE_HEADROOM_PERCENT: u64 = 25;
+const MIB: u64 = 1024 * 1024;
+
+/// Memory a prover uses per gate of the (dyadic) circuit size, which
+/// `estimate` scales with the size of the circuit
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MemoryModel {
+    /// Peak memory with low memory mode disabled
+    pub bytes_per_gate: u64,
+    /// Part of it that stays in memory in low memory mode whatever the storage
+    /// cap: the commitment key, the execution trace and the sumcheck working
+    /// buffers. The rest are polynomials barretenberg can back with files.
+    pub resident_bytes_per_gate: u64,
+}
+
+// These are derived rather than measured. An UltraHonk proving key holds 41
+// polynomials of 32 byte coefficients, about 1.3 KiB per gate, and the prover
+// roughly triples that with the copies it makes while committing and during
+// sumcheck; about a quarter can't be moved to storage. Both flavors commit to
+// the same polynomials, only the transcript hash differs, so they start with
+// the same model. Set measured values with `set_memory_model`.
+const DEFAULT_MODEL: MemoryModel = MemoryModel { bytes_per_gate: 4096, resident_bytes_per_gate: 1024 };
+const PROOF_TYPES: [&str; 2] = ["ultra_honk", "ultra_honk_keccak"];
+static MODELS: RwLock<[MemoryModel; 2]> = RwLock::new([DEFAULT_MODEL; 2]);
+
+fn model_index(proof_type: &str) -> Result<usize, String> {
+    PROOF_TYPES
+        .iter()
+        .position(|&known| known == proof_type)
+        .ok_or_else(|| format!("Unsupported proof type: {}", proof_type))
+}
+
+/// Model used to estimate the memory of proofs of `proof_type`
+pub fn memory_model(proof_type: &str) -> Result<MemoryModel, String> {
+    Ok(MODELS.read().unwrap()[model_index(proof_type)?])
+}
+
+/// Replaces the model of `proof_type`, e.g. with figures measured on the
+/// target devices from the peak RSS of the metrics
+pub fn set_memory_model(proof_type: &str, model: MemoryModel) -> Result<(), String> {
+    let index = model_index(proof_type)?;
+    if model.bytes_per_gate == 0 || model.resident_bytes_per_gate > model.bytes_per_gate {
+        return Err(format!(
+            "Invalid memory model: {} bytes per gate, {} of them resident in low memory mode",
+            model.bytes_per_gate, model.resident_bytes_per_gate
+        ));
+    }
+    MODELS.write().unwrap()[index] = model;
+    Ok(())
+}
+
+/// Predicted peak memory of proving (or computing the verification key of) a circuit
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MemoryEstimate {
+    pub circuit_size: u32,
+    /// Peak memory with low memory mode disabled
+    pub peak_bytes: u64,
+    /// Peak memory with low memory mode and an unlimited storage cap
+    pub low_memory_peak_bytes: u64,
+    /// Storage used with low memory mode and an unlimited storage cap
+    pub low_memory_storage_bytes: u64,
+}
+
+impl MemoryEstimate {
+    /// Peak memory with low memory mode and the given storage cap
+    pub fn low_memory_peak_with_cap(&self, storage_cap: u64) -> u64 {
+        self.low_memory_peak_bytes + self.low_memory_storage_bytes.saturating_sub(storage_cap)
+    }
+}
+
+impl fmt::Display for MemoryEstimate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "proving a circuit of size {} needs about {} MiB of memory, or {} MiB in low memory mode with {} MiB of storage",
+            self.circuit_size,
+            self.peak_bytes.div_ceil(MIB),
+            self.low_memory_peak_bytes.div_ceil(MIB),
+            self.low_memory_storage_bytes.div_ceil(MIB)
+        )
+    }
+}
+
+/// Estimates the peak memory of proving a circuit of `circuit_size` gates
+/// (rounded up to a power of two) with the model of the given proof type
+pub fn estimate(circuit_size: u32, proof_type: &str) -> Result<MemoryEstimate, String> {
+    let model = memory_model(proof_type)?;
+    let size = circuit_size.max(1).next_power_of_two() as u64;
+    Ok(MemoryEstimate {
+        circuit_size: size as u32,
+        peak_bytes: size * model.bytes_per_gate,
+        low_memory_peak_bytes: size * model.resident_bytes_per_gate,
+        low_memory_storage_bytes: size * (model.bytes_per_gate - model.resident_bytes_per_gate),
+    })
+}
+
+/// Memory settings picked for a memory budget, as returned by `select_memory_mode`
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MemoryMode {
+    pub low_memory_mode: bool,
+    pub storage_cap: u64,
+    pub estimate: MemoryEstimate,
+}
+
+/// Picks the fastest settings whose estimated peak fits in `memory_budget`:
+/// low memory mode is only enabled when needed, with just enough storage (plus
+/// some headroom) to fit, never more than `max_storage` if it isn't 0
+pub fn select_mode(estimate: MemoryEstimate, memory_budget: u64, max_storage: u64) -> Result<MemoryMode, String> {
+    if estimate.peak_bytes <= memory_budget {
+        return Ok(MemoryMode { low_memory_mode: false, storage_cap: 0, estimate });
+    }
+    if estimate.low_memory_peak_bytes > memory_budget {
+        return Err(format!(
+            "Not enough memory: {}, but the budget is {} MiB",
+            estimate,
+            memory_budget / MIB
+        ));
+    }
+
+    let needed = estimate.peak_bytes - memory_budget;
+    if max_storage > 0 && needed > max_storage {
+        return Err(format!(
+            "Not enough storage: {}, but the budget is {} MiB of memory and {} MiB of storage",
+            estimate,
+            memory_budget / MIB,
+            max_storage / MIB
+        ));
+    }
+    let with_headroom = (needed + needed * STORAGE_HEADROOM_PERCENT / 100).div_ceil(MIB) * MIB;
+    let mut storage_cap = with_headroom.min(estimate.low_memory_storage_bytes.div_ceil(MIB) * MIB);
+    if max_storage > 0 {
+        storage_cap = storage_cap.min(max_storage);
+    }
+    Ok(MemoryMode { low_memory_mode: true, storage_cap, estimate })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_estimate_rounds_to_power_of_two() {
+        let estimate = estimate(3000, "ultra_honk").unwrap();
+        assert_eq!(estimate.circuit_size, 4096);
+        assert_eq!(estimate.peak_bytes, 4096 * DEFAULT_MODEL.bytes_per_gate);
+        assert_eq!(estimate.low_memory_peak_with_cap(0), estimate.peak_bytes);
+        assert!(super::estimate(3000, "plonk").is_err());
+    }
+
+    #[test]
+    fn test_select_mode() {
+        // 16 MiB in memory, 4 MiB resident in low memory mode
+        let estimate = super::estimate(1 << 12, "ultra_honk").unwrap();
+
+        let mode = select_mode(estimate.clone(), 32 * MIB, 0).unwrap();
+        assert!(!mode.low_memory_mode);
+
+        let mode = select_mode(estimate.clone(), 8 * MIB, 0).unwrap();
+        assert!(mode.low_memory_mode);
+        assert_eq!(mode.storage_cap, 10 * MIB);
+        assert!(estimate.low_memory_peak_with_cap(mode.storage_cap) <= 8 * MIB);
+
+        // Enough storage for the estimate but not for the headroom
+        assert_eq!(select_mode(estimate.clone(), 8 * MIB, 9 * MIB).unwrap().storage_cap, 9 * MIB);
+
+        let e = select_mode(estimate.clone(), 8 * MIB, 4 * MIB).unwrap_err();
+        assert!(e.contains("Not enough storage"));
+        let e = select_mode(estimate, 2 * MIB, 0).unwrap_err();
+        assert!(e.contains("16 MiB of memory, or 4 MiB in low memory mode"), "{}", e);
+    }
+
+    #[test]
+    fn test_models_are_per_proof_type() {
+        let model = MemoryModel { bytes_per_gate: 2048, resident_bytes_per_gate: 512 };
+        set_memory_model("ultra_honk_keccak", model).unwrap();
+        let keccak = estimate(1024, "ultra_honk_keccak").unwrap();
+        assert_eq!((keccak.peak_bytes, keccak.low_memory_peak_bytes), (2 * MIB, MIB / 2));
+        assert_eq!(estimate(1024, "ultra_honk").unwrap().peak_bytes, 4 * MIB);
+        set_memory_model("ultra_honk_keccak", DEFAULT_MODEL).unwrap();
+
+        let too_resident = MemoryModel { bytes_per_gate: 1024, resident_bytes_per_gate: 2048 };
+        assert!(set_memory_model("ultra_honk", too_resident).is_err());
+        assert!(set_memory_model("plonk", model).is_err());
+    }
+}
@@ -0,0 +1,291 @@
+use log::debug;
+use noir_rs::barretenberg::utils::get_circuit_size;
+use serde::Serialize;
+use std::cell::RefCell;
+use std::fs;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::time::Instant;
+use tracing::field::Empty;
+use tracing::span::EnteredSpan;
+
+thread_local! {
+    // Metrics of the calls made on this thread since collection was enabled,
+    // None while it is disabled
+    static COLLECTED: RefCell<Option<Vec<CallMetrics>>> = const { RefCell::new(None) };
+}
+
+// The peak RSS is reset for the whole process, so it is only done on request
+static RESET_PEAK_RSS: AtomicBool = AtomicBool::new(false);
+// Number of resets so far, to tell whether another call reset the peak during a call
+static PEAK_RSS_RESETS: AtomicU64 = AtomicU64::new(0);
+
+/// Sets whether measured calls reset the peak RSS of the process when they
+/// start. Disabled by default, as a reset also moves the peak of the calls in
+/// progress on other threads, whose peaks are then left out.
+pub fn set_peak_rss_reset(enabled: bool) {
+    RESET_PEAK_RSS.store(enabled, Ordering::SeqCst);
+}
+
+/// Enables or disables the collection of metrics for the calls made on this
+/// thread, returning whether it was enabled. Disabling it drops the metrics
+/// that weren't taken.
+pub fn set_collecting(enabled: bool) -> bool {
+    COLLECTED.with(|collected| {
+        let mut collected = collected.borrow_mut();
+        let was_enabled = collected.is_some();
+        match (enabled, was_enabled) {
+            (true, false) => *collected = Some(Vec::new()),
+            (false, true) => *collected = None,
+            _ => {}
+        }
+        was_enabled
+    })
+}
+
+/// Returns the metrics collected on this thread so far, oldest first
+pub fn take_collected() -> Vec<CallMetrics> {
+    COLLECTED.with(|collected| collected.borrow_mut().as_mut().map(std::mem::take).unwrap_or_default())
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PhaseTiming {
+    pub name: &'static str,
+    pub nanos: u64,
+}
+
+/// What one prove, verification key or execute call cost
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CallMetrics {
+    pub operation: &'static str,
+    pub succeeded: bool,
+    pub total_nanos: u64,
+    /// Wall time of each phase of the call, in order
+    pub phases: Vec<PhaseTiming>,
+    /// Peak resident set size of the process (VmHWM). None if another call
+    /// reset the peak while this one ran, as it would miss part of the call.
+    pub peak_rss_bytes: Option<u64>,
+    /// Whether the peak was reset at the start of the call. If resets are
+    /// disabled (see `set_peak_rss_reset`) or the kernel refused,
+    /// `peak_rss_bytes` is the peak since the process started or the last reset.
+    pub peak_rss_reset: bool,
+    /// Bytes the process wrote to storage during the call, e.g. the polynomials
+    /// barretenberg moves to disk in low memory mode
+    pub disk_bytes_written: Option<u64>,
+    /// SRS points needed by the circuit, for calls that use the SRS
+    pub srs_points: Option<u32>,
+}
+
+/// Measures a call while collection is enabled on this thread, doing nothing
+/// otherwise. The metrics are collected when the recorder is dropped, so a
+/// call that fails early is recorded as failed.
+///
+/// The call and each of its phases are also traced as spans, named after the
+/// operation and the phases. A phase that fails is traced as `failed`.
+pub struct MetricsRecorder {
+    metrics: Option<CallMetrics>,
+    started: Instant,
+    phase_started: Instant,
+    write_bytes_before: Option<u64>,
+    peak_rss_resets: u64,
+    finished: bool,
+    // Named when it ends, as that's when `phase` gets the name
+    phase_span: Option<EnteredSpan>,
+    call_span: Option<EnteredSpan>,
+}
+
+impl MetricsRecorder {
+    pub fn start(operation: &'static str) -> Self {
+        let collecting = COLLECTED.with(|collected| collected.borrow().is_some());
+        let mut peak_rss_resets = PEAK_RSS_RESETS.load(Ordering::SeqCst);
+        let metrics = collecting.then(|| CallMetrics {
+            operation,
+            succeeded: false,
+            total_nanos: 0,
+            phases: Vec::new(),
+            peak_rss_bytes: None,
+            peak_rss_reset: RESET_PEAK_RSS.load(Ordering::SeqCst) && reset_peak_rss(),
+            disk_bytes_written: None,
+            srs_points: None,
+        });
+        if metrics.as_ref().is_some_and(|metrics| metrics.peak_rss_reset) {
+            peak_rss_resets += 1;
+        }
+        let write_bytes_before = metrics.as_ref().and_then(|_| read_write_bytes());
+        let call_span = tracing::info_span!("call", name = operation, succeeded = Empty).entered();
+        let now = Instant::now();
+        MetricsRecorder {
+            metrics,
+            started: now,
+            phase_started: now,
+            write_bytes_before,
+            peak_rss_resets,
+            finished: false,
+            phase_span: Some(phase_span()),
+            call_span: Some(call_span),
+        }
+    }
+
+    /// Ends the current phase, which started with the call or with the end of
+    /// the previous phase
+    pub fn phase(&mut self, name: &'static str) {
+        if let Some(span) = self.phase_span.take() {
+            span.record("name", name);
+        }
+        if let Some(metrics) = self.metrics.as_mut() {
+            let nanos = self.phase_started.elapsed().as_nanos() as u64;
+            metrics.phases.push(PhaseTiming { name, nanos });
+            self.phase_started = Instant::now();
+        }
+        self.phase_span = Some(phase_span());
+    }
+
+    /// Marks the call as successful and takes the measurements, so that what
+    /// follows (e.g. `srs_points`) isn't counted
+    pub fn finish(&mut self) {
+        self.measure(true);
+    }
+
+    /// Records the SRS points needed by the circuit. Counting them builds the
+    /// circuit in barretenberg, so this is only done while collecting.
+    pub fn srs_points(&mut self, circuit_bytecode: &str) {
+        if let Some(metrics) = self.metrics.as_mut() {
+            metrics.srs_points = Some(get_circuit_size(circuit_bytecode, false).max(1).next_power_of_two());
+        }
+    }
+
+    fn measure(&mut self, succeeded: bool) {
+        if self.finished {
+            return;
+        }
+        self.finished = true;
+        // What follows the last phase of a successful call isn't a phase
+        if let Some(span) = self.phase_span.take().filter(|_| !succeeded) {
+            span.record("name", "failed");
+        }
+        if let Some(span) = self.call_span.as_ref() {
+            span.record("succeeded", succeeded);
+        }
+        if let Some(metrics) = self.metrics.as_mut() {
+            metrics.succeeded = succeeded;
+            metrics.total_nanos = self.started.elapsed().as_nanos() as u64;
+            let reset_by_others = PEAK_RSS_RESETS.load(Ordering::SeqCst) != self.peak_rss_resets;
+            metrics.peak_rss_bytes = match reset_by_others {
+                true => {
+                    debug!("Peak RSS of {} left out, another call reset it", metrics.operation);
+                    None
+                }
+                false => read_status_kb("VmHWM").map(|kb| kb * 1024),
+            };
+            metrics.disk_bytes_written = self
+                .write_bytes_before
+                .zip(read_write_bytes())
+                .map(|(before, after)| after.saturating_sub(before));
+        }
+    }
+}
+
+impl Drop for MetricsRecorder {
+    fn drop(&mut self) {
+        self.measure(false);
+        // Closes the call span after its phases
+        self.call_span.take();
+        if let Some(metrics) = self.metrics.take() {
+            debug!("{:?}", metrics);
+            COLLECTED.with(|collected| {
+                if let Some(collected) = collected.borrow_mut().as_mut() {
+                    collected.push(metrics);
+                }
+            });
+        }
+    }
+}
+
+// Writing 5 to clear_refs resets VmHWM to the current RSS
+fn reset_peak_rss() -> bool {
+    let reset = fs::write("/proc/self/clear_refs", "5").is_ok();
+    if reset {
+        PEAK_RSS_RESETS.fetch_add(1, Ordering::SeqCst);
+    }
+    reset
+}
+
+fn phase_span() -> EnteredSpan {
+    tracing::info_span!("phase", name = Empty).entered()
+}
+
+pub fn read_status_kb(field: &str) -> Option<u64> {
+    parse_field(&fs::read_to_string("/proc/self/status").ok()?, field)?
+        .trim_end_matches("kB")
+        .trim()
+        .parse()
+        .ok()
+}
+
+// Counted by the kernel for the whole process, so writes made by other threads
+// during the call are included
+fn read_write_bytes() -> Option<u64> {
+    parse_field(&fs::read_to_string("/proc/self/io").ok()?, "write_bytes")?.parse().ok()
+}
+
+// Value of a `name: value` line of a /proc file
+fn parse_field<'a>(contents: &'a str, field: &str) -> Option<&'a str> {
+    contents.lines().find_map(|line| {
+        let (name, value) = line.split_once(':')?;
+        (name == field).then(|| value.trim())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_field() {
+        let status = "Name:\tnoir\nVmPeak:\t  2048 kB\nVmHWM:\t   1024 kB\n";
+        assert_eq!(parse_field(status, "VmHWM"), Some("1024 kB"));
+        assert_eq!(parse_field(status, "VmRSS"), None);
+        assert_eq!(parse_field("rchar: 10\nwrite_bytes: 4096\n", "write_bytes"), Some("4096"));
+    }
+
+    #[test]
+    fn test_only_collects_when_enabled() {
+        {
+            let mut recorder = MetricsRecorder::start("execute");
+            recorder.phase("decode");
+            recorder.finish();
+        }
+        assert!(!set_collecting(true));
+        {
+            let mut recorder = MetricsRecorder::start("execute");
+            recorder.phase("decode");
+            recorder.phase("execute");
+            recorder.finish();
+        }
+        // Dropped without finishing, as when a call fails
+        drop(MetricsRecorder::start("prove"));
+
+        let collected = take_collected();
+        assert_eq!(collected.len(), 2);
+        assert!(collected[0].succeeded);
+        assert_eq!(collected[0].phases.iter().map(|p| p.name).collect::<Vec<_>>(), vec!["decode", "execute"]);
+        assert!(!collected[1].succeeded);
+        assert!(take_collected().is_empty());
+        assert!(set_collecting(false));
+    }
+
+    #[test]
+    fn test_peak_rss_left_out_after_another_reset() {
+        set_collecting(true);
+        let mut recorder = MetricsRecorder::start("prove");
+        // As if a call on another thread had reset the peak
+        PEAK_RSS_RESETS.fetch_add(1, Ordering::SeqCst);
+        recorder.finish();
+        drop(recorder);
+        let collected = take_collected();
+        set_collecting(false);
+        assert!(!collected[0].peak_rss_reset);
+        assert_eq!(collected[0].peak_rss_bytes, None);
+    }
+}
@@ -1,3 +1,4 @@
+use acvm::{acir::brillig::ForeignCallResult, pwg::ForeignCallWaitInfo};
 use noir_rs::{
     native_types::{Witness, WitnessMap},
     FieldElement,
@@ -5,12 +6,23 @@ use noir_rs::{
     execute::execute,
     barretenberg::{
         prove::prove_ultra_honk,
-        verify::verify_ultra_honk,
-        srs::setup_srs,
+        verify::{verify_ultra_honk, get_ultra_honk_verification_key},
+        srs::setup_srs_from_bytecode,
     },
 };
-use std::collections::HashMap;
-use crate::test_utils::{create_test_witness_map, TEST_CIRCUIT_BYTECODE, convert_to_witness_map};
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+use crate::test_utils::{assert_equal_bytecode, brillig_loop_bytecode, create_test_witness_map, fold_program_bytecode, oracle_call_bytecode, TEST_CIRCUIT_BYTECODE, convert_to_witness_map};
+use crate::circuit::{circuit_info, ensure_single_function};
+use crate::executor::{execute_program, function_witnesses, last_failed_witness, ExecutionError, ExecutionLimits};
+use crate::foreign_calls::{ForeignCallResolver, NoForeignCalls};
+use crate::batch::{prove_each, prove_from_witness_stack, BatchOptions};
+use crate::scratch::set_scratch_dir;
+use crate::secret::SecretWitnessMap;
+use crate::witness::{deserialize_witness_stack, read_witness_file, serialize_witness_stack, write_witness_file};
 
 #[test]
 fn test_execute_circuit() {
@@ -48,12 +60,12 @@ fn test_prove_verify_circuit() {
     let witness_map = convert_to_witness_map(input_map);
     
     // Set up SRS
-    let _num_points = setup_srs(TEST_CIRCUIT_BYTECODE, None).expect("Failed to setup SRS");
+    let _num_points = setup_srs_from_bytecode(TEST_CIRCUIT_BYTECODE, None, false).expect("Failed to setup SRS");
 
     let vk = get_ultra_honk_verification_key(TEST_CIRCUIT_BYTECODE, false, Some(0)).expect("Failed to get verification key");
     
     // Generate a proof
-    let proof = prove_ultra_honk(TEST_CIRCUIT_BYTECODE, witness_map, vk, false, Some(0))
+    let proof = prove_ultra_honk(TEST_CIRCUIT_BYTECODE, witness_map, vk.clone(), false, Some(0))
         .expect("Proof generation failed");
     
     // Verify the proof
@@ -78,4 +90,244 @@ fn test_witness_operations() {
         witness_map.get(&Witness(1)).unwrap().to_hex(),
         "0000000000000000000000000000000000000000000000000000000000000004"
     );
-} 
\ No newline at end of file
+}
+
+#[test]
+fn test_circuit_info() {
+    let info = circuit_info(TEST_CIRCUIT_BYTECODE).expect("Failed to get circuit info");
+
+    // x * y == z, with z returned: one arithmetic opcode and one public input
+    assert_eq!(info.gate_count, 57);
+    assert_eq!(info.circuit_size, 64);
+    assert_eq!(info.public_inputs, 1);
+    assert_eq!(info.opcode_counts, BTreeMap::from([("AssertZero".to_string(), 1)]));
+    assert!(info.black_box_functions.is_empty());
+    assert_eq!(info.brillig_functions, 0);
+    assert_eq!(info.acir_functions, vec!["main".to_string()]);
+}
+
+#[test]
+fn test_prove_batch() {
+    let input = |a: &str, b: &str| HashMap::from([("0".to_string(), a.to_string()), ("1".to_string(), b.to_string())]);
+    let witnesses = vec![
+        Ok(SecretWitnessMap::new(convert_to_witness_map(input("0x3", "0x4")))),
+        Ok(SecretWitnessMap::new(convert_to_witness_map(input("0x2", "0x5")))),
+        Err("Failed to parse witness key 'a'".to_string()),
+        Ok(SecretWitnessMap::new(convert_to_witness_map(input("0x6", "0x7")))),
+        Ok(SecretWitnessMap::new(convert_to_witness_map(input("0x8", "0x9")))),
+    ];
+
+    setup_srs_from_bytecode(TEST_CIRCUIT_BYTECODE, None, false).expect("Failed to setup SRS");
+    let vk = get_ultra_honk_verification_key(TEST_CIRCUIT_BYTECODE, false, Some(0)).expect("Failed to get verification key");
+
+    // Four proofs generated at the same time against the same SRS
+    let options = BatchOptions { max_parallel: 4, memory_budget: 0, circuit_size: 0 };
+    let results = prove_each("ultra_honk", TEST_CIRCUIT_BYTECODE, witnesses, vk.clone(), false, 0, &options);
+
+    assert_eq!(results.len(), 5);
+    for index in [0, 1, 3, 4] {
+        let proof = results[index].clone().expect("Proof generation failed");
+        assert!(verify_ultra_honk(proof, vk.clone()).expect("Proof verification failed"));
+    }
+    // A bad item doesn't fail the rest of the batch
+    assert_eq!(results[2], Err("Failed to parse witness key 'a'".to_string()));
+}
+
+#[test]
+fn test_prove_from_serialized_witness() {
+    let mut input_map = HashMap::new();
+    input_map.insert("0".to_string(), "0x3".to_string());
+    input_map.insert("1".to_string(), "0x4".to_string());
+
+    let solved_witness = execute(TEST_CIRCUIT_BYTECODE, convert_to_witness_map(input_map)).expect("Circuit execution failed");
+    let serialized = serialize_witness_stack(&solved_witness).expect("Failed to serialize witness stack");
+    let deserialized = deserialize_witness_stack(&serialized).expect("Failed to deserialize witness stack");
+    assert_eq!(*deserialized, solved_witness);
+
+    // Proven from the stored witness only, as by Circuit.proveFromWitness
+    setup_srs_from_bytecode(TEST_CIRCUIT_BYTECODE, None, false).expect("Failed to setup SRS");
+    let vk = get_ultra_honk_verification_key(TEST_CIRCUIT_BYTECODE, false, Some(0)).expect("Failed to get verification key");
+    let proof = prove_from_witness_stack("ultra_honk", TEST_CIRCUIT_BYTECODE, &serialized, vk.clone(), false, 0)
+        .expect("Proof generation failed");
+    assert!(verify_ultra_honk(proof, vk).expect("Proof verification failed"));
+
+    assert!(prove_from_witness_stack("ultra_honk", TEST_CIRCUIT_BYTECODE, &serialized[1..], vec![], false, 0).is_err(), "A corrupted witness stack should be rejected");
+}
+
+#[test]
+fn test_witness_file_round_trip() {
+    let mut input_map = HashMap::new();
+    input_map.insert("0".to_string(), "0x3".to_string());
+    input_map.insert("1".to_string(), "0x4".to_string());
+
+    let solved_witness = execute(TEST_CIRCUIT_BYTECODE, convert_to_witness_map(input_map)).expect("Circuit execution failed");
+    let path = std::env::temp_dir().join(format!("noir_java_witness_{}.gz", std::process::id()));
+    let path = path.to_str().unwrap();
+    write_witness_file(path, &solved_witness).expect("Failed to write witness file");
+    assert_eq!(*read_witness_file(path).expect("Failed to read witness file"), solved_witness);
+    std::fs::remove_file(path).unwrap();
+
+    assert!(read_witness_file(path).is_err(), "Reading a missing file should fail");
+}
+
+#[test]
+fn test_execute_program_witness_stack() {
+    let mut input_map = HashMap::new();
+    input_map.insert("0".to_string(), "0x3".to_string());
+    input_map.insert("1".to_string(), "0x4".to_string());
+
+    let witness_stack = execute_program(TEST_CIRCUIT_BYTECODE, convert_to_witness_map(input_map), &mut NoForeignCalls, Default::default()).expect("Circuit execution failed");
+    let program = crate::circuit::decode_program(TEST_CIRCUIT_BYTECODE).unwrap();
+    let witnesses = function_witnesses(&program, &witness_stack).unwrap();
+
+    // A single function program has the witness of main only
+    assert_eq!(witnesses.len(), 1);
+    assert_eq!(witnesses[0].function, 0);
+    assert_eq!(witnesses[0].values.last().unwrap(), &format!("0x{:0>64}", "c"));
+    assert!(ensure_single_function(TEST_CIRCUIT_BYTECODE).is_ok());
+}
+
+#[test]
+fn test_execute_multi_function_program() {
+    let bytecode = fold_program_bytecode();
+    let mut input_map = HashMap::new();
+    input_map.insert("0".to_string(), "0x3".to_string());
+    input_map.insert("1".to_string(), "0x4".to_string());
+
+    let witness_stack = execute_program(&bytecode, convert_to_witness_map(input_map), &mut NoForeignCalls, Default::default()).expect("Program execution failed");
+    let program = crate::circuit::decode_program(&bytecode).unwrap();
+    let witnesses = function_witnesses(&program, &witness_stack).unwrap();
+
+    // The call to add is solved first, main comes last
+    assert_eq!(witnesses.len(), 2);
+    assert_eq!((witnesses[0].function, witnesses[0].name.as_str()), (1, "add"));
+    assert_eq!((witnesses[1].function, witnesses[1].name.as_str()), (0, "main"));
+    assert_eq!(witnesses[0].values.last().unwrap(), &format!("0x{:0>64}", "7"));
+    assert_eq!(witnesses[1].indices, vec![0, 1, 2]);
+    assert_eq!(witnesses[1].values.last().unwrap(), &format!("0x{:0>64}", "7"));
+}
+
+#[test]
+fn test_multi_function_program_is_not_proven() {
+    let bytecode = fold_program_bytecode();
+    let error = ensure_single_function(&bytecode).expect_err("A program with a #[fold] function can't be proven");
+    assert!(error.starts_with("Program has 2 ACIR functions (main, add)"), "{}", error);
+    assert_eq!(circuit_info(&bytecode).unwrap().acir_functions, vec!["main", "add"]);
+}
+
+#[test]
+fn test_brillig_loop_runs_within_limits() {
+    let limits = ExecutionLimits { max_brillig_steps: Some(10_000), timeout: Some(Duration::from_secs(10)) };
+    let witness_stack = execute_program(&brillig_loop_bytecode(Some(100)), WitnessMap::new(), &mut NoForeignCalls, limits)
+        .expect("A bounded loop should run to the end");
+    assert_eq!(witness_stack.length(), 1);
+}
+
+#[test]
+fn test_brillig_step_budget_exceeded() {
+    let limits = ExecutionLimits { max_brillig_steps: Some(1_000), timeout: None };
+    // Counting to 1000 takes more than 1000 steps too
+    for iterations in [None, Some(1_000)] {
+        match execute_program(&brillig_loop_bytecode(iterations), WitnessMap::new(), &mut NoForeignCalls, limits).map(|_| ()) {
+            Err(ExecutionError::StepBudgetExceeded { max_steps, location }) => {
+                assert_eq!(max_steps, 1_000);
+                assert_eq!(location.acir_function, "main");
+                assert_eq!(location.acir_index, 0);
+                assert_eq!(location.brillig.map(|(name, _)| name).as_deref(), Some("spin"));
+            }
+            other => panic!("Expected the step budget to be exceeded, got {:?}", other),
+        }
+    }
+}
+
+#[test]
+fn test_brillig_loop_times_out() {
+    let limits = ExecutionLimits { max_brillig_steps: None, timeout: Some(Duration::from_millis(50)) };
+    match execute_program(&brillig_loop_bytecode(None), WitnessMap::new(), &mut NoForeignCalls, limits).map(|_| ()) {
+        Err(ExecutionError::TimedOut { timeout, location }) => {
+            assert_eq!(timeout, Duration::from_millis(50));
+            assert_eq!(location.brillig.map(|(name, _)| name).as_deref(), Some("spin"));
+        }
+        other => panic!("Expected the execution to time out, got {:?}", other),
+    }
+}
+
+// Answers every foreign call after a delay, as a handler blocked on I/O would
+struct SlowForeignCalls(Duration);
+
+impl ForeignCallResolver for SlowForeignCalls {
+    fn resolve(&mut self, _call: &ForeignCallWaitInfo<FieldElement>) -> Result<ForeignCallResult<FieldElement>, String> {
+        std::thread::sleep(self.0);
+        Ok(ForeignCallResult { values: vec![] })
+    }
+}
+
+#[test]
+fn test_slow_foreign_call_times_out() {
+    let limits = ExecutionLimits { max_brillig_steps: None, timeout: Some(Duration::from_millis(50)) };
+    // A single call, so the deadline can only be caught right after it returns
+    let mut resolver = SlowForeignCalls(Duration::from_millis(200));
+    match execute_program(&oracle_call_bytecode("wait"), WitnessMap::new(), &mut resolver, limits).map(|_| ()) {
+        Err(ExecutionError::TimedOut { timeout, location }) => {
+            assert_eq!(timeout, Duration::from_millis(50));
+            assert_eq!(location.brillig.map(|(name, _)| name).as_deref(), Some("ask"));
+        }
+        other => panic!("Expected the execution to time out, got {:?}", other),
+    }
+
+    let mut resolver = SlowForeignCalls(Duration::ZERO);
+    execute_program(&oracle_call_bytecode("wait"), WitnessMap::new(), &mut resolver, limits)
+        .expect("A foreign call answered in time shouldn't time out");
+}
+
+#[test]
+fn test_failed_execution_wipes_the_witness() {
+    let mut input_map = HashMap::new();
+    input_map.insert("0".to_string(), "0x3".to_string());
+    input_map.insert("1".to_string(), "0x4".to_string());
+
+    execute_program(&assert_equal_bytecode(), convert_to_witness_map(input_map), &mut NoForeignCalls, Default::default())
+        .map(|_| ())
+        .expect_err("3 and 4 aren't equal");
+    // The inputs were in the ACVM when the assertion failed
+    let witness = last_failed_witness().expect("The witness of the failed execution wasn't wiped");
+    assert_eq!(witness.get(&Witness(0)), Some(&FieldElement::zero()));
+    assert_eq!(witness.get(&Witness(1)), Some(&FieldElement::zero()));
+}
+
+// The only test setting the scratch directory, as it changes TMPDIR for the
+// whole process
+#[test]
+fn test_low_memory_prove_uses_scratch_dir() {
+    let parent = std::env::temp_dir().join(format!("noir_java_scratch_prove_{}", std::process::id()));
+    let dir = PathBuf::from(set_scratch_dir(&parent).expect("Failed to set the scratch directory").path);
+
+    let mut input_map = HashMap::new();
+    input_map.insert("0".to_string(), "0x3".to_string());
+    input_map.insert("1".to_string(), "0x4".to_string());
+    setup_srs_from_bytecode(TEST_CIRCUIT_BYTECODE, None, false).expect("Failed to setup SRS");
+    let vk = get_ultra_honk_verification_key(TEST_CIRCUIT_BYTECODE, false, Some(0)).expect("Failed to get verification key");
+
+    // Barretenberg removes its files when the proof is done, so the directory
+    // is watched while proving. The other tests now write their files there
+    // too, under the noir_java_ prefix
+    let proving = AtomicBool::new(true);
+    let used = std::thread::scope(|scope| {
+        let watcher = scope.spawn(|| {
+            let mut used = false;
+            while !used && proving.load(Ordering::Relaxed) {
+                used = fs::read_dir(&dir).is_ok_and(|entries| {
+                    entries.flatten().any(|entry| !entry.file_name().to_string_lossy().starts_with("noir_java_"))
+                });
+                std::thread::yield_now();
+            }
+            used
+        });
+        let proof = prove_ultra_honk(TEST_CIRCUIT_BYTECODE, convert_to_witness_map(input_map), vk.clone(), true, Some(1 << 30));
+        proving.store(false, Ordering::Relaxed);
+        assert!(verify_ultra_honk(proof.expect("Proof generation failed"), vk).expect("Proof verification failed"));
+        watcher.join().unwrap()
+    });
+    assert!(used, "Barretenberg didn't write to the scratch directory in low memory mode");
+    let _ = fs::remove_dir_all(&parent);
+}
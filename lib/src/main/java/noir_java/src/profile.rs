@@ -0,0 +1,140 @@
+use acvm::{
+    acir::circuit::{Opcode, Program},
+    FieldElement,
+};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use std::time::Duration;
+
+use crate::circuit::{function_name, opcode_name};
+use crate::source_map::SourceMap;
+
+/// An opcode executed while profiling
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ProfileKey {
+    Acir { function: usize, opcode: usize },
+    /// A Brillig opcode of the unconstrained function called by ACIR opcode `opcode`
+    Brillig { function: usize, opcode: usize, brillig_function: usize, pc: usize },
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OpcodeStats {
+    pub count: u64,
+    pub nanos: u64,
+}
+
+impl OpcodeStats {
+    fn add(&mut self, other: OpcodeStats) {
+        self.count += other.count;
+        self.nanos += other.nanos;
+    }
+}
+
+/// Time spent on each opcode during an execution, before aggregation
+#[derive(Default)]
+pub struct ProfileSamples {
+    samples: HashMap<ProfileKey, OpcodeStats>,
+    pub total: Duration,
+}
+
+impl ProfileSamples {
+    pub fn record(&mut self, key: ProfileKey, elapsed: Duration) {
+        self.samples.entry(key).or_default().add(OpcodeStats { count: 1, nanos: elapsed.as_nanos() as u64 });
+    }
+}
+
+/// Where the time of an execution went, as returned by `profile_execution`
+#[derive(Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExecutionProfile {
+    pub total_nanos: u64,
+    /// ACIR opcodes executed by type
+    pub acir_opcodes: BTreeMap<String, OpcodeStats>,
+    /// Brillig opcodes executed by unconstrained function
+    pub brillig_functions: BTreeMap<String, OpcodeStats>,
+    /// Black box functions called by ACIR opcodes
+    pub black_box_functions: BTreeMap<String, OpcodeStats>,
+    /// Time spent in each Noir function itself, empty without debug symbols
+    pub source_functions: BTreeMap<String, OpcodeStats>,
+    #[serde(skip)]
+    folded_stacks: BTreeMap<String, u64>,
+}
+
+impl ExecutionProfile {
+    pub fn new(program: &Program<FieldElement>, samples: ProfileSamples, source_map: Option<&SourceMap>) -> Self {
+        let mut profile = ExecutionProfile { total_nanos: samples.total.as_nanos() as u64, ..Default::default() };
+        for (key, stats) in samples.samples {
+            let (function, opcode) = match key {
+                ProfileKey::Acir { function, opcode } | ProfileKey::Brillig { function, opcode, .. } => (function, opcode),
+            };
+            let Some(circuit) = program.functions.get(function) else { continue };
+            let mut frames = vec![function_name(circuit, function)];
+            if let Some(source_map) = source_map {
+                frames.extend(source_map.acir_frames(function, opcode));
+            }
+
+            match key {
+                ProfileKey::Acir { .. } => {
+                    let name = circuit.opcodes.get(opcode).map_or("Unknown", opcode_name);
+                    profile.acir_opcodes.entry(name.to_owned()).or_default().add(stats);
+                    if let Some(Opcode::BlackBoxFuncCall(call)) = circuit.opcodes.get(opcode) {
+                        profile.black_box_functions.entry(call.name().to_owned()).or_default().add(stats);
+                    }
+                    if let Some(source_function) = frames.get(1..).and_then(|source| source.last()) {
+                        profile.source_functions.entry(source_function.clone()).or_default().add(stats);
+                    }
+                    frames.push(name.to_owned());
+                }
+                ProfileKey::Brillig { brillig_function, pc, .. } => {
+                    let name = program
+                        .unconstrained_functions
+                        .get(brillig_function)
+                        .map(|function| function.function_name.clone())
+                        .filter(|name| !name.is_empty())
+                        .unwrap_or_else(|| format!("brillig_{}", brillig_function));
+                    profile.brillig_functions.entry(name.clone()).or_default().add(stats);
+                    frames.push(format!("unconstrained {}", name));
+                    if let Some(source_map) = source_map {
+                        let brillig_frames = source_map.brillig_frames(function, brillig_function, pc);
+                        if let Some(source_function) = brillig_frames.last() {
+                            profile.source_functions.entry(source_function.clone()).or_default().add(stats);
+                        }
+                        frames.extend(brillig_frames);
+                    }
+                }
+            }
+
+            frames.dedup();
+            let stack = frames.iter().map(|frame| frame.replace([';', '\n'], " ")).collect::<Vec<_>>().join(";");
+            *profile.folded_stacks.entry(stack).or_default() += stats.nanos;
+        }
+        profile
+    }
+
+    /// Folded stacks in nanoseconds, one `frame;frame;frame nanos` line per
+    /// stack, as read by flamegraph.pl and inferno
+    pub fn to_folded(&self) -> String {
+        self.folded_stacks.iter().map(|(stack, nanos)| format!("{} {}\n", stack, nanos)).collect()
+    }
+
+    pub fn to_json(&self) -> Result<String, String> {
+        serde_json::to_string(self).map_err(|e| format!("Failed to serialize profile: {:?}", e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_samples_are_aggregated() {
+        let mut samples = ProfileSamples::default();
+        let key = ProfileKey::Acir { function: 0, opcode: 0 };
+        samples.record(key, Duration::from_nanos(10));
+        samples.record(key, Duration::from_nanos(5));
+        samples.record(ProfileKey::Acir { function: 0, opcode: 1 }, Duration::from_nanos(1));
+        assert_eq!(samples.samples[&key], OpcodeStats { count: 2, nanos: 15 });
+        assert_eq!(samples.samples.len(), 2);
+    }
+}
@@ -0,0 +1,172 @@
+use log::{info, warn};
+use serde::Serialize;
+use std::sync::{RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+use crate::{metrics, scratch, srs, vk_cache};
+
+// Levels of ComponentCallbacks2.onTrimMemory, so that the level Android passes
+// can be forwarded as is. The RUNNING_* levels are sent while the app runs, the
+// others once its UI is hidden, and aren't ordered with them
+pub const TRIM_MEMORY_RUNNING_MODERATE: i32 = 5;
+pub const TRIM_MEMORY_RUNNING_LOW: i32 = 10;
+pub const TRIM_MEMORY_RUNNING_CRITICAL: i32 = 15;
+pub const TRIM_MEMORY_UI_HIDDEN: i32 = 20;
+pub const TRIM_MEMORY_BACKGROUND: i32 = 40;
+pub const TRIM_MEMORY_MODERATE: i32 = 60;
+pub const TRIM_MEMORY_COMPLETE: i32 = 80;
+
+/// Resources released at a trim level
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Releases {
+    pub heap: bool,
+    pub verification_keys: bool,
+    pub srs: bool,
+}
+
+impl Releases {
+    /// Nothing for unknown levels
+    pub fn for_level(level: i32) -> Self {
+        let (heap, verification_keys, srs) = match level {
+            // Hiding the UI doesn't mean memory is short
+            TRIM_MEMORY_RUNNING_MODERATE | TRIM_MEMORY_UI_HIDDEN => (true, false, false),
+            TRIM_MEMORY_RUNNING_LOW | TRIM_MEMORY_BACKGROUND => (true, true, false),
+            // The app is next to be killed, or other apps are
+            TRIM_MEMORY_RUNNING_CRITICAL | TRIM_MEMORY_MODERATE | TRIM_MEMORY_COMPLETE => (true, true, true),
+            _ => (false, false, false),
+        };
+        Releases { heap, verification_keys, srs }
+    }
+}
+
+// Read by the calls using the SRS, written to unload it
+static SRS_LOCK: RwLock<()> = RwLock::new(());
+
+/// Held by the calls that use the SRS (proving, computing a verification key),
+/// during which it can't be unloaded
+pub struct SrsInUse {
+    _guard: RwLockReadGuard<'static, ()>,
+}
+
+impl SrsInUse {
+    pub fn acquire() -> Self {
+        SrsInUse { _guard: SRS_LOCK.read().unwrap_or_else(|e| e.into_inner()) }
+    }
+}
+
+/// Held while no call uses the SRS, so while no proof is being generated
+pub struct SrsUnused {
+    _guard: RwLockWriteGuard<'static, ()>,
+}
+
+impl SrsUnused {
+    /// Returns `None` instead of waiting if a call is using the SRS
+    pub fn try_acquire() -> Option<Self> {
+        SRS_LOCK.try_write().ok().map(|guard| SrsUnused { _guard: guard })
+    }
+}
+
+/// What `release_resources` freed
+#[derive(Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReleaseReport {
+    pub level: i32,
+    /// Verification keys dropped from memory, the ones on disk are kept
+    pub verification_keys_dropped: usize,
+    pub srs_unloaded: bool,
+    pub heap_trimmed: bool,
+    /// Decrease of the resident set size of the process, which can be
+    /// negative if other threads allocated in the meantime
+    pub freed_bytes: i64,
+}
+
+/// Releases the native memory that can be recovered at this level, from the
+/// least to the most costly to rebuild:
+/// - at `TRIM_MEMORY_RUNNING_MODERATE` and `TRIM_MEMORY_UI_HIDDEN`, the free
+///   memory of the allocator is returned to the OS
+/// - at `TRIM_MEMORY_RUNNING_LOW` and `TRIM_MEMORY_BACKGROUND`, the
+///   verification keys cached in memory are dropped too
+/// - at `TRIM_MEMORY_RUNNING_CRITICAL`, `TRIM_MEMORY_MODERATE` and
+///   `TRIM_MEMORY_COMPLETE`, the SRS is unloaded too, so it has to be set up
+///   again before proving
+///
+/// Proving keys aren't kept between calls by barretenberg, so there are none
+/// to drop. The SRS stays loaded while another thread is proving.
+pub fn release_resources(level: i32) -> ReleaseReport {
+    let releases = Releases::for_level(level);
+    if releases == Releases::default() {
+        warn!("Unknown trim level {}, nothing released", level);
+    }
+    let rss_before = metrics::read_status_kb("VmRSS");
+    let mut report = ReleaseReport { level, ..Default::default() };
+
+    if releases.verification_keys {
+        report.verification_keys_dropped = vk_cache::vk_cache().release_memory();
+    }
+    if releases.srs {
+        match SrsUnused::try_acquire() {
+            Some(_unloading) => match srs::unload_srs(&scratch::scratch_dir()) {
+                Ok(()) => report.srs_unloaded = true,
+                Err(e) => warn!("{}", e),
+            },
+            None => warn!("Not unloading the SRS, a proof is being generated"),
+        }
+    }
+    // Last, so that what the steps above freed is returned too
+    if releases.heap {
+        report.heap_trimmed = trim_heap();
+    }
+
+    if let Some((before, after)) = rss_before.zip(metrics::read_status_kb("VmRSS")) {
+        report.freed_bytes = (before as i64 - after as i64) * 1024;
+    }
+    info!("Released resources: {:?}", report);
+    report
+}
+
+// Returns the free pages of the allocator (shared with barretenberg) to the OS
+#[cfg(target_os = "android")]
+fn trim_heap() -> bool {
+    extern "C" {
+        fn mallopt(param: libc::c_int, value: libc::c_int) -> libc::c_int;
+    }
+    // From bionic's malloc.h, supported by jemalloc and scudo since API 28
+    const M_PURGE: libc::c_int = -101;
+    // SAFETY: mallopt only reads its integer arguments
+    unsafe { mallopt(M_PURGE, 0) == 1 }
+}
+
+#[cfg(all(target_os = "linux", target_env = "gnu"))]
+fn trim_heap() -> bool {
+    // SAFETY: malloc_trim has no preconditions
+    unsafe { libc::malloc_trim(0) == 1 }
+}
+
+#[cfg(not(any(target_os = "android", all(target_os = "linux", target_env = "gnu"))))]
+fn trim_heap() -> bool {
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Only the mapping, as releasing empties the caches the other tests use
+    #[test]
+    fn test_levels_releases() {
+        let nothing = Releases::default();
+        let heap = Releases { heap: true, ..nothing };
+        let keys = Releases { verification_keys: true, ..heap };
+        let all = Releases { srs: true, ..keys };
+
+        assert_eq!(Releases::for_level(TRIM_MEMORY_RUNNING_MODERATE), heap);
+        assert_eq!(Releases::for_level(TRIM_MEMORY_RUNNING_LOW), keys);
+        assert_eq!(Releases::for_level(TRIM_MEMORY_RUNNING_CRITICAL), all);
+        // Above RUNNING_CRITICAL, but only means the UI was hidden
+        assert_eq!(Releases::for_level(TRIM_MEMORY_UI_HIDDEN), heap);
+        assert_eq!(Releases::for_level(TRIM_MEMORY_BACKGROUND), keys);
+        assert_eq!(Releases::for_level(TRIM_MEMORY_MODERATE), all);
+        assert_eq!(Releases::for_level(TRIM_MEMORY_COMPLETE), all);
+        assert_eq!(Releases::for_level(0), nothing);
+        assert_eq!(Releases::for_level(30), nothing);
+    }
+}
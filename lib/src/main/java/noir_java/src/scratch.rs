@@ -0,0 +1,136 @@
+use log::{debug, info, warn};
+use serde::Serialize;
+use std::ffi::CString;
+use std::fs;
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use crate::release;
+
+// Subdirectory of the directory given by the app, so that cleaning up never
+// touches the app's own files
+const SCRATCH_SUBDIR: &str = "noir_scratch";
+
+static SCRATCH_DIR: Mutex<Option<PathBuf>> = Mutex::new(None);
+
+/// State of the scratch directory after `set_scratch_dir`
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScratchDir {
+    pub path: String,
+    /// Files left by runs that didn't finish, now removed
+    pub removed_leftovers: usize,
+    pub free_bytes: u64,
+}
+
+/// Makes barretenberg write the data it moves out of memory in low memory mode
+/// under `parent`, removing what previous runs left there. Fails while a proof
+/// is being generated, as its files would be removed
+pub fn set_scratch_dir(parent: &Path) -> Result<ScratchDir, String> {
+    let _no_prove = release::SrsUnused::try_acquire()
+        .ok_or("Can't set the scratch directory while a proof is being generated")?;
+    let dir = parent.join(SCRATCH_SUBDIR);
+    let scratch = prepare(&dir)?;
+    // noir_rs has no parameter for the directory: barretenberg creates its
+    // files in std::filesystem::temp_directory_path(), which is read from
+    // TMPDIR (checked by test_low_memory_prove_uses_scratch_dir). Android
+    // doesn't set it, and /tmp doesn't exist there.
+    //
+    // Changing the environment races with getenv on other threads. Barretenberg
+    // only reads it while proving, which the lock above excludes, and the JVM
+    // reads a copy made at startup, so the only other readers would be native
+    // libraries of the app: call this at startup, before they run.
+    std::env::set_var("TMPDIR", &dir);
+    info!("Scratch directory: {:?}, {} bytes free", dir, scratch.free_bytes);
+    *SCRATCH_DIR.lock().unwrap_or_else(|e| e.into_inner()) = Some(dir);
+    Ok(scratch)
+}
+
+// Creates the directory, empties it and checks it can be written to
+fn prepare(dir: &Path) -> Result<ScratchDir, String> {
+    fs::create_dir_all(dir).map_err(|e| format!("Failed to create scratch directory {:?}: {}", dir, e))?;
+
+    // Nothing is being proven while the directory is set up, so whatever is
+    // there was left by a run that crashed or was killed
+    let mut removed_leftovers = 0;
+    let entries = fs::read_dir(dir).map_err(|e| format!("Failed to list scratch directory {:?}: {}", dir, e))?;
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let removed = if path.is_dir() { fs::remove_dir_all(&path) } else { fs::remove_file(&path) };
+        match removed {
+            Ok(()) => removed_leftovers += 1,
+            Err(e) => warn!("Failed to remove leftover {:?}: {}", path, e),
+        }
+    }
+    if removed_leftovers > 0 {
+        info!("Removed {} leftovers from {:?}", removed_leftovers, dir);
+    }
+
+    let probe = dir.join(".write_test");
+    fs::write(&probe, b"noir").map_err(|e| format!("Scratch directory {:?} is not writable: {}", dir, e))?;
+    let _ = fs::remove_file(&probe);
+
+    Ok(ScratchDir {
+        path: dir.to_string_lossy().into_owned(),
+        removed_leftovers,
+        free_bytes: free_space(dir)?,
+    })
+}
+
+/// Directory barretenberg writes to in low memory mode
+pub fn scratch_dir() -> PathBuf {
+    SCRATCH_DIR.lock().unwrap_or_else(|e| e.into_inner()).clone().unwrap_or_else(std::env::temp_dir)
+}
+
+/// Fails if the scratch directory can't hold `storage_cap` bytes. A directory
+/// whose free space can't be read (e.g. the default one on Android) isn't checked
+pub fn check_free_space(storage_cap: u64) -> Result<(), String> {
+    let dir = scratch_dir();
+    match free_space(&dir) {
+        Ok(free) if free < storage_cap => Err(format!(
+            "Storage cap of {} bytes exceeds the {} bytes free in the scratch directory {:?}",
+            storage_cap, free, dir
+        )),
+        Ok(free) => {
+            debug!("{} bytes free in {:?} for a storage cap of {} bytes", free, dir, storage_cap);
+            Ok(())
+        }
+        Err(e) => {
+            warn!("{}, set a scratch directory to check it", e);
+            Ok(())
+        }
+    }
+}
+
+/// Bytes available to the app on the file system holding `path`
+pub fn free_space(path: &Path) -> Result<u64, String> {
+    let c_path = CString::new(path.as_os_str().as_bytes()).map_err(|e| format!("Invalid path {:?}: {}", path, e))?;
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+    // SAFETY: c_path is a valid C string and stat is a properly sized buffer
+    if unsafe { libc::statvfs(c_path.as_ptr(), &mut stat) } != 0 {
+        return Err(format!("Failed to read the free space of {:?}: {}", path, std::io::Error::last_os_error()));
+    }
+    Ok(stat.f_bavail as u64 * stat.f_frsize as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Doesn't go through set_scratch_dir, which would change TMPDIR for the other tests
+    #[test]
+    fn test_leftovers_are_removed() {
+        let dir = std::env::temp_dir().join(format!("noir_java_scratch_test_{}", std::process::id()));
+        fs::create_dir_all(dir.join("nested")).unwrap();
+        fs::write(dir.join("poly-0"), b"leftover").unwrap();
+
+        let scratch = prepare(&dir).unwrap();
+        assert_eq!(scratch.removed_leftovers, 2);
+        assert!(scratch.free_bytes > 0);
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 0);
+        assert!(free_space(&dir.join("missing")).is_err());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
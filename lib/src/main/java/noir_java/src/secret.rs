@@ -0,0 +1,270 @@
+use noir_rs::{
+    acir::native_types::{Witness, WitnessMap, WitnessStack},
+    AcirField, FieldElement,
+};
+use log::debug;
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+use std::ops::{Deref, DerefMut};
+pub use zeroize::{Zeroize, Zeroizing};
+
+#[cfg(test)]
+thread_local! {
+    static WIPED_VALUES: std::cell::Cell<usize> = const { std::cell::Cell::new(0) };
+}
+
+/// Number of witness values wiped on this thread so far, to check that a code
+/// path wipes what it read
+#[cfg(test)]
+pub fn wiped_values() -> usize {
+    WIPED_VALUES.with(|wiped| wiped.get())
+}
+
+/// Overwrites every value of the map with zero, in place, keeping the indices
+pub fn wipe_witness_map(witness_map: &mut WitnessMap<FieldElement>) {
+    let witnesses: Vec<Witness> = (&*witness_map).into_iter().map(|(witness, _)| *witness).collect();
+    for witness in &witnesses {
+        // Replaces the value in its node of the map rather than moving it
+        witness_map.insert(*witness, FieldElement::zero());
+    }
+    // The map is usually freed right after, which could let the writes be
+    // optimized out otherwise
+    std::hint::black_box(&*witness_map);
+    #[cfg(test)]
+    WIPED_VALUES.with(|wiped| wiped.set(wiped.get() + witnesses.len()));
+}
+
+// Const initialized and without destructors, so that the allocator can read
+// them without allocating, including while the thread exits
+thread_local! {
+    // Number of WipeFreed guards alive on this thread
+    static WIPE_FREED: Cell<usize> = const { Cell::new(0) };
+    // Freed bytes wiped on this thread so far
+    static WIPED_BYTES: Cell<usize> = const { Cell::new(0) };
+}
+
+fn wiping() -> bool {
+    WIPE_FREED.try_with(|guards| guards.get() > 0).unwrap_or(false)
+}
+
+/// Number of freed bytes wiped by the allocator on this thread so far
+pub fn wiped_bytes() -> usize {
+    WIPED_BYTES.try_with(|wiped| wiped.get()).unwrap_or(0)
+}
+
+/// Allocator of the library, which wipes the memory a thread frees while that
+/// thread holds a `WipeFreed` guard. It reaches the copies of witnesses made by
+/// code that doesn't wipe them, like noir_rs when it proves from a witness map
+/// it takes by value. Other threads, such as the log sink or the verification
+/// pool, free memory as usual. What barretenberg allocates in C++ doesn't go
+/// through it.
+///
+/// It is only installed in Android builds, where the guards wipe nothing
+/// otherwise.
+pub struct WipingAllocator;
+
+// SAFETY: every call is forwarded to the system allocator with the same layout
+unsafe impl GlobalAlloc for WipingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        System.alloc(layout)
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        System.alloc_zeroed(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        if wiping() {
+            // Volatile writes, which can't be dropped as dead stores before the free
+            std::slice::from_raw_parts_mut(ptr, layout.size()).zeroize();
+            let _ = WIPED_BYTES.try_with(|wiped| wiped.set(wiped.get() + layout.size()));
+        }
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        // Unguarded threads keep in place growth
+        if !wiping() {
+            return System.realloc(ptr, layout, new_size);
+        }
+        // Growing in place isn't guaranteed, and moving would leave a copy behind
+        let new_ptr = System.alloc(Layout::from_size_align_unchecked(new_size, layout.align()));
+        if !new_ptr.is_null() {
+            std::ptr::copy_nonoverlapping(ptr, new_ptr, layout.size().min(new_size));
+            self.dealloc(ptr, layout);
+        }
+        new_ptr
+    }
+}
+
+/// While alive, the memory freed by the current thread is wiped first. The
+/// guard can't be sent to another thread, as it only covers the one that
+/// started it.
+pub struct WipeFreed {
+    wiped_before: usize,
+    _not_send: std::marker::PhantomData<*const ()>,
+}
+
+impl WipeFreed {
+    pub fn start() -> Self {
+        WIPE_FREED.with(|guards| guards.set(guards.get() + 1));
+        WipeFreed { wiped_before: wiped_bytes(), _not_send: std::marker::PhantomData }
+    }
+}
+
+impl Drop for WipeFreed {
+    fn drop(&mut self) {
+        WIPE_FREED.with(|guards| guards.set(guards.get() - 1));
+        // What wiping cost the call, as the number of bytes written
+        debug!("Wiped {} freed bytes", wiped_bytes() - self.wiped_before);
+    }
+}
+
+/// Witness map holding private inputs or solved values, wiped when dropped so
+/// that it is also cleared when a call fails halfway
+#[derive(Default)]
+pub struct SecretWitnessMap(WitnessMap<FieldElement>);
+
+impl SecretWitnessMap {
+    pub fn new(witness_map: WitnessMap<FieldElement>) -> Self {
+        SecretWitnessMap(witness_map)
+    }
+
+    /// Hands the map over to code that consumes it (the ACVM or the prover),
+    /// which is then responsible for it
+    pub fn into_inner(mut self) -> WitnessMap<FieldElement> {
+        std::mem::take(&mut self.0)
+    }
+}
+
+impl Deref for SecretWitnessMap {
+    type Target = WitnessMap<FieldElement>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl DerefMut for SecretWitnessMap {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+impl Drop for SecretWitnessMap {
+    fn drop(&mut self) {
+        wipe_witness_map(&mut self.0);
+    }
+}
+
+/// Solved witness stack whose maps are all wiped when dropped
+#[derive(Default)]
+pub struct SecretWitnessStack(WitnessStack<FieldElement>);
+
+impl SecretWitnessStack {
+    pub fn new(witness_stack: WitnessStack<FieldElement>) -> Self {
+        SecretWitnessStack(witness_stack)
+    }
+}
+
+impl Deref for SecretWitnessStack {
+    type Target = WitnessStack<FieldElement>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl DerefMut for SecretWitnessStack {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+impl Drop for SecretWitnessStack {
+    fn drop(&mut self) {
+        while let Some(mut item) = self.0.pop() {
+            wipe_witness_map(&mut item.witness);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn witness_map(values: &[&str]) -> WitnessMap<FieldElement> {
+        let mut witness_map = WitnessMap::new();
+        for (index, value) in values.iter().enumerate() {
+            witness_map.insert(Witness(index as u32), FieldElement::try_from_str(value).unwrap());
+        }
+        witness_map
+    }
+
+    #[test]
+    fn test_wipe_keeps_indices() {
+        let mut map = witness_map(&["0x2a", "0x7"]);
+        wipe_witness_map(&mut map);
+        assert_eq!(map.get(&Witness(0)), Some(&FieldElement::zero()));
+        assert_eq!(map.get(&Witness(1)), Some(&FieldElement::zero()));
+    }
+
+    #[test]
+    fn test_dropped_witnesses_are_wiped() {
+        let before = wiped_values();
+        drop(SecretWitnessMap::new(witness_map(&["0x2a", "0x7"])));
+        assert_eq!(wiped_values() - before, 2);
+
+        // Handed over maps are the responsibility of whoever took them
+        let before = wiped_values();
+        let map = SecretWitnessMap::new(witness_map(&["0x2a"])).into_inner();
+        assert_eq!(wiped_values(), before);
+        assert_eq!(map.get(&Witness(0)), FieldElement::try_from_str("0x2a").as_ref());
+
+        let mut stack = WitnessStack::default();
+        stack.push(1, witness_map(&["0x1"]));
+        stack.push(0, map);
+        drop(SecretWitnessStack::new(stack));
+        assert_eq!(wiped_values() - before, 2);
+    }
+
+    #[test]
+    fn test_freed_memory_is_wiped_while_guarded() {
+        let before = wiped_bytes();
+        {
+            let _wipe_freed = WipeFreed::start();
+            drop(vec![0x2au8; 4096]);
+        }
+        assert!(wiped_bytes() - before >= 4096);
+
+        let before = wiped_bytes();
+        drop(vec![0x2au8; 4096]);
+        assert_eq!(wiped_bytes(), before);
+    }
+
+    #[test]
+    fn test_realloc_moves_only_while_guarded() {
+        let mut unguarded = vec![0x2au8; 4096];
+        let before = wiped_bytes();
+        unguarded.reserve_exact(1 << 20);
+        assert_eq!(wiped_bytes(), before, "Unguarded growth shouldn't wipe anything");
+
+        let mut guarded = vec![0x2au8; 4096];
+        let _wipe_freed = WipeFreed::start();
+        let before = wiped_bytes();
+        guarded.reserve_exact(1 << 20);
+        assert!(wiped_bytes() - before >= 4096, "The old buffer should be wiped when growing");
+    }
+
+    #[test]
+    fn test_guard_only_covers_its_thread() {
+        let _wipe_freed = WipeFreed::start();
+        let wiped_elsewhere = std::thread::spawn(|| {
+            drop(vec![0x2au8; 4096]);
+            wiped_bytes()
+        })
+        .join()
+        .unwrap();
+        assert_eq!(wiped_elsewhere, 0);
+    }
+}
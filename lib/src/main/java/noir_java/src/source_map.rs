@@ -0,0 +1,187 @@
+use acvm::acir::circuit::{brillig::BrilligFunctionId, AcirOpcodeLocation, BrilligOpcodeLocation};
+use noirc_errors::{
+    debug_info::{DebugInfo, ProgramDebugInfo},
+    Location,
+};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+
+#[derive(Deserialize)]
+struct SourceFile {
+    source: String,
+}
+
+// The parts of a compiled circuit needed to map opcodes back to the source
+#[derive(Deserialize)]
+struct DebugArtifact {
+    #[serde(deserialize_with = "ProgramDebugInfo::deserialize_compressed_base64_json")]
+    debug_symbols: ProgramDebugInfo,
+    file_map: BTreeMap<u32, SourceFile>,
+}
+
+/// Maps opcodes back to the Noir functions they were compiled from, using the
+/// debug symbols and sources embedded in a compiled circuit
+pub struct SourceMap {
+    debug_infos: Vec<DebugInfo>,
+    sources: BTreeMap<u32, String>,
+}
+
+impl SourceMap {
+    /// Reads the `debug_symbols` and `file_map` fields of a compiled circuit
+    pub fn from_manifest(manifest_json: &str) -> Result<Self, String> {
+        let artifact: DebugArtifact =
+            serde_json::from_str(manifest_json).map_err(|e| format!("Failed to read debug symbols: {}", e))?;
+        Ok(SourceMap {
+            debug_infos: artifact.debug_symbols.debug_infos,
+            sources: artifact.file_map.into_iter().map(|(id, file)| (id, file.source)).collect(),
+        })
+    }
+
+    /// Noir functions on the call stack of an ACIR opcode, outermost first
+    pub fn acir_frames(&self, function: usize, acir_index: usize) -> Vec<String> {
+        let Some(debug_info) = self.debug_infos.get(function) else {
+            return Vec::new();
+        };
+        match debug_info.acir_locations.get(&AcirOpcodeLocation::new(acir_index)) {
+            Some(call_stack) => self.frames(debug_info.location_tree.get_call_stack(*call_stack)),
+            None => Vec::new(),
+        }
+    }
+
+    /// Noir functions on the call stack of a Brillig opcode, from the entry of
+    /// the unconstrained function
+    pub fn brillig_frames(&self, function: usize, brillig_function: usize, pc: usize) -> Vec<String> {
+        let Some(debug_info) = self.debug_infos.get(function) else {
+            return Vec::new();
+        };
+        let call_stack = debug_info
+            .brillig_locations
+            .get(&BrilligFunctionId(brillig_function as u32))
+            .and_then(|locations| locations.get(&BrilligOpcodeLocation(pc)));
+        match call_stack {
+            Some(call_stack) => self.frames(debug_info.location_tree.get_call_stack(*call_stack)),
+            None => Vec::new(),
+        }
+    }
+
+    fn frames(&self, locations: Vec<Location>) -> Vec<String> {
+        let mut frames: Vec<String> = Vec::new();
+        for location in locations {
+            let name = self
+                .sources
+                .get(&(location.file.as_usize() as u32))
+                .and_then(|source| enclosing_function(source, location.span.start() as usize));
+            if let Some(name) = name {
+                // A call site and the body of the function it is in give the same frame
+                if frames.last() != Some(&name) {
+                    frames.push(name);
+                }
+            }
+        }
+        frames
+    }
+}
+
+/// Name of the last function defined before `offset` in a Noir source file.
+/// This is a best-effort lookup: the debug symbols of a circuit only name
+/// functions when it is compiled for the debugger, so the source is scanned for
+/// `fn` outside of comments and string literals instead. Code that follows a
+/// function without being part of it, like a global, is given to that function.
+pub fn enclosing_function(source: &str, offset: usize) -> Option<String> {
+    let mut end = offset.min(source.len());
+    while !source.is_char_boundary(end) {
+        end -= 1;
+    }
+    // Bytes of multibyte characters never match the ASCII looked for below
+    let bytes = source.as_bytes();
+    let is_ident = |b: u8| b.is_ascii_alphanumeric() || b == b'_' || !b.is_ascii();
+    let mut name = None;
+    let mut comment_depth = 0;
+    let mut i = 0;
+    while i < end {
+        let rest = &bytes[i..];
+        let after_ident = i > 0 && is_ident(bytes[i - 1]);
+        if comment_depth > 0 {
+            // Block comments nest
+            if rest.starts_with(b"*/") {
+                comment_depth -= 1;
+                i += 2;
+            } else if rest.starts_with(b"/*") {
+                comment_depth += 1;
+                i += 2;
+            } else {
+                i += 1;
+            }
+        } else if rest.starts_with(b"//") {
+            i += rest.iter().position(|&b| b == b'\n').unwrap_or(rest.len());
+        } else if rest.starts_with(b"/*") {
+            comment_depth = 1;
+            i += 2;
+        } else if rest[0] == b'"' {
+            i += 1;
+            while i < bytes.len() && bytes[i] != b'"' {
+                i += if bytes[i] == b'\\' { 2 } else { 1 };
+            }
+            i += 1;
+        } else if rest[0] == b'r' && !after_ident && rest[1..].iter().find(|&&b| b != b'#') == Some(&b'"') {
+            // Raw string, r"..." or r#"..."#, which ends at a quote followed by as many #
+            let hashes = rest[1..].iter().take_while(|&&b| b == b'#').count();
+            let mut closing = vec![b'"'];
+            closing.resize(hashes + 1, b'#');
+            i += hashes + 2;
+            i += bytes[i..].windows(closing.len()).position(|window| window == closing.as_slice()).map_or(bytes.len(), |p| p + closing.len());
+        } else if rest.starts_with(b"fn") && !after_ident && rest.get(2).is_some_and(u8::is_ascii_whitespace) {
+            let ident: String = source[i + 2..]
+                .trim_start()
+                .chars()
+                .take_while(|c| c.is_alphanumeric() || *c == '_')
+                .collect();
+            if !ident.is_empty() {
+                name = Some(ident);
+            }
+            i += 2;
+        } else {
+            i += 1;
+        }
+    }
+    name
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_enclosing_function() {
+        let source = "use std;\n\nfn main(x: Field) {\n    assert(square(x) == 9);\n}\n\nunconstrained fn square(x: Field) -> Field {\n    x * x // fn in a comment\n}\n";
+        assert_eq!(enclosing_function(source, 2), None);
+        assert_eq!(enclosing_function(source, source.find("assert").unwrap()).as_deref(), Some("main"));
+        assert_eq!(enclosing_function(source, source.find("x * x").unwrap()).as_deref(), Some("square"));
+        // "fn " inside a comment is ignored
+        assert_eq!(enclosing_function(source, source.len()).as_deref(), Some("square"));
+    }
+
+    #[test]
+    fn test_enclosing_function_skips_comments_and_strings() {
+        let source = concat!(
+            "fn main() {\n",
+            "    /* fn block() { /* fn nested */ } */\n",
+            "    let a = \"fn string\\\" fn escaped\";\n",
+            "    let b = r#\"fn raw \" fn\"#;\n",
+            "    let c = f\"fn {a}\";\n",
+            "    let myfn = 1;\n",
+            "    body();\n",
+            "}\n",
+        );
+        assert_eq!(enclosing_function(source, source.find("body").unwrap()).as_deref(), Some("main"));
+    }
+
+    #[test]
+    fn test_enclosing_function_multibyte_offsets() {
+        let source = "fn main() {\n    // é\n}\nfn other() {}\n";
+        let inside = source.find('é').unwrap() + 1;
+        assert!(!source.is_char_boundary(inside));
+        assert_eq!(enclosing_function(source, inside).as_deref(), Some("main"));
+        assert_eq!(enclosing_function(source, usize::MAX).as_deref(), Some("other"));
+    }
+}
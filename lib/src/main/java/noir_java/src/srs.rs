@@ -0,0 +1,555 @@
+use ark_bn254::{Bn254, Fq, Fq2, Fr, G1Affine, G1Projective, G2Affine};
+use ark_ec::{pairing::Pairing, AffineRepr, VariableBaseMSM};
+use ark_ff::{BigInteger, One, PrimeField};
+use log::{debug, info};
+use noir_rs::barretenberg::srs::{localsrs::LocalSrs, setup_srs, Srs};
+use sha2::{Digest, Sha256};
+use std::fmt;
+use std::fs::{self, File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::os::fd::AsRawFd;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+const G1_POINT_SIZE: usize = 64;
+const G2_POINT_SIZE: usize = 128;
+
+/// Where the Aztec ceremony points are downloaded from by default
+pub const DEFAULT_SRS_URL: &str = "https://crs.aztec.network";
+const G1_FILE_NAME: &str = "bn254_g1.dat";
+const G2_FILE_NAME: &str = "bn254_g2.dat";
+// Held while the cache is written, by any thread or process
+const LOCK_FILE_NAME: &str = "srs.lock";
+// Report progress at most once per megabyte
+const PROGRESS_STEP: u64 = 1 << 20;
+
+// [x]_2 from the Aztec Ignition ceremony, as hardcoded in barretenberg and
+// in the Solidity verifiers it generates (coordinates given as c0, c1)
+const CEREMONY_G2_X: [&str; 2] = [
+    "0118c4d5b837bcc2bc89b5b398b5974e9f5944073b32078b7e231fec938883b0",
+    "260e01b251f6f1c7e7ff4e580791dee8ea51d87a358e038b4efe30fac09383c1",
+];
+const CEREMONY_G2_Y: [&str; 2] = [
+    "22febda3c0c0632a56475b4214e5615e11e6dd3f96e6cea2854a87d4dacc5e55",
+    "04fc6369f7110fe3d25156c1bb9a72859cf2a04641f99ba4ee413c80da6a5fe4",
+];
+
+/// Reasons a locally supplied SRS is rejected
+#[derive(Debug)]
+pub enum SrsVerificationError {
+    /// The file could not be read or does not hold enough points
+    Malformed(String),
+    /// A G1 point is not a valid BN254 point
+    InvalidG1Point(usize),
+    /// The first G1 point is not the BN254 generator
+    GeneratorMismatch,
+    /// The G2 point is not the one from the Aztec ceremony
+    G2Mismatch,
+    /// The G1 points are not successive powers of the ceremony secret
+    PairingMismatch,
+    /// The hash of the G1 prefix differs from the expected one
+    HashMismatch { expected: String, actual: String },
+}
+
+impl fmt::Display for SrsVerificationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SrsVerificationError::Malformed(reason) => write!(f, "Malformed SRS: {}", reason),
+            SrsVerificationError::InvalidG1Point(index) => write!(f, "Invalid G1 point at index {}", index),
+            SrsVerificationError::GeneratorMismatch => write!(f, "First G1 point is not the BN254 generator"),
+            SrsVerificationError::G2Mismatch => write!(f, "G2 point does not match the Aztec ceremony G2 point"),
+            SrsVerificationError::PairingMismatch => write!(f, "G1 points are not consistent with the G2 point (pairing check failed)"),
+            SrsVerificationError::HashMismatch { expected, actual } => {
+                write!(f, "SRS hash mismatch: expected {}, got {}", expected, actual)
+            }
+        }
+    }
+}
+
+/// Loads the SRS at `path` and verifies its first `num_points` G1 points,
+/// returning the hex SHA-256 of that prefix
+pub fn verify_local_srs(
+    path: &str,
+    num_points: u32,
+    expected_hash: Option<&str>,
+) -> Result<String, SrsVerificationError> {
+    // LocalSrs panics on unreadable files
+    let srs = std::panic::catch_unwind(|| LocalSrs::new(num_points, Some(path)).to_srs())
+        .map_err(|_| SrsVerificationError::Malformed(format!("Failed to load SRS from {}", path)))?;
+    verify_srs(&srs, Some(num_points), expected_hash)
+}
+
+/// Verifies an SRS against the Aztec ceremony:
+/// - the first G1 point is the generator and the G2 point is the ceremony's [x]_2
+/// - e(sum r^i * G1[i+1], [1]_2) == e(sum r^i * G1[i], [x]_2), which holds only if
+///   every point of the prefix is the next power of the same secret
+/// - optionally, the SHA-256 of the G1 prefix equals `expected_hash`
+///
+/// The first two checks pin each point to the ceremony's, so no known hashes of
+/// the ceremony files are embedded: a prefix passes exactly when it is theirs.
+pub fn verify_srs(
+    srs: &Srs,
+    num_points: Option<u32>,
+    expected_hash: Option<&str>,
+) -> Result<String, SrsVerificationError> {
+    let available = (srs.g1_data.len() / G1_POINT_SIZE).min(srs.num_points as usize);
+    let used = match num_points {
+        Some(n) => n as usize,
+        None => available,
+    };
+    if used == 0 || used > available {
+        return Err(SrsVerificationError::Malformed(format!(
+            "SRS holds {} G1 points, {} required",
+            available, used
+        )));
+    }
+    if srs.g2_data.len() < G2_POINT_SIZE {
+        return Err(SrsVerificationError::Malformed(format!(
+            "G2 data is {} bytes, expected {}",
+            srs.g2_data.len(),
+            G2_POINT_SIZE
+        )));
+    }
+    debug!("Verifying SRS prefix of {} points", used);
+
+    let prefix = &srs.g1_data[..used * G1_POINT_SIZE];
+    let hash = hex::encode(Sha256::digest(prefix));
+    if let Some(expected) = expected_hash {
+        let expected = expected.trim_start_matches("0x").to_lowercase();
+        if expected != hash {
+            return Err(SrsVerificationError::HashMismatch { expected, actual: hash });
+        }
+    }
+
+    let points = prefix
+        .chunks_exact(G1_POINT_SIZE)
+        .enumerate()
+        .map(|(i, bytes)| parse_g1(bytes).ok_or(SrsVerificationError::InvalidG1Point(i)))
+        .collect::<Result<Vec<_>, _>>()?;
+    if points[0] != G1Affine::generator() {
+        return Err(SrsVerificationError::GeneratorMismatch);
+    }
+
+    let g2 = parse_g2(&srs.g2_data[..G2_POINT_SIZE]).ok_or(SrsVerificationError::G2Mismatch)?;
+    if g2 != ceremony_g2() {
+        return Err(SrsVerificationError::G2Mismatch);
+    }
+
+    if !check_powers(&points, g2, &hash) {
+        return Err(SrsVerificationError::PairingMismatch);
+    }
+
+    info!("SRS verified ({} points, sha256 {})", used, hash);
+    Ok(hash)
+}
+
+// Checks that points[i + 1] = x * points[i] where g2 = [x]_2, batching all
+// the pairs with powers of a challenge derived from the data itself
+fn check_powers(points: &[G1Affine], g2: G2Affine, seed: &str) -> bool {
+    if points.len() < 2 {
+        return true;
+    }
+    let challenge = Fr::from_be_bytes_mod_order(&Sha256::digest(seed.as_bytes()));
+    let mut scalars = Vec::with_capacity(points.len() - 1);
+    let mut power = Fr::one();
+    for _ in 1..points.len() {
+        scalars.push(power);
+        power *= challenge;
+    }
+    let (lhs, rhs) = match (
+        G1Projective::msm(&points[1..], &scalars),
+        G1Projective::msm(&points[..points.len() - 1], &scalars),
+    ) {
+        (Ok(lhs), Ok(rhs)) => (lhs, rhs),
+        _ => return false,
+    };
+    Bn254::pairing(lhs, G2Affine::generator()) == Bn254::pairing(rhs, g2)
+}
+
+// Barretenberg stores field elements as 32-byte big-endian integers,
+// G1 points as x || y and G2 points as x.c0 || x.c1 || y.c0 || y.c1
+fn parse_fq(bytes: &[u8]) -> Option<Fq> {
+    let value = Fq::from_be_bytes_mod_order(bytes);
+    // Reject non-canonical encodings
+    if value.into_bigint().to_bytes_be() != bytes {
+        return None;
+    }
+    Some(value)
+}
+
+fn parse_g1(bytes: &[u8]) -> Option<G1Affine> {
+    let point = G1Affine::new_unchecked(parse_fq(&bytes[..32])?, parse_fq(&bytes[32..64])?);
+    if !point.is_on_curve() {
+        return None;
+    }
+    Some(point)
+}
+
+fn parse_g2(bytes: &[u8]) -> Option<G2Affine> {
+    let x = Fq2::new(parse_fq(&bytes[..32])?, parse_fq(&bytes[32..64])?);
+    let y = Fq2::new(parse_fq(&bytes[64..96])?, parse_fq(&bytes[96..128])?);
+    let point = G2Affine::new_unchecked(x, y);
+    if !point.is_on_curve() || !point.is_in_correct_subgroup_assuming_on_curve() {
+        return None;
+    }
+    Some(point)
+}
+
+fn encode_fq(value: Fq) -> Vec<u8> {
+    let mut bytes = value.into_bigint().to_bytes_be();
+    while bytes.len() < 32 {
+        bytes.insert(0, 0);
+    }
+    bytes
+}
+
+fn encode_g1(point: G1Affine) -> Vec<u8> {
+    let mut bytes = encode_fq(point.x);
+    bytes.extend(encode_fq(point.y));
+    bytes
+}
+
+fn encode_g2(point: G2Affine) -> Vec<u8> {
+    let mut bytes = encode_fq(point.x.c0);
+    bytes.extend(encode_fq(point.x.c1));
+    bytes.extend(encode_fq(point.y.c0));
+    bytes.extend(encode_fq(point.y.c1));
+    bytes
+}
+
+fn ceremony_g2() -> G2Affine {
+    let fq = |s: &str| Fq::from_be_bytes_mod_order(&hex::decode(s).expect("valid constant"));
+    G2Affine::new_unchecked(
+        Fq2::new(fq(CEREMONY_G2_X[0]), fq(CEREMONY_G2_X[1])),
+        Fq2::new(fq(CEREMONY_G2_Y[0]), fq(CEREMONY_G2_Y[1])),
+    )
+}
+
+/// Replaces the SRS loaded in barretenberg with its first point, the generator,
+/// which is enough to verify proofs but not to prove. barretenberg frees the
+/// points it held, which can take hundreds of megabytes for large circuits
+pub fn unload_srs(temp_dir: &Path) -> Result<(), String> {
+    let srs = Srs { g1_data: encode_g1(G1Affine::generator()), g2_data: encode_g2(ceremony_g2()), num_points: 1 };
+    load_srs(srs, 1, temp_dir).map(|_| info!("SRS unloaded")).map_err(|e| format!("Failed to unload SRS: {}", e))
+}
+
+/// Hands `srs` over to barretenberg, which noir_rs only lets load a local SRS
+/// from a file. The file is unique to the call, so concurrent setups don't
+/// read each other's points.
+pub fn load_srs(srs: Srs, num_points: u32, dir: &Path) -> Result<u32, String> {
+    static NEXT_FILE: AtomicU64 = AtomicU64::new(0);
+    let path = dir.join(format!("srs.{}.{}.tmp", std::process::id(), NEXT_FILE.fetch_add(1, Ordering::Relaxed)));
+    let path = path.to_string_lossy().into_owned();
+    LocalSrs(srs).save(Some(&path));
+    let result = setup_srs(num_points, Some(&path));
+    if let Err(e) = fs::remove_file(&path) {
+        debug!("Failed to remove temporary SRS file: {:?}", e);
+    }
+    result.map_err(|e| format!("{:?}", e))
+}
+
+/// Downloads the SRS from a configurable mirror into a persistent cache
+///
+/// The G1 points are stored as a flat prefix of the ceremony file, so a cache
+/// holding fewer points than needed (or an interrupted download) is resumed
+/// with a range request instead of starting over.
+pub struct SrsSource {
+    pub base_url: String,
+    pub cache_dir: PathBuf,
+}
+
+impl SrsSource {
+    pub fn new(base_url: Option<&str>, cache_dir: &str) -> Self {
+        SrsSource {
+            base_url: base_url.unwrap_or(DEFAULT_SRS_URL).trim_end_matches('/').to_owned(),
+            cache_dir: PathBuf::from(cache_dir),
+        }
+    }
+
+    /// Returns the first `num_points` points, downloading whatever the cache
+    /// is missing. `on_progress` receives (downloaded bytes, total bytes), and
+    /// aborts the download by returning an error.
+    pub fn fetch(&self, num_points: u32, on_progress: &mut dyn FnMut(u64, u64) -> Result<(), String>) -> Result<Srs, String> {
+        fs::create_dir_all(&self.cache_dir)
+            .map_err(|e| format!("Failed to create SRS cache directory {:?}: {}", self.cache_dir, e))?;
+        // Other calls wait for this download rather than appending to the same files
+        let _lock = CacheLock::acquire(&self.cache_dir.join(LOCK_FILE_NAME))?;
+
+        let g1_path = self.cache_dir.join(G1_FILE_NAME);
+        let g2_path = self.cache_dir.join(G2_FILE_NAME);
+        let g1_size = num_points as u64 * G1_POINT_SIZE as u64;
+        let g2_size = G2_POINT_SIZE as u64;
+
+        // Drop any partially written point left by an interrupted download
+        let cached_g1 = cached_len(&g1_path, G1_POINT_SIZE as u64)?.min(g1_size);
+        let cached_g2 = cached_len(&g2_path, g2_size)?.min(g2_size);
+        let total = g1_size + g2_size;
+        let mut downloaded = cached_g1 + cached_g2;
+        debug!("SRS cache holds {} of {} bytes", downloaded, total);
+        on_progress(downloaded, total)?;
+
+        if cached_g1 < g1_size {
+            let url = format!("{}/g1.dat", self.base_url);
+            self.download_range(&url, &g1_path, cached_g1, g1_size, &mut |n| {
+                downloaded += n;
+                on_progress(downloaded, total)
+            })?;
+        }
+        if cached_g2 < g2_size {
+            let url = format!("{}/g2.dat", self.base_url);
+            self.download_range(&url, &g2_path, cached_g2, g2_size, &mut |n| {
+                downloaded += n;
+                on_progress(downloaded, total)
+            })?;
+        }
+        info!("SRS cache at {:?} holds {} points", self.cache_dir, num_points);
+
+        Ok(Srs {
+            g1_data: read_prefix(&g1_path, g1_size)?,
+            g2_data: read_prefix(&g2_path, g2_size)?,
+            num_points,
+        })
+    }
+
+    // Appends bytes [start, end) of `url` to `path`, falling back to a full
+    // download if the server ignores the range header
+    fn download_range(
+        &self,
+        url: &str,
+        path: &Path,
+        start: u64,
+        end: u64,
+        on_chunk: &mut dyn FnMut(u64) -> Result<(), String>,
+    ) -> Result<(), String> {
+        debug!("Downloading bytes {}..{} of {}", start, end, url);
+        let response = ureq::get(url)
+            .set("Range", &format!("bytes={}-{}", start, end - 1))
+            .call()
+            .map_err(|e| format!("Failed to download {}: {}", url, e))?;
+
+        // A server ignoring the range header sends the whole file
+        let mut offset = if response.status() == 206 {
+            start
+        } else {
+            debug!("Server ignored range request, downloading {} from the start", url);
+            0
+        };
+        let mut file = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(path)
+            .map_err(|e| format!("Failed to open {:?}: {}", path, e))?;
+        file.set_len(offset)
+            .and_then(|_| file.seek(SeekFrom::Start(offset)))
+            .map_err(|e| format!("Failed to prepare {:?}: {}", path, e))?;
+
+        let mut reader = response.into_reader().take(end - offset);
+        let mut buffer = vec![0u8; 64 * 1024];
+        let mut unreported = 0u64;
+        while offset < end {
+            let read = reader.read(&mut buffer).map_err(|e| format!("Failed to read {}: {}", url, e))?;
+            if read == 0 {
+                return Err(format!("Download of {} ended after {} of {} bytes", url, offset, end));
+            }
+            file.write_all(&buffer[..read]).map_err(|e| format!("Failed to write {:?}: {}", path, e))?;
+            // Bytes that were already cached are not reported twice
+            unreported += (offset + read as u64).saturating_sub(start.max(offset));
+            offset += read as u64;
+            if unreported >= PROGRESS_STEP || offset == end {
+                on_chunk(unreported)?;
+                unreported = 0;
+            }
+        }
+        file.flush().map_err(|e| format!("Failed to flush {:?}: {}", path, e))
+    }
+}
+
+// Exclusive flock on a file of the cache directory, released when dropped
+struct CacheLock(File);
+
+impl CacheLock {
+    fn acquire(path: &Path) -> Result<CacheLock, String> {
+        let file = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(path)
+            .map_err(|e| format!("Failed to open {:?}: {}", path, e))?;
+        // Each open file description holds its own lock, so this also
+        // excludes other threads of the process
+        if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX) } != 0 {
+            return Err(format!("Failed to lock {:?}: {}", path, std::io::Error::last_os_error()));
+        }
+        Ok(CacheLock(file))
+    }
+}
+
+impl Drop for CacheLock {
+    fn drop(&mut self) {
+        unsafe { libc::flock(self.0.as_raw_fd(), libc::LOCK_UN) };
+    }
+}
+
+fn cached_len(path: &Path, unit: u64) -> Result<u64, String> {
+    match fs::metadata(path) {
+        Ok(metadata) => Ok(metadata.len() - metadata.len() % unit),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(0),
+        Err(e) => Err(format!("Failed to read {:?}: {}", path, e)),
+    }
+}
+
+fn read_prefix(path: &Path, len: u64) -> Result<Vec<u8>, String> {
+    let mut data = Vec::with_capacity(len as usize);
+    File::open(path)
+        .and_then(|file| file.take(len).read_to_end(&mut data))
+        .map_err(|e| format!("Failed to read {:?}: {}", path, e))?;
+    if (data.len() as u64) < len {
+        return Err(format!("{:?} holds {} bytes, expected {}", path, data.len(), len));
+    }
+    Ok(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ark_ec::CurveGroup;
+
+    // Builds an SRS with a known secret, which can't match the ceremony G2
+    fn fake_srs(secret: u64, num_points: u32) -> Srs {
+        let tau = Fr::from(secret);
+        let mut g1_data = Vec::new();
+        let mut power = Fr::one();
+        for _ in 0..num_points {
+            g1_data.extend(encode_g1((G1Affine::generator() * power).into_affine()));
+            power *= tau;
+        }
+        let g2_data = encode_g2((G2Affine::generator() * tau).into_affine());
+        Srs { g1_data, g2_data, num_points }
+    }
+
+    #[test]
+    fn test_ceremony_g2_is_valid() {
+        let g2 = ceremony_g2();
+        assert!(g2.is_on_curve());
+        assert!(g2.is_in_correct_subgroup_assuming_on_curve());
+        assert_eq!(parse_g2(&encode_g2(g2)), Some(g2));
+    }
+
+    #[test]
+    fn test_check_powers() {
+        let srs = fake_srs(42, 8);
+        let points: Vec<_> = srs.g1_data.chunks_exact(G1_POINT_SIZE).map(|b| parse_g1(b).unwrap()).collect();
+        let g2 = parse_g2(&srs.g2_data).unwrap();
+        assert!(check_powers(&points, g2, "seed"));
+
+        let mut corrupted = points.clone();
+        corrupted[5] = (corrupted[5] * Fr::from(3u64)).into_affine();
+        assert!(!check_powers(&corrupted, g2, "seed"));
+    }
+
+    #[test]
+    fn test_rejects_foreign_g2() {
+        let srs = fake_srs(42, 8);
+        assert!(matches!(verify_srs(&srs, None, None), Err(SrsVerificationError::G2Mismatch)));
+    }
+
+    #[test]
+    fn test_rejects_truncated_srs() {
+        let mut srs = fake_srs(42, 8);
+        srs.g1_data.truncate(3 * G1_POINT_SIZE + 10);
+        assert!(matches!(verify_srs(&srs, Some(4), None), Err(SrsVerificationError::Malformed(_))));
+    }
+
+    #[test]
+    fn test_rejects_hash_mismatch() {
+        let srs = fake_srs(42, 8);
+        let result = verify_srs(&srs, Some(4), Some("00"));
+        assert!(matches!(result, Err(SrsVerificationError::HashMismatch { .. })));
+    }
+
+    #[test]
+    fn test_rejects_wrong_generator() {
+        let mut srs = fake_srs(42, 4);
+        let other = encode_g1((G1Affine::generator() * Fr::from(2u64)).into_affine());
+        srs.g1_data[..G1_POINT_SIZE].copy_from_slice(&other);
+        assert!(matches!(verify_srs(&srs, None, None), Err(SrsVerificationError::GeneratorMismatch)));
+    }
+
+    // Minimal HTTP server honouring single range requests, recording the
+    // Range header of every request it receives
+    fn serve(files: Vec<(&'static str, Vec<u8>)>) -> (String, std::sync::Arc<std::sync::Mutex<Vec<String>>>) {
+        use std::io::BufRead;
+        use std::net::TcpListener;
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let requests = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let log = requests.clone();
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let mut reader = std::io::BufReader::new(stream.try_clone().unwrap());
+                let mut request_line = String::new();
+                reader.read_line(&mut request_line).unwrap();
+                let name = request_line.split_whitespace().nth(1).unwrap().trim_start_matches('/').to_owned();
+                let mut range = String::new();
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    if line.trim().is_empty() {
+                        break;
+                    }
+                    if let Some(value) = line.to_lowercase().strip_prefix("range: bytes=") {
+                        range = value.trim().to_owned();
+                    }
+                }
+                log.lock().unwrap().push(format!("{} {}", name, range));
+                let data = &files.iter().find(|(n, _)| *n == name).unwrap().1;
+                let (start, end) = range
+                    .split_once('-')
+                    .map(|(a, b)| (a.parse::<usize>().unwrap(), b.parse::<usize>().unwrap() + 1))
+                    .unwrap();
+                let body = &data[start..end.min(data.len())];
+                write!(stream, "HTTP/1.1 206 Partial Content\r\nContent-Length: {}\r\nConnection: close\r\n\r\n", body.len()).unwrap();
+                stream.write_all(body).unwrap();
+            }
+        });
+        (url, requests)
+    }
+
+    #[test]
+    fn test_fetch_resumes_from_cache() {
+        let srs = fake_srs(7, 16);
+        let (url, requests) = serve(vec![("g1.dat", srs.g1_data.clone()), ("g2.dat", srs.g2_data.clone())]);
+        let cache_dir = std::env::temp_dir().join(format!("noir_java_srs_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&cache_dir);
+        let source = SrsSource::new(Some(&url), cache_dir.to_str().unwrap());
+
+        let mut last = (0, 0);
+        let first = source
+            .fetch(4, &mut |done, total| {
+                last = (done, total);
+                Ok(())
+            })
+            .unwrap();
+        assert_eq!(first.g1_data, srs.g1_data[..4 * G1_POINT_SIZE]);
+        assert_eq!(first.g2_data, srs.g2_data);
+        assert_eq!(last, (4 * 64 + 128, 4 * 64 + 128));
+
+        // Simulate a download interrupted in the middle of a point
+        let mut file = OpenOptions::new().append(true).open(cache_dir.join(G1_FILE_NAME)).unwrap();
+        file.write_all(&srs.g1_data[4 * G1_POINT_SIZE..4 * G1_POINT_SIZE + 10]).unwrap();
+
+        // A listener failing aborts the download, leaving what was cached
+        let aborted = source.fetch(8, &mut |done, _| if done > 4 * 64 + 128 { Err("cancelled".to_owned()) } else { Ok(()) });
+        assert_eq!(aborted.err().as_deref(), Some("cancelled"));
+
+        let second = source.fetch(8, &mut |_, _| Ok(())).unwrap();
+        assert_eq!(second.g1_data, srs.g1_data[..8 * G1_POINT_SIZE]);
+        assert_eq!(
+            *requests.lock().unwrap(),
+            vec!["g1.dat 0-255".to_owned(), "g2.dat 0-127".to_owned(), "g1.dat 256-511".to_owned()]
+        );
+        fs::remove_dir_all(&cache_dir).unwrap();
+    }
+}
@@ -1,9 +1,16 @@
+use base64::{engine::general_purpose::STANDARD, Engine};
 use noir_rs::{
-    native_types::{Witness, WitnessMap},
+    acir::brillig::{BinaryIntOp, BitSize, HeapVector, IntegerBitSize, MemoryAddress, Opcode as BrilligOpcode},
+    acir::circuit::{
+        brillig::{BrilligBytecode, BrilligFunctionId},
+        opcodes::AcirFunctionId,
+        Circuit, Opcode, Program, PublicInputs,
+    },
+    native_types::{Expression, Witness, WitnessMap},
     FieldElement,
     AcirField,
 };
-use std::collections::HashMap;
+use std::collections::{BTreeSet, HashMap};
 
 /// Creates a simple witness map for testing
 pub fn create_test_witness_map() -> WitnessMap<FieldElement> {
@@ -32,4 +39,160 @@ pub fn convert_to_witness_map(map: HashMap<String, String>) -> WitnessMap<FieldE
         );
     }
     witness_map
-} 
\ No newline at end of file
+} 
+
+/// Encodes a program the way `nargo compile` writes its `bytecode`
+pub fn encode_program(program: &Program<FieldElement>) -> String {
+    STANDARD.encode(Program::serialize_program(program))
+}
+
+fn witnesses(indices: &[u32]) -> BTreeSet<Witness> {
+    indices.iter().map(|&index| Witness(index)).collect()
+}
+
+/// Program of two ACIR functions, as compiled from
+///
+/// ```noir
+/// fn main(x: Field, y: Field) -> pub Field {
+///     add(x, y)
+/// }
+///
+/// #[fold]
+/// fn add(x: Field, y: Field) -> Field {
+///     x + y
+/// }
+/// ```
+pub fn fold_program_bytecode() -> String {
+    let main = Circuit {
+        function_name: "main".to_string(),
+        current_witness_index: 2,
+        opcodes: vec![Opcode::Call {
+            id: AcirFunctionId(1),
+            inputs: vec![Witness(0), Witness(1)],
+            outputs: vec![Witness(2)],
+            predicate: None,
+        }],
+        private_parameters: witnesses(&[0, 1]),
+        return_values: PublicInputs(witnesses(&[2])),
+        ..Circuit::default()
+    };
+    // x + y - sum = 0
+    let sum = Expression {
+        mul_terms: vec![],
+        linear_combinations: vec![
+            (FieldElement::one(), Witness(0)),
+            (FieldElement::one(), Witness(1)),
+            (-FieldElement::one(), Witness(2)),
+        ],
+        q_c: FieldElement::zero(),
+    };
+    let add = Circuit {
+        function_name: "add".to_string(),
+        current_witness_index: 2,
+        opcodes: vec![Opcode::AssertZero(sum)],
+        private_parameters: witnesses(&[0, 1]),
+        return_values: PublicInputs(witnesses(&[2])),
+        ..Circuit::default()
+    };
+    encode_program(&Program { functions: vec![main, add], unconstrained_functions: vec![] })
+}
+
+/// Program asserting that its two inputs are equal, as compiled from
+///
+/// ```noir
+/// fn main(x: Field, y: Field) {
+///     assert(x == y);
+/// }
+/// ```
+pub fn assert_equal_bytecode() -> String {
+    let main = Circuit {
+        function_name: "main".to_string(),
+        current_witness_index: 1,
+        opcodes: vec![Opcode::AssertZero(Expression {
+            mul_terms: vec![],
+            linear_combinations: vec![(FieldElement::one(), Witness(0)), (-FieldElement::one(), Witness(1))],
+            q_c: FieldElement::zero(),
+        })],
+        private_parameters: witnesses(&[0, 1]),
+        ..Circuit::default()
+    };
+    encode_program(&Program { functions: vec![main], unconstrained_functions: vec![] })
+}
+
+/// Program whose main calls the unconstrained function `spin`, which counts to
+/// `iterations` in a loop, or loops forever for `None`
+pub fn brillig_loop_bytecode(iterations: Option<u32>) -> String {
+    let u32_const = |address, value: u32| BrilligOpcode::Const {
+        destination: MemoryAddress::direct(address),
+        bit_size: BitSize::Integer(IntegerBitSize::U32),
+        value: FieldElement::from(value as u128),
+    };
+    let bytecode = match iterations {
+        None => vec![BrilligOpcode::Jump { location: 0 }],
+        Some(iterations) => vec![
+            u32_const(10, 0),
+            u32_const(11, 1),
+            u32_const(12, iterations),
+            // while i < iterations { i += 1 }
+            BrilligOpcode::BinaryIntOp {
+                destination: MemoryAddress::direct(13),
+                op: BinaryIntOp::LessThan,
+                bit_size: IntegerBitSize::U32,
+                lhs: MemoryAddress::direct(10),
+                rhs: MemoryAddress::direct(12),
+            },
+            BrilligOpcode::JumpIf { condition: MemoryAddress::direct(13), location: 6 },
+            BrilligOpcode::Jump { location: 8 },
+            BrilligOpcode::BinaryIntOp {
+                destination: MemoryAddress::direct(10),
+                op: BinaryIntOp::Add,
+                bit_size: IntegerBitSize::U32,
+                lhs: MemoryAddress::direct(10),
+                rhs: MemoryAddress::direct(11),
+            },
+            BrilligOpcode::Jump { location: 3 },
+            // Returns nothing
+            u32_const(14, 0),
+            BrilligOpcode::Stop {
+                return_data: HeapVector { pointer: MemoryAddress::direct(14), size: MemoryAddress::direct(14) },
+            },
+        ],
+    };
+    let main = Circuit {
+        function_name: "main".to_string(),
+        opcodes: vec![Opcode::BrilligCall { id: BrilligFunctionId(0), inputs: vec![], outputs: vec![], predicate: None }],
+        ..Circuit::default()
+    };
+    let spin = BrilligBytecode { function_name: "spin".to_string(), bytecode };
+    encode_program(&Program { functions: vec![main], unconstrained_functions: vec![spin] })
+}
+
+/// Program whose main calls the unconstrained function `ask`, which makes the
+/// foreign call `function` without inputs or outputs
+pub fn oracle_call_bytecode(function: &str) -> String {
+    let bytecode = vec![
+        BrilligOpcode::ForeignCall {
+            function: function.to_string(),
+            destinations: vec![],
+            destination_value_types: vec![],
+            inputs: vec![],
+            input_value_types: vec![],
+        },
+        // Returns nothing
+        BrilligOpcode::Const {
+            destination: MemoryAddress::direct(0),
+            bit_size: BitSize::Integer(IntegerBitSize::U32),
+            value: FieldElement::zero(),
+        },
+        BrilligOpcode::Stop {
+            return_data: HeapVector { pointer: MemoryAddress::direct(0), size: MemoryAddress::direct(0) },
+        },
+    ];
+    let main = Circuit {
+        function_name: "main".to_string(),
+        opcodes: vec![Opcode::BrilligCall { id: BrilligFunctionId(0), inputs: vec![], outputs: vec![], predicate: None }],
+        ..Circuit::default()
+    };
+    let ask = BrilligBytecode { function_name: "ask".to_string(), bytecode };
+    encode_program(&Program { functions: vec![main], unconstrained_functions: vec![ask] })
+}
@@ -0,0 +1,245 @@
+use serde::Serialize;
+use serde_json::{json, Map, Value};
+use std::cell::Cell;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Mutex, OnceLock};
+use std::time::Instant;
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id, Record};
+use tracing::subscriber::Interest;
+use tracing::{Metadata, Subscriber};
+use tracing_subscriber::layer::{Context, Layer, SubscriberExt};
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::Registry;
+
+// Checked before doing any work for a span, so spans cost next to nothing
+// while no trace is being recorded
+static RECORDING_ACTIVE: AtomicBool = AtomicBool::new(false);
+static RECORDING: Mutex<Option<Recording>> = Mutex::new(None);
+static NEXT_THREAD_ID: AtomicU64 = AtomicU64::new(1);
+
+thread_local! {
+    static THREAD_ID: Cell<u64> = const { Cell::new(0) };
+}
+
+struct Recording {
+    path: PathBuf,
+    started: Instant,
+    events: Vec<Value>,
+    // Names of the threads the events were recorded on, by trace thread id
+    threads: BTreeMap<u64, String>,
+}
+
+/// Trace written by `stop`
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TraceSummary {
+    pub path: String,
+    pub spans: usize,
+    pub duration_nanos: u64,
+}
+
+/// Starts recording the spans of every thread, to be written to `path` in the
+/// Chrome trace event format (which Perfetto and chrome://tracing open) by `stop`
+pub fn start(path: &Path) -> Result<(), String> {
+    install()?;
+    let mut recording = RECORDING.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(current) = recording.as_ref() {
+        return Err(format!("A trace is already being recorded to {:?}", current.path));
+    }
+    // Fails now rather than after the run to trace
+    fs::write(path, b"").map_err(|e| format!("Failed to create trace file {:?}: {}", path, e))?;
+    *recording = Some(Recording {
+        path: path.to_owned(),
+        started: Instant::now(),
+        events: Vec::new(),
+        threads: BTreeMap::new(),
+    });
+    RECORDING_ACTIVE.store(true, Ordering::Relaxed);
+    Ok(())
+}
+
+/// Stops recording and writes the trace. Spans still open are left out.
+pub fn stop() -> Result<TraceSummary, String> {
+    RECORDING_ACTIVE.store(false, Ordering::Relaxed);
+    let recording = RECORDING
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .take()
+        .ok_or_else(|| "No trace is being recorded".to_owned())?;
+
+    let pid = std::process::id();
+    let spans = recording.events.len();
+    let mut events = recording.events;
+    for (tid, name) in &recording.threads {
+        events.push(json!({ "name": "thread_name", "ph": "M", "pid": pid, "tid": tid, "args": { "name": name } }));
+    }
+    let trace = json!({ "traceEvents": events, "displayTimeUnit": "ms" });
+    let contents = serde_json::to_vec(&trace).map_err(|e| format!("Failed to serialize trace: {}", e))?;
+    fs::write(&recording.path, contents).map_err(|e| format!("Failed to write trace file {:?}: {}", recording.path, e))?;
+
+    Ok(TraceSummary {
+        path: recording.path.to_string_lossy().into_owned(),
+        spans,
+        duration_nanos: recording.started.elapsed().as_nanos() as u64,
+    })
+}
+
+// Installed on first use, as the global subscriber can only be set once. A
+// failure is kept, so every later start fails the same way
+fn install() -> Result<(), String> {
+    static INSTALLED: OnceLock<Result<(), String>> = OnceLock::new();
+    INSTALLED
+        .get_or_init(|| {
+            tracing::subscriber::set_global_default(Registry::default().with(ChromeTraceLayer))
+                .map_err(|e| format!("Failed to install the trace recorder: {}", e))
+        })
+        .clone()
+}
+
+/// Records each span as a complete ("X") event when it closes. A span with a
+/// `name` field is named after it; if the field is declared but left empty,
+/// as for a phase cut short, the span is dropped.
+struct ChromeTraceLayer;
+
+struct SpanTiming {
+    started: Instant,
+    args: Map<String, Value>,
+}
+
+impl<S> Layer<S> for ChromeTraceLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn register_callsite(&self, _metadata: &'static Metadata<'static>) -> Interest {
+        // Asks again for every span, as recording can start and stop at any time
+        Interest::sometimes()
+    }
+
+    fn enabled(&self, metadata: &Metadata<'_>, _ctx: Context<'_, S>) -> bool {
+        metadata.is_span() && RECORDING_ACTIVE.load(Ordering::Relaxed)
+    }
+
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(id) else { return };
+        let mut args = Map::new();
+        attrs.record(&mut ArgsVisitor(&mut args));
+        span.extensions_mut().insert(SpanTiming { started: Instant::now(), args });
+    }
+
+    fn on_record(&self, id: &Id, values: &Record<'_>, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(id) else { return };
+        let mut extensions = span.extensions_mut();
+        if let Some(timing) = extensions.get_mut::<SpanTiming>() {
+            values.record(&mut ArgsVisitor(&mut timing.args));
+        }
+    }
+
+    fn on_close(&self, id: Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(&id) else { return };
+        let Some(mut timing) = span.extensions_mut().remove::<SpanTiming>() else { return };
+        let metadata = span.metadata();
+        let name = match timing.args.remove("name") {
+            Some(Value::String(name)) => name,
+            Some(other) => other.to_string(),
+            None if metadata.fields().field("name").is_some() => return,
+            None => metadata.name().to_owned(),
+        };
+        let ended = Instant::now();
+
+        let mut recording = RECORDING.lock().unwrap_or_else(|e| e.into_inner());
+        let Some(recording) = recording.as_mut() else { return };
+        let tid = thread_id();
+        recording
+            .threads
+            .entry(tid)
+            .or_insert_with(|| std::thread::current().name().map_or_else(|| format!("thread {}", tid), str::to_owned));
+        let ts = timing.started.saturating_duration_since(recording.started);
+        recording.events.push(json!({
+            "name": name,
+            "cat": metadata.target(),
+            "ph": "X",
+            "ts": ts.as_nanos() as f64 / 1000.0,
+            "dur": ended.saturating_duration_since(timing.started).as_nanos() as f64 / 1000.0,
+            "pid": std::process::id(),
+            "tid": tid,
+            "args": timing.args,
+        }));
+    }
+}
+
+// Small ids for the trace, as Rust thread ids can't be turned into numbers
+fn thread_id() -> u64 {
+    THREAD_ID.with(|id| {
+        if id.get() == 0 {
+            id.set(NEXT_THREAD_ID.fetch_add(1, Ordering::Relaxed));
+        }
+        id.get()
+    })
+}
+
+struct ArgsVisitor<'a>(&'a mut Map<String, Value>);
+
+impl Visit for ArgsVisitor<'_> {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.0.insert(field.name().to_owned(), Value::from(value));
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.0.insert(field.name().to_owned(), Value::from(value));
+    }
+
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.0.insert(field.name().to_owned(), Value::from(value));
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.0.insert(field.name().to_owned(), Value::from(value));
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        self.0.insert(field.name().to_owned(), Value::from(format!("{:?}", value)));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tracing::field::Empty;
+
+    #[test]
+    fn test_spans_are_written_as_complete_events() {
+        let path = std::env::temp_dir().join(format!("noir_java_trace_{}.json", std::process::id()));
+        start(&path).unwrap();
+        assert!(start(&path).is_err());
+        {
+            let _call = tracing::info_span!("call", name = "traced_call", circuit_size = 4096u64).entered();
+            let phase = tracing::info_span!("phase", name = Empty).entered();
+            phase.record("name", "decode");
+            drop(phase);
+            // Cut short, so left out
+            let _phase = tracing::info_span!("phase", name = Empty).entered();
+        }
+        let summary = stop().unwrap();
+        assert!(stop().is_err());
+
+        let trace: Value = serde_json::from_slice(&fs::read(&path).unwrap()).unwrap();
+        fs::remove_file(&path).unwrap();
+        // Spans of other tests running meanwhile may be there too
+        let events = trace["traceEvents"].as_array().unwrap();
+        let event = |name: &str| events.iter().find(|event| event["name"] == name && event["ph"] == "X").cloned();
+        let call = event("traced_call").unwrap();
+        let decode = events
+            .iter()
+            .find(|event| event["name"] == "decode" && event["tid"] == call["tid"] && event["ts"].as_f64() >= call["ts"].as_f64())
+            .unwrap();
+        assert_eq!(call["args"]["circuit_size"], 4096);
+        assert!(decode["dur"].as_f64() <= call["dur"].as_f64());
+        assert!(event("phase").is_none());
+        assert!(summary.spans >= 2);
+    }
+}
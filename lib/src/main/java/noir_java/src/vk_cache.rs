@@ -0,0 +1,320 @@
+use log::{debug, error, info};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard, OnceLock};
+
+use crate::compat;
+
+const CHECKSUM_SIZE: usize = 32;
+// Subdirectory of the directory set by the app that the cache owns, the only
+// place it creates or removes anything
+const OWNED_DIR_NAME: &str = "noir_java_vk";
+
+/// Identifies a verification key: the same bytecode gives different keys
+/// depending on the flavor and on whether zero knowledge is disabled
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct VkCacheKey {
+    pub bytecode_hash: String,
+    pub flavor: String,
+    pub disable_zk: bool,
+}
+
+impl VkCacheKey {
+    pub fn new(circuit_bytecode: &str, flavor: &str, disable_zk: bool) -> Self {
+        VkCacheKey {
+            bytecode_hash: hex::encode(Sha256::digest(circuit_bytecode.as_bytes())),
+            flavor: flavor.to_owned(),
+            disable_zk,
+        }
+    }
+
+    fn file_name(&self) -> String {
+        format!(
+            "{}_{}{}.vk",
+            self.bytecode_hash,
+            self.flavor,
+            if self.disable_zk { "_nozk" } else { "" }
+        )
+    }
+}
+
+#[derive(Clone, Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VkCacheStats {
+    pub memory_hits: u64,
+    pub disk_hits: u64,
+    pub misses: u64,
+    pub entries: usize,
+}
+
+// A key being computed holds the lock of its slot, so other requests for it
+// wait for the result instead of computing it again
+type Slot = Arc<Mutex<Option<Vec<u8>>>>;
+
+#[derive(Default)]
+struct CacheState {
+    slots: HashMap<VkCacheKey, Slot>,
+    disk_dir: Option<PathBuf>,
+    stats: VkCacheStats,
+}
+
+/// Verification keys by circuit, in memory and optionally on disk. Computing a
+/// key only blocks the requests for that same key.
+#[derive(Default)]
+pub struct VkCache {
+    state: Mutex<CacheState>,
+}
+
+impl VkCache {
+    fn state(&self) -> MutexGuard<'_, CacheState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Returns the cached key, looking in memory first and then on disk,
+    /// or computes it and stores it in both
+    pub fn get_or_compute<F>(&self, key: VkCacheKey, compute: F) -> Result<Vec<u8>, String>
+    where
+        F: FnOnce() -> Result<Vec<u8>, String>,
+    {
+        let (slot, disk_dir) = {
+            let mut state = self.state();
+            let slot = state.slots.entry(key.clone()).or_default().clone();
+            (slot, state.disk_dir.clone())
+        };
+        let mut vk = slot.lock().unwrap_or_else(|e| e.into_inner());
+
+        if let Some(vk) = vk.as_ref() {
+            self.state().stats.memory_hits += 1;
+            debug!("Verification key cache hit for {}", key.bytecode_hash);
+            return Ok(vk.clone());
+        }
+
+        if let Some(dir) = &disk_dir {
+            if let Some(loaded) = read_checked(&dir.join(key.file_name())) {
+                self.state().stats.disk_hits += 1;
+                debug!("Verification key loaded from disk cache for {}", key.bytecode_hash);
+                *vk = Some(loaded.clone());
+                return Ok(loaded);
+            }
+        }
+
+        self.state().stats.misses += 1;
+        debug!("Verification key cache miss for {}", key.bytecode_hash);
+        let computed = compute()?;
+        if let Some(dir) = &disk_dir {
+            // A failed write only costs a recomputation on the next launch
+            if let Err(e) = write_checked(dir, &key.file_name(), &computed) {
+                error!("Failed to write verification key to disk cache: {}", e);
+            }
+        }
+        *vk = Some(computed.clone());
+        Ok(computed)
+    }
+
+    /// Sets the directory keys are persisted to, or disables persistence if `None`.
+    /// Keys are kept in `noir_java_vk/noir_rs-<version>` under it, as another
+    /// noir_rs release may build different keys; those of other releases are
+    /// removed. Nothing else in the directory is touched.
+    pub fn set_disk_dir(&self, dir: Option<PathBuf>) -> Result<(), String> {
+        let dir = match dir {
+            Some(parent) => {
+                let owned = parent.join(OWNED_DIR_NAME);
+                let dir = owned.join(version_dir_name());
+                fs::create_dir_all(&dir).map_err(|e| format!("Failed to create cache directory {:?}: {}", dir, e))?;
+                remove_other_versions(&owned);
+                Some(dir)
+            }
+            None => None,
+        };
+        info!("Verification key disk cache: {:?}", dir);
+        self.state().disk_dir = dir;
+        Ok(())
+    }
+
+    /// Returns the stats, waiting for the keys being computed to be counted
+    pub fn stats(&self) -> VkCacheStats {
+        let (slots, stats) = {
+            let state = self.state();
+            (state.slots.values().cloned().collect::<Vec<_>>(), state.stats.clone())
+        };
+        VkCacheStats { entries: count_loaded(slots), ..stats }
+    }
+
+    /// Drops the keys held in memory, keeping the ones on disk and the stats,
+    /// and returns how many were dropped
+    pub fn release_memory(&self) -> usize {
+        let slots = std::mem::take(&mut self.state().slots);
+        count_loaded(slots.into_values())
+    }
+
+    /// Drops the keys held in memory, keeping the ones on disk, and resets the stats
+    pub fn clear(&self) {
+        let mut state = self.state();
+        state.slots.clear();
+        state.stats = VkCacheStats::default();
+    }
+}
+
+// Takes the lock of every slot, so a key being computed is counted once it is
+// done. Must be called without the state lock, which computations take to
+// update the stats.
+fn count_loaded(slots: impl IntoIterator<Item = Slot>) -> usize {
+    slots
+        .into_iter()
+        .filter(|slot| slot.lock().unwrap_or_else(|e| e.into_inner()).is_some())
+        .count()
+}
+
+fn version_dir_name() -> String {
+    format!("noir_rs-{}", compat::NOIR_RS_VERSION)
+}
+
+fn remove_other_versions(parent: &Path) {
+    let Ok(entries) = fs::read_dir(parent) else { return };
+    let current = version_dir_name();
+    for entry in entries.flatten() {
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if name.starts_with("noir_rs-") && name != current {
+            info!("Removing verification keys of {}", name);
+            if let Err(e) = fs::remove_dir_all(entry.path()) {
+                error!("Failed to remove {:?}: {}", entry.path(), e);
+            }
+        }
+    }
+}
+
+// Files hold the SHA-256 of the key followed by the key, so that a corrupted
+// file is recomputed rather than handed to barretenberg
+fn read_checked(path: &Path) -> Option<Vec<u8>> {
+    let data = fs::read(path).ok()?;
+    if data.len() > CHECKSUM_SIZE {
+        let (checksum, vk) = data.split_at(CHECKSUM_SIZE);
+        if Sha256::digest(vk).as_slice() == checksum {
+            return Some(vk.to_vec());
+        }
+    }
+    error!("Discarding corrupted verification key {:?}", path);
+    let _ = fs::remove_file(path);
+    None
+}
+
+// Writes through a temporary file so a crash never leaves a truncated key behind
+fn write_checked(dir: &Path, name: &str, vk: &[u8]) -> Result<(), String> {
+    let mut data = Sha256::digest(vk).to_vec();
+    data.extend_from_slice(vk);
+    let tmp_path = dir.join(format!("{}.{}.tmp", name, std::process::id()));
+    fs::write(&tmp_path, data).map_err(|e| format!("Failed to write {:?}: {}", tmp_path, e))?;
+    fs::rename(&tmp_path, dir.join(name)).map_err(|e| format!("Failed to rename {:?}: {}", tmp_path, e))
+}
+
+/// Cache shared by all the JNI calls
+pub fn vk_cache() -> &'static VkCache {
+    static VK_CACHE: OnceLock<VkCache> = OnceLock::new();
+    VK_CACHE.get_or_init(VkCache::default)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_memory_and_disk_hits() {
+        let dir = std::env::temp_dir().join(format!("noir_java_vk_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let key = VkCacheKey::new("bytecode", "ultra_honk", false);
+
+        let cache = VkCache::default();
+        cache.set_disk_dir(Some(dir.clone())).unwrap();
+        assert_eq!(cache.get_or_compute(key.clone(), || Ok(vec![1, 2, 3])).unwrap(), vec![1, 2, 3]);
+        assert_eq!(cache.get_or_compute(key.clone(), || panic!("should be cached")).unwrap(), vec![1, 2, 3]);
+
+        // A new cache, as after an app restart, finds the key on disk
+        let restarted = VkCache::default();
+        restarted.set_disk_dir(Some(dir.clone())).unwrap();
+        assert_eq!(restarted.get_or_compute(key, || panic!("should be on disk")).unwrap(), vec![1, 2, 3]);
+
+        let stats = cache.stats();
+        assert_eq!((stats.memory_hits, stats.disk_hits, stats.misses, stats.entries), (1, 0, 1, 1));
+        assert_eq!(restarted.stats().disk_hits, 1);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_disk_entries_are_checked() {
+        let dir = std::env::temp_dir().join(format!("noir_java_vk_checked_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let stale = dir.join(OWNED_DIR_NAME).join("noir_rs-0.0.1");
+        fs::create_dir_all(&stale).unwrap();
+        // Not the cache's, even though it has the same name
+        let foreign = dir.join("noir_rs-0.0.1");
+        fs::create_dir_all(&foreign).unwrap();
+        let key = VkCacheKey::new("bytecode", "ultra_honk", false);
+
+        let cache = VkCache::default();
+        cache.set_disk_dir(Some(dir.clone())).unwrap();
+        assert!(!stale.exists());
+        assert!(foreign.exists());
+        cache.get_or_compute(key.clone(), || Ok(vec![1, 2, 3])).unwrap();
+
+        // Flip a byte of the key on disk
+        let path = dir.join(OWNED_DIR_NAME).join(version_dir_name()).join(key.file_name());
+        let mut data = fs::read(&path).unwrap();
+        *data.last_mut().unwrap() ^= 1;
+        fs::write(&path, data).unwrap();
+
+        let restarted = VkCache::default();
+        restarted.set_disk_dir(Some(dir.clone())).unwrap();
+        assert_eq!(restarted.get_or_compute(key, || Ok(vec![1, 2, 3])).unwrap(), vec![1, 2, 3]);
+        assert_eq!(restarted.stats().misses, 1);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_computing_a_key_does_not_block_others() {
+        let cache = VkCache::default();
+        let (started, wait) = std::sync::mpsc::channel();
+        let (release, blocked) = std::sync::mpsc::channel::<()>();
+        let cache = &cache;
+        std::thread::scope(|scope| {
+            scope.spawn(move || {
+                cache
+                    .get_or_compute(VkCacheKey::new("slow", "ultra_honk", false), || {
+                        started.send(()).unwrap();
+                        blocked.recv().unwrap();
+                        Ok(vec![1])
+                    })
+                    .unwrap();
+            });
+            wait.recv().unwrap();
+            // Would deadlock if the slow computation held the whole cache
+            assert_eq!(cache.get_or_compute(VkCacheKey::new("fast", "ultra_honk", false), || Ok(vec![2])).unwrap(), vec![2]);
+            release.send(()).unwrap();
+            // Waits for the slow key rather than skipping it
+            assert_eq!(cache.stats().entries, 2);
+        });
+        assert_eq!(cache.release_memory(), 2);
+        assert_eq!(cache.stats().entries, 0);
+    }
+
+    #[test]
+    fn test_key_depends_on_flavor() {
+        let cache = VkCache::default();
+        cache.get_or_compute(VkCacheKey::new("bytecode", "ultra_honk", false), || Ok(vec![1])).unwrap();
+        let vk = cache
+            .get_or_compute(VkCacheKey::new("bytecode", "ultra_honk_keccak", false), || Ok(vec![2]))
+            .unwrap();
+        assert_eq!(vk, vec![2]);
+        assert_eq!(cache.stats().misses, 2);
+    }
+
+    #[test]
+    fn test_errors_are_not_cached() {
+        let cache = VkCache::default();
+        let key = VkCacheKey::new("bytecode", "ultra_honk", false);
+        assert!(cache.get_or_compute(key.clone(), || Err("failed".to_owned())).is_err());
+        assert_eq!(cache.get_or_compute(key, || Ok(vec![1])).unwrap(), vec![1]);
+    }
+}
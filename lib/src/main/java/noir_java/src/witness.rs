@@ -0,0 +1,106 @@
+use log::info;
+use noir_rs::{
+    acir::native_types::{Witness, WitnessMap, WitnessStack},
+    AcirField, FieldElement,
+};
+use std::fs;
+
+use crate::logging;
+use crate::secret::{SecretWitnessMap, SecretWitnessStack, Zeroizing};
+
+/// Serializes a solved witness stack in the gzipped format `nargo execute` writes
+pub fn serialize_witness_stack(witness_stack: &WitnessStack<FieldElement>) -> Result<Zeroizing<Vec<u8>>, String> {
+    witness_stack
+        .serialize()
+        .map(Zeroizing::new)
+        .map_err(|e| format!("Failed to serialize witness stack: {:?}", e))
+}
+
+pub fn deserialize_witness_stack(buffer: &[u8]) -> Result<SecretWitnessStack, String> {
+    WitnessStack::try_from(buffer)
+        .map(SecretWitnessStack::new)
+        .map_err(|e| format!("Failed to deserialize witness stack: {:?}", e))
+}
+
+/// Returns the witness of the main function, which is the last one pushed on the stack
+pub fn main_witness(witness_stack: &WitnessStack<FieldElement>) -> Result<SecretWitnessMap, String> {
+    witness_stack
+        .peek()
+        .map(|item| SecretWitnessMap::new(item.witness.clone()))
+        .ok_or_else(|| "Witness stack is empty".to_owned())
+}
+
+/// Returns the indices and hex values of the requested witnesses, or of the
+/// whole map if `requested` is `None`, sorted by index
+pub fn select_witnesses(
+    witness_map: &WitnessMap<FieldElement>,
+    requested: Option<&[u32]>,
+) -> Result<(Vec<u32>, Zeroizing<Vec<String>>), String> {
+    let mut indices = Vec::new();
+    let mut values = Zeroizing::new(Vec::new());
+    match requested {
+        Some(requested) => {
+            let mut requested = requested.to_vec();
+            requested.sort_unstable();
+            requested.dedup();
+            for index in requested {
+                let value = witness_map
+                    .get(&Witness(index))
+                    .ok_or_else(|| format!("Witness {} not found in the solved witness", logging::secret(index)))?;
+                indices.push(index);
+                values.push(format!("0x{}", value.to_hex()));
+            }
+        }
+        None => {
+            for (witness, value) in witness_map {
+                indices.push(witness.0);
+                values.push(format!("0x{}", value.to_hex()));
+            }
+        }
+    }
+    Ok((indices, values))
+}
+
+/// Reads a `.gz` witness file as written by `nargo execute`
+pub fn read_witness_file(path: &str) -> Result<SecretWitnessStack, String> {
+    let buffer = Zeroizing::new(fs::read(path).map_err(|e| format!("Failed to read witness file {}: {}", path, e))?);
+    let witness_stack = deserialize_witness_stack(&buffer)?;
+    info!("Read witness stack of {} functions from {}", witness_stack.length(), path);
+    Ok(witness_stack)
+}
+
+/// Writes a witness stack to a `.gz` file that nargo and bb can read
+pub fn write_witness_file(path: &str, witness_stack: &WitnessStack<FieldElement>) -> Result<(), String> {
+    let buffer = serialize_witness_stack(witness_stack)?;
+    fs::write(path, &*buffer).map_err(|e| format!("Failed to write witness file {}: {}", path, e))?;
+    info!("Wrote witness stack of {} functions to {}", witness_stack.length(), path);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sparse_map() -> WitnessMap<FieldElement> {
+        let mut witness_map = WitnessMap::new();
+        for (index, value) in [(7, "0x3"), (2, "0x1"), (12, "0x4")] {
+            witness_map.insert(Witness(index), FieldElement::try_from_str(value).unwrap());
+        }
+        witness_map
+    }
+
+    #[test]
+    fn test_select_all_witnesses() {
+        let (indices, values) = select_witnesses(&sparse_map(), None).unwrap();
+        assert_eq!(indices, vec![2, 7, 12]);
+        assert_eq!(values[1], format!("0x{:0>64}", "3"));
+    }
+
+    #[test]
+    fn test_select_requested_witnesses() {
+        let (indices, values) = select_witnesses(&sparse_map(), Some(&[12, 2, 12])).unwrap();
+        assert_eq!(indices, vec![2, 12]);
+        assert_eq!(*values, vec![format!("0x{:0>64}", "1"), format!("0x{:0>64}", "4")]);
+        assert!(select_witnesses(&sparse_map(), Some(&[3])).is_err());
+    }
+}
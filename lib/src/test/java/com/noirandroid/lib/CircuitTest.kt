@@ -0,0 +1,42 @@
+package com.noirandroid.lib
+
+import org.junit.Assert.assertEquals
+import org.junit.Assert.assertThrows
+import org.junit.Test
+
+class CircuitTest {
+    private fun type(kind: String, elements: List<Type>? = null, length: Int? = null, type: Type? = null) =
+        Type(kind, null, type, null, elements, length, null, null)
+
+    private fun circuit(vararg parameters: Parameter): Circuit {
+        val abi = Abi(parameters.toList(), null, emptyMap(), emptyMap(), emptyList())
+        return Circuit("", CircuitManifest(null, "0", abi, "", false))
+    }
+
+    @Test
+    fun tupleElementsAreFlattenedInOrder() {
+        val pair = type("tuple", listOf(type("field"), type("array", length = 2, type = type("field"))))
+        val circuit = circuit(
+            Parameter("a", type("field"), "private"),
+            Parameter("pair", pair, "private"),
+            Parameter("flag", type("boolean"), "public")
+        )
+
+        val witness = circuit.generateWitnessMap(
+            mapOf("a" to 1.0, "pair" to listOf("0x2", listOf(3.0, 4.0)), "flag" to true),
+            circuit.manifest.abi.parameters,
+            0
+        )
+
+        assertEquals(mapOf("0" to "0x1", "1" to "0x2", "2" to "0x3", "3" to "0x4", "4" to "0x1"), witness)
+    }
+
+    @Test
+    fun tupleOfTheWrongSizeIsRejected() {
+        val circuit = circuit(Parameter("pair", type("tuple", listOf(type("field"), type("field"))), "private"))
+
+        assertThrows(IllegalArgumentException::class.java) {
+            circuit.generateWitnessMap(mapOf("pair" to listOf(1.0)), circuit.manifest.abi.parameters, 0)
+        }
+    }
+}